@@ -8,6 +8,24 @@ mod m20221215_153723_create_wallet_transaction_table;
 mod m20221215_153841_create_fiat_currency_table;
 mod m20221215_153911_create_payment_table;
 mod m20221215_153937_create_user_transaction_table;
+mod m20230110_101200_add_webhook_secret_to_user;
+mod m20230110_101300_create_webhook_delivery_table;
+mod m20230112_093000_add_required_confirmations_to_network;
+mod m20230114_150000_add_token_to_crypto_currency;
+mod m20230117_110000_add_reservation_to_wallet;
+mod m20230119_143000_add_hd_derivation;
+mod m20230124_100000_create_refund_table;
+mod m20230126_120000_add_idempotency_to_payment;
+mod m20230128_090000_create_api_key_table;
+mod m20230130_100000_create_refresh_token_table;
+mod m20230201_090000_create_payment_quote_table;
+mod m20230203_100000_create_payment_policy_table;
+mod m20230205_110000_add_details_to_wallet_transaction;
+mod m20230207_090000_add_reconciliation_to_payment;
+mod m20230209_100000_add_kind_to_network;
+mod m20230211_090000_create_sweep_table;
+mod m20230213_090000_add_encrypted_key_to_wallet;
+mod m20230215_090000_create_withdrawal_table;
 
 pub struct Migrator;
 
@@ -23,6 +41,24 @@ impl MigratorTrait for Migrator {
             Box::new(m20221215_153841_create_fiat_currency_table::Migration),
             Box::new(m20221215_153911_create_payment_table::Migration),
             Box::new(m20221215_153937_create_user_transaction_table::Migration),
+            Box::new(m20230110_101200_add_webhook_secret_to_user::Migration),
+            Box::new(m20230110_101300_create_webhook_delivery_table::Migration),
+            Box::new(m20230112_093000_add_required_confirmations_to_network::Migration),
+            Box::new(m20230114_150000_add_token_to_crypto_currency::Migration),
+            Box::new(m20230117_110000_add_reservation_to_wallet::Migration),
+            Box::new(m20230119_143000_add_hd_derivation::Migration),
+            Box::new(m20230124_100000_create_refund_table::Migration),
+            Box::new(m20230126_120000_add_idempotency_to_payment::Migration),
+            Box::new(m20230128_090000_create_api_key_table::Migration),
+            Box::new(m20230130_100000_create_refresh_token_table::Migration),
+            Box::new(m20230201_090000_create_payment_quote_table::Migration),
+            Box::new(m20230203_100000_create_payment_policy_table::Migration),
+            Box::new(m20230205_110000_add_details_to_wallet_transaction::Migration),
+            Box::new(m20230207_090000_add_reconciliation_to_payment::Migration),
+            Box::new(m20230209_100000_add_kind_to_network::Migration),
+            Box::new(m20230211_090000_create_sweep_table::Migration),
+            Box::new(m20230213_090000_add_encrypted_key_to_wallet::Migration),
+            Box::new(m20230215_090000_create_withdrawal_table::Migration),
         ]
     }
 }
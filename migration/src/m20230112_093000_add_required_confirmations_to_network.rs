@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221212_153800_create_network_table::Network;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .add_column(
+                        ColumnDef::new(NetworkConfirmations::RequiredConfirmations)
+                            .integer()
+                            .not_null()
+                            .default(12),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .drop_column(NetworkConfirmations::RequiredConfirmations)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum NetworkConfirmations {
+    RequiredConfirmations,
+}
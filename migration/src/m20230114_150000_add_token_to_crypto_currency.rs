@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221212_153837_create_crypto_currency_table::CryptoCurrency;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CryptoCurrency::Table)
+                    .add_column(ColumnDef::new(CryptoCurrencyToken::ContractAddress).string())
+                    .add_column(
+                        ColumnDef::new(CryptoCurrencyToken::Decimals)
+                            .integer()
+                            .not_null()
+                            .default(18),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CryptoCurrency::Table)
+                    .drop_column(CryptoCurrencyToken::ContractAddress)
+                    .drop_column(CryptoCurrencyToken::Decimals)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum CryptoCurrencyToken {
+    ContractAddress,
+    Decimals,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::m20221212_153934_create_wallet_table::Wallet;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Wallet::Table)
+                    .add_column(ColumnDef::new(WalletReservation::PaymentId).integer())
+                    .add_column(ColumnDef::new(WalletReservation::ReservedAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        // busy wallets are held by the last payment they were given to, so that they can be freed
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                r#"UPDATE "wallet"
+                    SET "payment_id" = "holder"."id", "reserved_at" = "holder"."created_at"
+                    FROM (
+                        SELECT DISTINCT ON ("dest_wallet_id") "id", "dest_wallet_id", "created_at"
+                        FROM "payment"
+                        WHERE "dest_wallet_id" IS NOT NULL
+                        ORDER BY "dest_wallet_id", "id" DESC
+                    ) AS "holder"
+                    WHERE "holder"."dest_wallet_id" = "wallet"."id" AND "wallet"."status" = 'BUSY'"#
+                    .to_owned(),
+            ))
+            .await
+            .map(|_| ())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Wallet::Table)
+                    .drop_column(WalletReservation::PaymentId)
+                    .drop_column(WalletReservation::ReservedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum WalletReservation {
+    PaymentId,
+    ReservedAt,
+}
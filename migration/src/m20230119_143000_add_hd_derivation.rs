@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221212_153800_create_network_table::Network, m20221212_153934_create_wallet_table::Wallet,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .add_column(ColumnDef::new(NetworkDerivation::ExtendedPublicKey).string())
+                    .add_column(
+                        ColumnDef::new(NetworkDerivation::NextDerivationIndex)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Wallet::Table)
+                    .add_column(ColumnDef::new(WalletDerivation::DerivationPath).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Wallet::Table)
+                    .drop_column(WalletDerivation::DerivationPath)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .drop_column(NetworkDerivation::ExtendedPublicKey)
+                    .drop_column(NetworkDerivation::NextDerivationIndex)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum NetworkDerivation {
+    ExtendedPublicKey,
+    NextDerivationIndex,
+}
+
+#[derive(Iden)]
+pub enum WalletDerivation {
+    DerivationPath,
+}
@@ -0,0 +1,103 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221212_153837_create_crypto_currency_table::CryptoCurrency,
+    m20221215_153911_create_payment_table::Payment,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Payment::Table)
+                    .add_column(ColumnDef::new(PaymentPaid::PaidCryptoAmount).decimal())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Refund::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Refund::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Refund::PaymentId).integer().not_null())
+                    .col(
+                        ColumnDef::new(Refund::CryptoCurrencyId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Refund::Amount).decimal().not_null())
+                    .col(
+                        ColumnDef::new(Refund::DestinationAddress)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Refund::Reason).string().not_null())
+                    .col(ColumnDef::new(Refund::Status).string().not_null())
+                    .col(ColumnDef::new(Refund::TransactionHash).string())
+                    .col(ColumnDef::new(Refund::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(Refund::ApprovedAt).date_time())
+                    .col(ColumnDef::new(Refund::ProcessedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Refund::Table, Refund::PaymentId)
+                            .to(Payment::Table, Payment::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Refund::Table, Refund::CryptoCurrencyId)
+                            .to(CryptoCurrency::Table, CryptoCurrency::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Refund::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Payment::Table)
+                    .drop_column(PaymentPaid::PaidCryptoAmount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PaymentPaid {
+    PaidCryptoAmount,
+}
+
+#[derive(Iden)]
+pub enum Refund {
+    Table,
+    Id,
+    PaymentId,
+    CryptoCurrencyId,
+    Amount,
+    DestinationAddress,
+    Reason,
+    Status,
+    TransactionHash,
+    CreatedAt,
+    ApprovedAt,
+    ProcessedAt,
+}
@@ -0,0 +1,112 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::m20221215_153911_create_payment_table::Payment;
+
+/// Payments whose seller order can't be paid again by another payment
+const UNPAYABLE_AGAIN: &str = r#""status" NOT IN ('EXPIRED', 'PARTIALLY_PAID')"#;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Payment::Table)
+                    .add_column(ColumnDef::new(PaymentIdempotency::IdempotencyKey).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_payment_user_id_idempotency_key")
+                    .table(Payment::Table)
+                    .col(Payment::UserId)
+                    .col(PaymentIdempotency::IdempotencyKey)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // orders created more than once before the index existed keep their older payments,
+        // which are left out of it
+        let duplicate_payment_ids = manager
+            .get_connection()
+            .query_all(Statement::from_string(
+                manager.get_database_backend(),
+                format!(
+                    r#"SELECT "id" FROM (
+                        SELECT "id", ROW_NUMBER() OVER (
+                            PARTITION BY "user_id", "seller_order_id" ORDER BY "id" DESC
+                        ) AS "rank"
+                        FROM "payment"
+                        WHERE {UNPAYABLE_AGAIN}
+                    ) AS "ranked"
+                    WHERE "rank" > 1"#
+                ),
+            ))
+            .await?
+            .iter()
+            .map(|row| row.try_get::<i32>("", "id").map(|id| id.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut predicate = UNPAYABLE_AGAIN.to_owned();
+        if !duplicate_payment_ids.is_empty() {
+            predicate += &format!(r#" AND "id" NOT IN ({})"#, duplicate_payment_ids.join(", "));
+        }
+
+        // sea-query can't build partial indexes, expired and partially paid orders may be paid
+        // again, see `payment_service::find_existing`
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                format!(
+                    r#"CREATE UNIQUE INDEX "idx_payment_user_id_seller_order_id"
+                    ON "payment" ("user_id", "seller_order_id")
+                    WHERE {predicate}"#
+                ),
+            ))
+            .await
+            .map(|_| ())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_payment_user_id_seller_order_id")
+                    .table(Payment::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_payment_user_id_idempotency_key")
+                    .table(Payment::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Payment::Table)
+                    .drop_column(PaymentIdempotency::IdempotencyKey)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PaymentIdempotency {
+    IdempotencyKey,
+}
@@ -0,0 +1,99 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221212_153837_create_crypto_currency_table::CryptoCurrency,
+    m20221215_153911_create_payment_table::Payment,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PaymentQuote::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PaymentQuote::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PaymentQuote::PaymentId).integer().not_null())
+                    .col(
+                        ColumnDef::new(PaymentQuote::CryptoCurrencyId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PaymentQuote::Rate).decimal().not_null())
+                    .col(ColumnDef::new(PaymentQuote::Source).string().not_null())
+                    .col(
+                        ColumnDef::new(PaymentQuote::SpreadPercent)
+                            .decimal()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentQuote::CryptoAmount)
+                            .decimal()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentQuote::FetchedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentQuote::ValidUntil)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PaymentQuote::Table, PaymentQuote::PaymentId)
+                            .to(Payment::Table, Payment::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PaymentQuote::Table, PaymentQuote::CryptoCurrencyId)
+                            .to(CryptoCurrency::Table, CryptoCurrency::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_payment_quote_payment_id")
+                    .table(PaymentQuote::Table)
+                    .col(PaymentQuote::PaymentId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PaymentQuote::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PaymentQuote {
+    Table,
+    Id,
+    PaymentId,
+    CryptoCurrencyId,
+    Rate,
+    Source,
+    SpreadPercent,
+    CryptoAmount,
+    FetchedAt,
+    ValidUntil,
+}
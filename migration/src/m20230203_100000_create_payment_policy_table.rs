@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::{
+    m20221208_222429_create_user_table::User,
+    m20221212_153837_create_crypto_currency_table::CryptoCurrency,
+    m20221215_153911_create_payment_table::Payment,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Payment::Table)
+                    .add_column(ColumnDef::new(PaymentSettlement::SettledCryptoAmount).decimal())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PaymentPolicy::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PaymentPolicy::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PaymentPolicy::UserId).integer().not_null())
+                    .col(ColumnDef::new(PaymentPolicy::CryptoCurrencyId).integer())
+                    .col(ColumnDef::new(PaymentPolicy::UnderpaymentToleranceAmount).decimal())
+                    .col(ColumnDef::new(PaymentPolicy::UnderpaymentTolerancePercent).decimal())
+                    .col(
+                        ColumnDef::new(PaymentPolicy::OverpaymentAction)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PaymentPolicy::CreatedAt)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PaymentPolicy::UpdatedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PaymentPolicy::Table, PaymentPolicy::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PaymentPolicy::Table, PaymentPolicy::CryptoCurrencyId)
+                            .to(CryptoCurrency::Table, CryptoCurrency::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        // sea-query can't build partial indexes, a merchant has a single default policy and a
+        // single policy per crypto currency
+        for sql in [
+            r#"CREATE UNIQUE INDEX "idx_payment_policy_user_id"
+                ON "payment_policy" ("user_id")
+                WHERE "crypto_currency_id" IS NULL"#,
+            r#"CREATE UNIQUE INDEX "idx_payment_policy_user_id_crypto_currency_id"
+                ON "payment_policy" ("user_id", "crypto_currency_id")
+                WHERE "crypto_currency_id" IS NOT NULL"#,
+            // partially paid orders may be paid again, like expired ones
+            r#"DROP INDEX "idx_payment_user_id_seller_order_id""#,
+            r#"CREATE UNIQUE INDEX "idx_payment_user_id_seller_order_id"
+                ON "payment" ("user_id", "seller_order_id")
+                WHERE "status" NOT IN ('EXPIRED', 'PARTIALLY_PAID')"#,
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for sql in [
+            r#"DROP INDEX "idx_payment_user_id_seller_order_id""#,
+            r#"CREATE UNIQUE INDEX "idx_payment_user_id_seller_order_id"
+                ON "payment" ("user_id", "seller_order_id")
+                WHERE "status" <> 'EXPIRED'"#,
+        ] {
+            db.execute(Statement::from_string(backend, sql.to_owned()))
+                .await?;
+        }
+
+        manager
+            .drop_table(Table::drop().table(PaymentPolicy::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Payment::Table)
+                    .drop_column(PaymentSettlement::SettledCryptoAmount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PaymentSettlement {
+    SettledCryptoAmount,
+}
+
+#[derive(Iden)]
+pub enum PaymentPolicy {
+    Table,
+    Id,
+    UserId,
+    CryptoCurrencyId,
+    UnderpaymentToleranceAmount,
+    UnderpaymentTolerancePercent,
+    OverpaymentAction,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,198 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, Statement};
+
+use crate::{
+    m20221212_153800_create_network_table::Network,
+    m20221212_153837_create_crypto_currency_table::CryptoCurrency,
+    m20221215_153723_create_wallet_transaction_table::WalletTransaction,
+    m20221215_153911_create_payment_table::Payment,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WalletTransaction::Table)
+                    .add_column(ColumnDef::new(WalletTransactionDetails::NetworkId).integer())
+                    .add_column(ColumnDef::new(WalletTransactionDetails::PaymentId).integer())
+                    .add_column(
+                        ColumnDef::new(WalletTransactionDetails::CryptoCurrencyId).integer(),
+                    )
+                    .add_column(ColumnDef::new(WalletTransactionDetails::FromAddress).string())
+                    .add_column(ColumnDef::new(WalletTransactionDetails::Value).decimal())
+                    .add_column(ColumnDef::new(WalletTransactionDetails::BlockNumber).big_integer())
+                    .add_column(ColumnDef::new(WalletTransactionDetails::BlockHash).string())
+                    .add_column(
+                        ColumnDef::new(WalletTransactionDetails::Confirmations)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(WalletTransactionDetails::Status)
+                            .string()
+                            .not_null()
+                            .default("PENDING"),
+                    )
+                    .add_column(ColumnDef::new(WalletTransactionDetails::UpdatedAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        // earlier transactions are on the network of their wallet, their payment is unknown
+        // since wallets are reused
+        manager
+            .get_connection()
+            .execute(Statement::from_string(
+                manager.get_database_backend(),
+                r#"UPDATE "wallet_transaction" SET "network_id" = "wallet"."network_id"
+                    FROM "wallet" WHERE "wallet"."id" = "wallet_transaction"."wallet_id""#
+                    .to_owned(),
+            ))
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WalletTransaction::Table)
+                    .modify_column(
+                        ColumnDef::new(WalletTransactionDetails::NetworkId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_wallet_transaction_network_id")
+                    .from(
+                        WalletTransaction::Table,
+                        WalletTransactionDetails::NetworkId,
+                    )
+                    .to(Network::Table, Network::Id)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_wallet_transaction_payment_id")
+                    .from(
+                        WalletTransaction::Table,
+                        WalletTransactionDetails::PaymentId,
+                    )
+                    .to(Payment::Table, Payment::Id)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_wallet_transaction_crypto_currency_id")
+                    .from(
+                        WalletTransaction::Table,
+                        WalletTransactionDetails::CryptoCurrencyId,
+                    )
+                    .to(CryptoCurrency::Table, CryptoCurrency::Id)
+                    .to_owned(),
+            )
+            .await?;
+
+        // a transaction may pay several wallets, each of them gets its own row
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wallet_transaction_network_id_hash_wallet_id")
+                    .table(WalletTransaction::Table)
+                    .col(WalletTransactionDetails::NetworkId)
+                    .col(WalletTransaction::Hash)
+                    .col(WalletTransaction::WalletId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wallet_transaction_payment_id")
+                    .table(WalletTransaction::Table)
+                    .col(WalletTransactionDetails::PaymentId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for index in [
+            "idx_wallet_transaction_payment_id",
+            "idx_wallet_transaction_network_id_hash_wallet_id",
+        ] {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .name(index)
+                        .table(WalletTransaction::Table)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        for foreign_key in [
+            "fk_wallet_transaction_crypto_currency_id",
+            "fk_wallet_transaction_payment_id",
+            "fk_wallet_transaction_network_id",
+        ] {
+            manager
+                .drop_foreign_key(
+                    ForeignKey::drop()
+                        .name(foreign_key)
+                        .table(WalletTransaction::Table)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(WalletTransaction::Table)
+                    .drop_column(WalletTransactionDetails::NetworkId)
+                    .drop_column(WalletTransactionDetails::PaymentId)
+                    .drop_column(WalletTransactionDetails::CryptoCurrencyId)
+                    .drop_column(WalletTransactionDetails::FromAddress)
+                    .drop_column(WalletTransactionDetails::Value)
+                    .drop_column(WalletTransactionDetails::BlockNumber)
+                    .drop_column(WalletTransactionDetails::BlockHash)
+                    .drop_column(WalletTransactionDetails::Confirmations)
+                    .drop_column(WalletTransactionDetails::Status)
+                    .drop_column(WalletTransactionDetails::UpdatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum WalletTransactionDetails {
+    NetworkId,
+    PaymentId,
+    CryptoCurrencyId,
+    FromAddress,
+    Value,
+    BlockNumber,
+    BlockHash,
+    Confirmations,
+    Status,
+    UpdatedAt,
+}
@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20221212_153800_create_network_table::Network;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .add_column(
+                        ColumnDef::new(NetworkKind::Kind)
+                            .string()
+                            .not_null()
+                            .default("EVM"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .drop_column(NetworkKind::Kind)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum NetworkKind {
+    Kind,
+}
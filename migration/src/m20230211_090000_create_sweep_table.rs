@@ -0,0 +1,143 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221212_153800_create_network_table::Network,
+    m20221212_153837_create_crypto_currency_table::CryptoCurrency,
+    m20221212_153934_create_wallet_table::Wallet,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .add_column(ColumnDef::new(NetworkTreasury::TreasuryAddress).string())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Wallet::Table)
+                    .add_column(ColumnDef::new(WalletSweep::SweepRequestedAt).date_time())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Sweep::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Sweep::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Sweep::NetworkId).integer().not_null())
+                    .col(ColumnDef::new(Sweep::WalletId).integer().not_null())
+                    .col(ColumnDef::new(Sweep::CryptoCurrencyId).integer())
+                    .col(ColumnDef::new(Sweep::Kind).string().not_null())
+                    .col(ColumnDef::new(Sweep::Hash).string().not_null())
+                    .col(ColumnDef::new(Sweep::FromAddress).string().not_null())
+                    .col(ColumnDef::new(Sweep::ToAddress).string().not_null())
+                    .col(ColumnDef::new(Sweep::Amount).decimal().not_null())
+                    .col(ColumnDef::new(Sweep::Fee).decimal())
+                    .col(
+                        ColumnDef::new(Sweep::Status)
+                            .string()
+                            .not_null()
+                            .default("PENDING"),
+                    )
+                    .col(ColumnDef::new(Sweep::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(Sweep::UpdatedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Sweep::Table, Sweep::NetworkId)
+                            .to(Network::Table, Network::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Sweep::Table, Sweep::WalletId)
+                            .to(Wallet::Table, Wallet::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Sweep::Table, Sweep::CryptoCurrencyId)
+                            .to(CryptoCurrency::Table, CryptoCurrency::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_sweep_wallet_id")
+                    .table(Sweep::Table)
+                    .col(Sweep::WalletId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Sweep::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Wallet::Table)
+                    .drop_column(WalletSweep::SweepRequestedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Network::Table)
+                    .drop_column(NetworkTreasury::TreasuryAddress)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum NetworkTreasury {
+    TreasuryAddress,
+}
+
+#[derive(Iden)]
+pub enum WalletSweep {
+    SweepRequestedAt,
+}
+
+#[derive(Iden)]
+pub enum Sweep {
+    Table,
+    Id,
+    NetworkId,
+    WalletId,
+    CryptoCurrencyId,
+    Kind,
+    Hash,
+    FromAddress,
+    ToAddress,
+    Amount,
+    Fee,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::prelude::*;
+
+use crate::{
+    m20221208_222429_create_user_table::User,
+    m20221212_153837_create_crypto_currency_table::CryptoCurrency,
+    m20221215_153841_create_fiat_currency_table::FiatCurrency,
+    m20221215_153937_create_user_transaction_table::UserTransaction,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Withdrawal::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Withdrawal::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Withdrawal::UserId).integer().not_null())
+                    .col(
+                        ColumnDef::new(Withdrawal::FiatCurrencyId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Withdrawal::Amount).decimal().not_null())
+                    .col(
+                        ColumnDef::new(Withdrawal::DestinationType)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Withdrawal::Destination).string().not_null())
+                    .col(ColumnDef::new(Withdrawal::DestinationReference).string())
+                    .col(ColumnDef::new(Withdrawal::CryptoCurrencyId).integer())
+                    .col(
+                        ColumnDef::new(Withdrawal::Status)
+                            .string()
+                            .not_null()
+                            .default("PENDING"),
+                    )
+                    .col(ColumnDef::new(Withdrawal::PayoutReference).string())
+                    .col(ColumnDef::new(Withdrawal::RejectionReason).string())
+                    .col(
+                        ColumnDef::new(Withdrawal::UserTransactionId)
+                            .integer()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Withdrawal::CreatedAt).date_time().not_null())
+                    .col(ColumnDef::new(Withdrawal::ApprovedAt).date_time())
+                    .col(ColumnDef::new(Withdrawal::SubmittedAt).date_time())
+                    .col(ColumnDef::new(Withdrawal::ProcessedAt).date_time())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Withdrawal::Table, Withdrawal::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Withdrawal::Table, Withdrawal::FiatCurrencyId)
+                            .to(FiatCurrency::Table, FiatCurrency::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Withdrawal::Table, Withdrawal::CryptoCurrencyId)
+                            .to(CryptoCurrency::Table, CryptoCurrency::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Withdrawal::Table, Withdrawal::UserTransactionId)
+                            .to(UserTransaction::Table, UserTransaction::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_withdrawal_user_id_status")
+                    .table(Withdrawal::Table)
+                    .col(Withdrawal::UserId)
+                    .col(Withdrawal::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Withdrawal::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Withdrawal {
+    Table,
+    Id,
+    UserId,
+    FiatCurrencyId,
+    Amount,
+    DestinationType,
+    Destination,
+    DestinationReference,
+    CryptoCurrencyId,
+    Status,
+    PayoutReference,
+    RejectionReason,
+    UserTransactionId,
+    CreatedAt,
+    ApprovedAt,
+    SubmittedAt,
+    ProcessedAt,
+}
@@ -1,8 +1,19 @@
+use crate::errors::{PriceOracleError, SignerError};
+use crate::services::payment_quote_service::QuotePolicy;
+use crate::services::payout_provider::{MockPayoutProvider, PayoutProvider, PayoutProviderKind};
+use crate::services::price_oracle::{
+    HttpJsonPriceOracle, KucoinPriceOracle, PriceOracle, PriceOracleKind, StaticPriceOracle,
+};
+use crate::services::signer::{LocalSigner, WalletKeys};
+use crate::services::web3_service;
+use chrono::Duration;
 use config::{Config, ConfigError};
 use jsonwebtoken::{DecodingKey, EncodingKey};
 use migration::DbErr;
+use sea_orm::prelude::Decimal;
 use sea_orm::{ConnectOptions, Database, DbConn};
 use serde::Deserialize;
+use std::sync::Arc;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct AppConfig {
@@ -10,9 +21,23 @@ pub struct AppConfig {
     pub port: u16,
     pub database_url: String,
     pub jwt_secret: String,
-    pub jwt_validity_duration_in_days: i64,
+    pub jwt_validity_duration_in_minutes: i64,
+    pub refresh_token_validity_duration_in_days: i64,
     pub payment_waiting_duration_in_minutes: i64,
     pub payment_gateway_base_url: String,
+    pub payment_quote_validity_duration_in_minutes: i64,
+    #[serde(default)]
+    pub payment_quote_spread_percent: Decimal,
+    #[serde(default)]
+    pub price_oracle: PriceOracleKind,
+    pub price_oracle_static_prices: Option<String>,
+    pub price_oracle_http_url: Option<String>,
+    pub price_oracle_http_price_pointer: Option<String>,
+    pub wallet_key_master_secret: Option<String>,
+    pub wallet_extended_private_key: Option<String>,
+    pub sweep_gas_private_key: Option<String>,
+    #[serde(default)]
+    pub payout_provider: PayoutProviderKind,
 }
 
 impl AppConfig {
@@ -24,11 +49,32 @@ impl AppConfig {
         let config = Config::builder()
             .add_source(config::Environment::default())
             .build()?;
+        let config = Self::with_legacy_jwt_validity(config)?;
 
         let app_config: AppConfig = config.try_deserialize()?;
         Ok(app_config)
     }
 
+    /// Fall back to the former JWT_VALIDITY_DURATION_IN_DAYS when the validity is not set in minutes
+    fn with_legacy_jwt_validity(config: Config) -> Result<Config, ConfigError> {
+        if config.get_int("jwt_validity_duration_in_minutes").is_ok() {
+            return Ok(config);
+        }
+
+        match config.get_int("jwt_validity_duration_in_days") {
+            Ok(days) => {
+                log::warn!(
+                    "JWT_VALIDITY_DURATION_IN_DAYS is deprecated, set JWT_VALIDITY_DURATION_IN_MINUTES instead"
+                );
+                Config::builder()
+                    .add_source(config)
+                    .set_override("jwt_validity_duration_in_minutes", days * 24 * 60)?
+                    .build()
+            }
+            Err(_) => Ok(config),
+        }
+    }
+
     pub async fn setup_db(&self) -> Result<DbConn, DbErr> {
         log::info!("Setup database");
 
@@ -45,4 +91,75 @@ impl AppConfig {
     pub async fn create_jwt_decoding_key(&self) -> DecodingKey {
         DecodingKey::from_secret(self.jwt_secret.as_ref())
     }
+
+    pub fn quote_policy(&self) -> QuotePolicy {
+        QuotePolicy {
+            validity_duration: Duration::minutes(self.payment_quote_validity_duration_in_minutes),
+            spread_percent: self.payment_quote_spread_percent,
+        }
+    }
+
+    pub fn create_price_oracle(&self) -> Result<Arc<dyn PriceOracle>, PriceOracleError> {
+        log::info!("Setup {:?} price oracle", self.price_oracle);
+
+        let required = |value: &Option<String>, name: &str| {
+            value
+                .clone()
+                .ok_or_else(|| PriceOracleError::Misconfigured(format!("{name} is not set")))
+        };
+
+        let price_oracle: Arc<dyn PriceOracle> = match self.price_oracle {
+            PriceOracleKind::Kucoin => Arc::new(KucoinPriceOracle::default()),
+            PriceOracleKind::Static => Arc::new(StaticPriceOracle::from_table(&required(
+                &self.price_oracle_static_prices,
+                "PRICE_ORACLE_STATIC_PRICES",
+            )?)?),
+            PriceOracleKind::HttpJson => Arc::new(HttpJsonPriceOracle::new(
+                required(&self.price_oracle_http_url, "PRICE_ORACLE_HTTP_URL")?,
+                required(
+                    &self.price_oracle_http_price_pointer,
+                    "PRICE_ORACLE_HTTP_PRICE_POINTER",
+                )?,
+            )),
+        };
+
+        Ok(price_oracle)
+    }
+
+    pub fn create_payout_provider(&self) -> Arc<dyn PayoutProvider> {
+        log::info!("Setup {:?} payout provider", self.payout_provider);
+
+        match self.payout_provider {
+            PayoutProviderKind::Mock => Arc::new(MockPayoutProvider),
+        }
+    }
+
+    pub fn create_wallet_keys(&self) -> Result<WalletKeys, SignerError> {
+        let extended_private_key = self
+            .wallet_extended_private_key
+            .as_ref()
+            .map(|extended_private_key| {
+                web3_service::parse_extended_private_key(extended_private_key).map_err(|err| {
+                    SignerError::Misconfigured(format!("WALLET_EXTENDED_PRIVATE_KEY: {err}"))
+                })
+            })
+            .transpose()?;
+
+        Ok(WalletKeys::new(
+            self.wallet_key_master_secret.clone(),
+            extended_private_key,
+        ))
+    }
+
+    /// Signer paying the gas of sweeps, or none when sweeping is not configured
+    pub fn create_sweep_gas_signer(&self) -> Result<Option<LocalSigner>, SignerError> {
+        self.sweep_gas_private_key
+            .as_ref()
+            .map(|gas_private_key| {
+                LocalSigner::from_hex(gas_private_key).map_err(|_| {
+                    SignerError::Misconfigured("SWEEP_GAS_PRIVATE_KEY is invalid".to_owned())
+                })
+            })
+            .transpose()
+    }
 }
@@ -11,6 +11,8 @@ pub struct Model {
     pub name: String,
     pub symbol: String,
     pub network_id: i32,
+    pub contract_address: Option<String>,
+    pub decimals: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -25,6 +27,18 @@ pub enum Relation {
     Network,
     #[sea_orm(has_many = "super::payment::Entity")]
     Payment,
+    #[sea_orm(has_many = "super::payment_policy::Entity")]
+    PaymentPolicy,
+    #[sea_orm(has_many = "super::payment_quote::Entity")]
+    PaymentQuote,
+    #[sea_orm(has_many = "super::refund::Entity")]
+    Refund,
+    #[sea_orm(has_many = "super::sweep::Entity")]
+    Sweep,
+    #[sea_orm(has_many = "super::wallet_transaction::Entity")]
+    WalletTransaction,
+    #[sea_orm(has_many = "super::withdrawal::Entity")]
+    Withdrawal,
 }
 
 impl Related<super::network::Entity> for Entity {
@@ -39,4 +53,40 @@ impl Related<super::payment::Entity> for Entity {
     }
 }
 
+impl Related<super::payment_policy::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PaymentPolicy.def()
+    }
+}
+
+impl Related<super::payment_quote::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PaymentQuote.def()
+    }
+}
+
+impl Related<super::refund::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Refund.def()
+    }
+}
+
+impl Related<super::sweep::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sweep.def()
+    }
+}
+
+impl Related<super::wallet_transaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WalletTransaction.def()
+    }
+}
+
+impl Related<super::withdrawal::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Withdrawal.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -18,6 +18,8 @@ pub enum Relation {
     Payment,
     #[sea_orm(has_many = "super::user_transaction::Entity")]
     UserTransaction,
+    #[sea_orm(has_many = "super::withdrawal::Entity")]
+    Withdrawal,
 }
 
 impl Related<super::payment::Entity> for Entity {
@@ -32,4 +34,10 @@ impl Related<super::user_transaction::Entity> for Entity {
     }
 }
 
+impl Related<super::withdrawal::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Withdrawal.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -2,11 +2,19 @@
 
 pub mod prelude;
 
+pub mod api_key;
 pub mod crypto_currency;
 pub mod fiat_currency;
 pub mod network;
 pub mod payment;
+pub mod payment_policy;
+pub mod payment_quote;
+pub mod refresh_token;
+pub mod refund;
+pub mod sweep;
 pub mod user;
 pub mod user_transaction;
 pub mod wallet;
 pub mod wallet_transaction;
+pub mod webhook_delivery;
+pub mod withdrawal;
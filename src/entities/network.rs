@@ -1,7 +1,22 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.5
 
 use sea_orm::entity::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+/// Chain family of a network, which decides how its wallets are watched
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum NetworkKind {
+    /// Ethereum and compatible chains, through their JSON-RPC endpoints
+    #[default]
+    #[sea_orm(string_value = "EVM")]
+    Evm,
+    /// Bitcoin, through the JSON-RPC interface of a bitcoind node
+    #[sea_orm(string_value = "BITCOIN")]
+    Bitcoin,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "network")]
@@ -13,14 +28,26 @@ pub struct Model {
     pub http_address_url: String,
     #[serde(skip_serializing)]
     pub websocket_address_url: String,
+    pub required_confirmations: i32,
+    #[serde(skip_serializing)]
+    pub extended_public_key: Option<String>,
+    #[serde(skip_serializing)]
+    pub next_derivation_index: i32,
+    pub kind: NetworkKind,
+    /// Where funds received by the wallets of the network are swept to
+    pub treasury_address: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::crypto_currency::Entity")]
     CryptoCurrency,
+    #[sea_orm(has_many = "super::sweep::Entity")]
+    Sweep,
     #[sea_orm(has_many = "super::wallet::Entity")]
     Wallet,
+    #[sea_orm(has_many = "super::wallet_transaction::Entity")]
+    WalletTransaction,
 }
 
 impl Related<super::crypto_currency::Entity> for Entity {
@@ -29,10 +56,22 @@ impl Related<super::crypto_currency::Entity> for Entity {
     }
 }
 
+impl Related<super::sweep::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sweep.def()
+    }
+}
+
 impl Related<super::wallet::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Wallet.def()
     }
 }
 
+impl Related<super::wallet_transaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WalletTransaction.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
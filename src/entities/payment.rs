@@ -1,9 +1,9 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.5
 
 use sea_orm::entity::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "String(None)")]
 pub enum PaymentStatus {
     #[sea_orm(string_value = "WAITING")]
@@ -16,6 +16,12 @@ pub enum PaymentStatus {
     Finished,
     #[sea_orm(string_value = "EXPIRED")]
     Expired,
+    /// Less than the accepted amount was paid before the payment expired
+    #[sea_orm(string_value = "PARTIALLY_PAID")]
+    PartiallyPaid,
+    /// More than the crypto amount was paid, the surplus is left refundable to the payer
+    #[sea_orm(string_value = "OVERPAID")]
+    Overpaid,
 }
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
@@ -40,6 +46,14 @@ pub struct Model {
     pub expired_at: DateTime,
     pub done_at: Option<DateTime>,
     pub verified_at: Option<DateTime>,
+    pub paid_crypto_amount: Option<Decimal>,
+    /// Paid crypto amount the merchant is credited for
+    pub settled_crypto_amount: Option<Decimal>,
+    #[serde(skip_serializing)]
+    pub idempotency_key: Option<String>,
+    /// Last block the wallet of the payment is reconciled up to
+    #[serde(skip_serializing)]
+    pub reconciled_block_number: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -60,6 +74,10 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     FiatCurrency,
+    #[sea_orm(has_many = "super::payment_quote::Entity")]
+    PaymentQuote,
+    #[sea_orm(has_many = "super::refund::Entity")]
+    Refund,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::UserId",
@@ -78,6 +96,10 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Wallet,
+    #[sea_orm(has_many = "super::wallet_transaction::Entity")]
+    WalletTransaction,
+    #[sea_orm(has_many = "super::webhook_delivery::Entity")]
+    WebhookDelivery,
 }
 
 impl Related<super::crypto_currency::Entity> for Entity {
@@ -92,6 +114,18 @@ impl Related<super::fiat_currency::Entity> for Entity {
     }
 }
 
+impl Related<super::payment_quote::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PaymentQuote.def()
+    }
+}
+
+impl Related<super::refund::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Refund.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
@@ -110,4 +144,16 @@ impl Related<super::wallet::Entity> for Entity {
     }
 }
 
+impl Related<super::wallet_transaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WalletTransaction.def()
+    }
+}
+
+impl Related<super::webhook_delivery::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WebhookDelivery.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
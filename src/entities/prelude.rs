@@ -1,10 +1,18 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.5
 
+pub use super::api_key::Entity as ApiKey;
 pub use super::crypto_currency::Entity as CryptoCurrency;
 pub use super::fiat_currency::Entity as FiatCurrency;
 pub use super::network::Entity as Network;
 pub use super::payment::Entity as Payment;
+pub use super::payment_policy::Entity as PaymentPolicy;
+pub use super::payment_quote::Entity as PaymentQuote;
+pub use super::refresh_token::Entity as RefreshToken;
+pub use super::refund::Entity as Refund;
+pub use super::sweep::Entity as Sweep;
 pub use super::user::Entity as User;
 pub use super::user_transaction::Entity as UserTransaction;
 pub use super::wallet::Entity as Wallet;
 pub use super::wallet_transaction::Entity as WalletTransaction;
+pub use super::webhook_delivery::Entity as WebhookDelivery;
+pub use super::withdrawal::Entity as Withdrawal;
@@ -32,14 +32,33 @@ pub struct Model {
     pub password_hash: String,
     pub role: UserRole,
     pub created_at: DateTime,
+    #[serde(skip_serializing)]
+    pub webhook_secret: Option<String>,
+    #[serde(skip_serializing)]
+    pub token_version: i32,
+    pub disabled: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_key::Entity")]
+    ApiKey,
     #[sea_orm(has_many = "super::payment::Entity")]
     Payment,
+    #[sea_orm(has_many = "super::payment_policy::Entity")]
+    PaymentPolicy,
+    #[sea_orm(has_many = "super::refresh_token::Entity")]
+    RefreshToken,
     #[sea_orm(has_many = "super::user_transaction::Entity")]
     UserTransaction,
+    #[sea_orm(has_many = "super::withdrawal::Entity")]
+    Withdrawal,
+}
+
+impl Related<super::api_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKey.def()
+    }
 }
 
 impl Related<super::payment::Entity> for Entity {
@@ -48,10 +67,28 @@ impl Related<super::payment::Entity> for Entity {
     }
 }
 
+impl Related<super::payment_policy::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PaymentPolicy.def()
+    }
+}
+
+impl Related<super::refresh_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RefreshToken.def()
+    }
+}
+
 impl Related<super::user_transaction::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserTransaction.def()
     }
 }
 
+impl Related<super::withdrawal::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Withdrawal.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,9 +1,9 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.5
 
 use sea_orm::entity::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
 #[sea_orm(rs_type = "String", db_type = "String(None)")]
 pub enum UserTransactionType {
     #[sea_orm(string_value = "DEPOSIT")]
@@ -53,6 +53,8 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     User,
+    #[sea_orm(has_one = "super::withdrawal::Entity")]
+    Withdrawal,
 }
 
 impl Related<super::fiat_currency::Entity> for Entity {
@@ -73,4 +75,10 @@ impl Related<super::user::Entity> for Entity {
     }
 }
 
+impl Related<super::withdrawal::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Withdrawal.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
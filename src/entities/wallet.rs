@@ -10,6 +10,9 @@ pub enum WalletStatus {
     Free,
     #[sea_orm(string_value = "BUSY")]
     Busy,
+    /// Derived for a single payment and never handed out again
+    #[sea_orm(string_value = "RETIRED")]
+    Retired,
 }
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
@@ -20,6 +23,14 @@ pub struct Model {
     pub address: String,
     pub network_id: i32,
     pub status: WalletStatus,
+    pub payment_id: Option<i32>,
+    pub reserved_at: Option<DateTime>,
+    pub derivation_path: Option<String>,
+    /// Set once the wallet is released with funds to move to the treasury
+    pub sweep_requested_at: Option<DateTime>,
+    /// Private key of the wallet, as a keystore v3 encrypted with the master secret
+    #[serde(skip_serializing)]
+    pub encrypted_key: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -34,6 +45,8 @@ pub enum Relation {
     Network,
     #[sea_orm(has_many = "super::payment::Entity")]
     Payment,
+    #[sea_orm(has_many = "super::sweep::Entity")]
+    Sweep,
     #[sea_orm(has_many = "super::wallet_transaction::Entity")]
     WalletTransaction,
 }
@@ -50,6 +63,12 @@ impl Related<super::payment::Entity> for Entity {
     }
 }
 
+impl Related<super::sweep::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sweep.def()
+    }
+}
+
 impl Related<super::wallet_transaction::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::WalletTransaction.def()
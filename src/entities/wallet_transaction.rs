@@ -3,6 +3,19 @@
 use sea_orm::entity::prelude::*;
 use serde::Serialize;
 
+#[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize)]
+#[sea_orm(rs_type = "String", db_type = "String(None)")]
+pub enum WalletTransactionStatus {
+    /// Seen in the mempool, or mined with less than the required confirmations
+    #[sea_orm(string_value = "PENDING")]
+    Pending,
+    #[sea_orm(string_value = "CONFIRMED")]
+    Confirmed,
+    /// Reverted on chain
+    #[sea_orm(string_value = "FAILED")]
+    Failed,
+}
+
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
 #[sea_orm(table_name = "wallet_transaction")]
 pub struct Model {
@@ -11,10 +24,46 @@ pub struct Model {
     pub hash: String,
     pub wallet_id: i32,
     pub created_at: DateTime,
+    pub network_id: i32,
+    pub payment_id: Option<i32>,
+    /// Token transferred, or the native currency of the network
+    pub crypto_currency_id: Option<i32>,
+    pub from_address: Option<String>,
+    /// Amount received by the wallet, in currency units
+    pub value: Option<Decimal>,
+    pub block_number: Option<i64>,
+    pub block_hash: Option<String>,
+    pub confirmations: i32,
+    pub status: WalletTransactionStatus,
+    pub updated_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::crypto_currency::Entity",
+        from = "Column::CryptoCurrencyId",
+        to = "super::crypto_currency::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    CryptoCurrency,
+    #[sea_orm(
+        belongs_to = "super::network::Entity",
+        from = "Column::NetworkId",
+        to = "super::network::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Network,
+    #[sea_orm(
+        belongs_to = "super::payment::Entity",
+        from = "Column::PaymentId",
+        to = "super::payment::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Payment,
     #[sea_orm(
         belongs_to = "super::wallet::Entity",
         from = "Column::WalletId",
@@ -25,6 +74,24 @@ pub enum Relation {
     Wallet,
 }
 
+impl Related<super::crypto_currency::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CryptoCurrency.def()
+    }
+}
+
+impl Related<super::network::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Network.def()
+    }
+}
+
+impl Related<super::payment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Payment.def()
+    }
+}
+
 impl Related<super::wallet::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Wallet.def()
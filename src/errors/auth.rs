@@ -8,6 +8,18 @@ pub enum AuthError {
 
     #[error("Wrong password")]
     WrongPassword,
+
+    #[error("Refresh token is invalid, expired or revoked")]
+    InvalidRefreshToken,
+
+    #[error("User is disabled")]
+    UserIsDisabled,
+
+    #[error("This API key is not belongs to you")]
+    ApiKeyIsNotBelongsToYou,
+
+    #[error("API key is already revoked")]
+    ApiKeyAlreadyRevoked,
 }
 
 impl ResponseError for AuthError {
@@ -15,6 +27,10 @@ impl ResponseError for AuthError {
         match *self {
             AuthError::UsernameAlreadyFound => StatusCode::CONFLICT,
             AuthError::WrongPassword => StatusCode::UNAUTHORIZED,
+            AuthError::InvalidRefreshToken => StatusCode::UNAUTHORIZED,
+            AuthError::UserIsDisabled => StatusCode::FORBIDDEN,
+            AuthError::ApiKeyIsNotBelongsToYou => StatusCode::UNAUTHORIZED,
+            AuthError::ApiKeyAlreadyRevoked => StatusCode::CONFLICT,
         }
     }
 
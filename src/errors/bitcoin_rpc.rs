@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum BitcoinRpcError {
+    #[error("Bitcoin node is unreachable: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Bitcoin node returned error {code}: {message}")]
+    Rpc { code: i64, message: String },
+
+    #[error("Bitcoin node returned an unexpected response: {0}")]
+    BadResponse(String),
+}
@@ -0,0 +1,46 @@
+use super::{BitcoinRpcError, SignerError};
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use ethers::providers::ProviderError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("EVM node failed: {0}")]
+    Provider(#[from] ProviderError),
+
+    #[error("Bitcoin node failed: {0}")]
+    BitcoinRpc(#[from] BitcoinRpcError),
+
+    #[error("'{0}' is not a valid address of the network")]
+    InvalidAddress(String),
+
+    #[error("Amount of {0} base units is too large")]
+    AmountOutOfRange(String),
+
+    #[error("Amount of {0} is not a whole number of base units of the currency")]
+    UnrepresentableAmount(String),
+
+    #[error("Subscription to the node is closed")]
+    SubscriptionClosed,
+
+    #[error("Network is misconfigured: {0}")]
+    Misconfigured(String),
+
+    #[error(transparent)]
+    Signer(#[from] SignerError),
+}
+
+impl ResponseError for ChainError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            ChainError::InvalidAddress(_) => StatusCode::BAD_REQUEST,
+            ChainError::UnrepresentableAmount(_) => StatusCode::BAD_REQUEST,
+            ChainError::Signer(ref err) => err.status_code(),
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
@@ -0,0 +1,30 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    #[error("Keystore is malformed: {0}")]
+    Malformed(String),
+
+    #[error("Keystore uses unsupported {0}")]
+    Unsupported(String),
+
+    #[error("Keystore password is wrong")]
+    WrongPassword,
+
+    #[error("Keystore could not be processed: {0}")]
+    Interrupted(String),
+}
+
+impl ResponseError for KeystoreError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            KeystoreError::Interrupted(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
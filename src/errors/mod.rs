@@ -1,9 +1,25 @@
 mod auth;
+mod bitcoin_rpc;
+mod chain;
 mod internal;
+mod keystore;
 mod not_found;
 mod payment;
+mod payout;
+mod price_oracle;
+mod refund;
+mod signer;
+mod withdrawal;
 
 pub use auth::AuthError;
+pub use bitcoin_rpc::BitcoinRpcError;
+pub use chain::ChainError;
 pub use internal::InternalError;
+pub use keystore::KeystoreError;
 pub use not_found::NotFoundError;
 pub use payment::PaymentError;
+pub use payout::PayoutError;
+pub use price_oracle::PriceOracleError;
+pub use refund::RefundError;
+pub use signer::SignerError;
+pub use withdrawal::WithdrawalError;
@@ -20,6 +20,21 @@ pub enum NotFoundError {
 
     #[error("Transaction with given id doesn't exists")]
     UserTransactionNotFoundWithGivenId,
+
+    #[error("Refund with given id doesn't exists")]
+    RefundNotFoundWithGivenId,
+
+    #[error("API key with given id doesn't exists")]
+    ApiKeyNotFoundWithGivenId,
+
+    #[error("Payment policy with given id doesn't exists")]
+    PaymentPolicyNotFoundWithGivenId,
+
+    #[error("Wallet with given id doesn't exists")]
+    WalletNotFoundWithGivenId,
+
+    #[error("Withdrawal with given id doesn't exists")]
+    WithdrawalNotFoundWithGivenId,
 }
 
 impl ResponseError for NotFoundError {
@@ -1,6 +1,5 @@
 use crate::entities::payment::PaymentStatus;
 use actix_web::{http::StatusCode, HttpResponse, ResponseError};
-use sea_orm::prelude::Decimal;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,16 +13,26 @@ pub enum PaymentError {
     #[error("Payment should be in 'WAITING' state to be payable, current payment state: {0}")]
     PaymentIsNotPayable(PaymentStatus),
 
-    #[error("Payment should be done to be verified, current status: {0}")]
+    #[error("Payment should be done or overpaid to be verified, current status: {0}")]
     PaymentShouldBeDone(PaymentStatus),
 
+    #[error("Idempotency-Key header should be a non-empty string of at most 255 characters")]
+    InvalidIdempotencyKey,
+
+    #[error("This payment policy isn't belongs to you")]
+    PaymentPolicyIsNotBelongsToYou,
+
+    #[error("Underpayment tolerances should not be negative, and a percentage at most 100")]
+    InvalidUnderpaymentTolerance,
+
     #[error("There is no free wallet for your selected network, please try again later")]
     NotFreeWallet,
 
-    #[error(
-        "There is not enough balance to withdrawal, withdrawable amount for this currency is: {0}"
-    )]
-    NotEnoughBalance(Decimal),
+    #[error("Payment with id {0} is already created for this request with other details")]
+    IdempotencyConflict(i32),
+
+    #[error("Payment is changed by another request, please try again")]
+    PaymentIsChangedConcurrently,
 }
 
 impl ResponseError for PaymentError {
@@ -33,8 +42,12 @@ impl ResponseError for PaymentError {
             PaymentError::UserTransactionIsNotBelongsToYou => StatusCode::UNAUTHORIZED,
             PaymentError::PaymentIsNotPayable(_) => StatusCode::NOT_ACCEPTABLE,
             PaymentError::PaymentShouldBeDone(_) => StatusCode::BAD_REQUEST,
+            PaymentError::InvalidIdempotencyKey => StatusCode::BAD_REQUEST,
+            PaymentError::PaymentPolicyIsNotBelongsToYou => StatusCode::UNAUTHORIZED,
+            PaymentError::InvalidUnderpaymentTolerance => StatusCode::BAD_REQUEST,
             PaymentError::NotFreeWallet => StatusCode::IM_USED,
-            PaymentError::NotEnoughBalance(_) => StatusCode::NOT_ACCEPTABLE,
+            PaymentError::IdempotencyConflict(_) => StatusCode::CONFLICT,
+            PaymentError::PaymentIsChangedConcurrently => StatusCode::CONFLICT,
         }
     }
 
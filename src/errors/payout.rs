@@ -0,0 +1,22 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PayoutError {
+    /// The payout will never be made, the withdrawal is rejected
+    #[error("Payout provider refused the payout: {0}")]
+    Refused(String),
+
+    #[error("Payout with reference '{0}' is unknown to the payout provider")]
+    UnknownPayout(String),
+}
+
+impl ResponseError for PayoutError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::BAD_GATEWAY
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
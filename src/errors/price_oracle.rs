@@ -0,0 +1,33 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PriceOracleError {
+    #[error("Price provider is unreachable: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Price provider returned an unexpected response: {0}")]
+    BadResponse(String),
+
+    #[error("No price found for {crypto_symbol}/{fiat_symbol}")]
+    PriceNotFound {
+        crypto_symbol: String,
+        fiat_symbol: String,
+    },
+
+    #[error("Price oracle is misconfigured: {0}")]
+    Misconfigured(String),
+}
+
+impl ResponseError for PriceOracleError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            PriceOracleError::PriceNotFound { .. } => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
@@ -0,0 +1,64 @@
+use super::{ChainError, InternalError, NotFoundError};
+use crate::entities::refund::RefundStatus;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use sea_orm::prelude::Decimal;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RefundError {
+    #[error("Refund amount should be positive")]
+    NonPositiveAmount,
+
+    #[error("Refund amount is more than refundable amount of this payment: {0}")]
+    AmountExceedsRefundable(Decimal),
+
+    #[error("This payment has nothing to refund")]
+    NothingToRefund,
+
+    #[error("Refund should be in '{0}' state, current refund state: {1}")]
+    UnexpectedStatus(RefundStatus, RefundStatus),
+
+    #[error("Refund can only be sent from a wallet of its EVM network")]
+    UnsendableFromWallet,
+
+    #[error("Wallet holds a payment or is to be swept, refund can't be sent from it")]
+    WalletIsInUse,
+
+    #[error("Gas wallet is not configured, refund should be sent from a given wallet")]
+    NoGasWallet,
+
+    #[error(transparent)]
+    NotFound(#[from] NotFoundError),
+
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+
+    #[error(transparent)]
+    Internal(#[from] InternalError),
+}
+
+impl ResponseError for RefundError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            RefundError::NonPositiveAmount => StatusCode::BAD_REQUEST,
+            RefundError::AmountExceedsRefundable(_) => StatusCode::NOT_ACCEPTABLE,
+            RefundError::NothingToRefund => StatusCode::NOT_ACCEPTABLE,
+            RefundError::UnexpectedStatus(_, _) => StatusCode::CONFLICT,
+            RefundError::UnsendableFromWallet => StatusCode::NOT_ACCEPTABLE,
+            RefundError::WalletIsInUse => StatusCode::CONFLICT,
+            RefundError::NoGasWallet => StatusCode::NOT_ACCEPTABLE,
+            RefundError::NotFound(ref err) => err.status_code(),
+            RefundError::Chain(ref err) => err.status_code(),
+            RefundError::Internal(ref err) => err.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match *self {
+            RefundError::NotFound(ref err) => err.error_response(),
+            RefundError::Chain(ref err) => err.error_response(),
+            RefundError::Internal(ref err) => err.error_response(),
+            _ => HttpResponse::build(self.status_code()).body(self.to_string()),
+        }
+    }
+}
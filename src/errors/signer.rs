@@ -0,0 +1,48 @@
+use super::KeystoreError;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use ethers::signers::WalletError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SignerError {
+    #[error("Gateway holds no key of this wallet")]
+    NoKey,
+
+    #[error("Keys are only held for wallets of EVM networks")]
+    UnsupportedNetwork,
+
+    #[error("Wallet keys are locked, the master secret is not set")]
+    Locked,
+
+    #[error("Key does not belong to the wallet address")]
+    AddressMismatch,
+
+    #[error("Private key is invalid")]
+    InvalidKey,
+
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+
+    #[error("Signer failed: {0}")]
+    Wallet(#[from] WalletError),
+
+    #[error("Wallet keys are misconfigured: {0}")]
+    Misconfigured(String),
+}
+
+impl ResponseError for SignerError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            SignerError::NoKey => StatusCode::NOT_ACCEPTABLE,
+            SignerError::UnsupportedNetwork => StatusCode::NOT_ACCEPTABLE,
+            SignerError::AddressMismatch => StatusCode::BAD_REQUEST,
+            SignerError::InvalidKey => StatusCode::BAD_REQUEST,
+            SignerError::Keystore(ref err) => err.status_code(),
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).body(self.to_string())
+    }
+}
@@ -0,0 +1,44 @@
+use super::InternalError;
+use crate::entities::withdrawal::WithdrawalStatus;
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use sea_orm::prelude::Decimal;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum WithdrawalError {
+    #[error("Withdrawal amount should be positive")]
+    NonPositiveAmount,
+
+    #[error(
+        "There is not enough balance to withdrawal, withdrawable amount for this currency is: {0}"
+    )]
+    NotEnoughBalance(Decimal),
+
+    #[error("This withdrawal isn't belongs to you")]
+    WithdrawalIsNotBelongsToYou,
+
+    #[error("Withdrawal should be in '{0}' state, current withdrawal state: {1}")]
+    UnexpectedStatus(WithdrawalStatus, WithdrawalStatus),
+
+    #[error(transparent)]
+    Internal(#[from] InternalError),
+}
+
+impl ResponseError for WithdrawalError {
+    fn status_code(&self) -> StatusCode {
+        match *self {
+            WithdrawalError::NonPositiveAmount => StatusCode::BAD_REQUEST,
+            WithdrawalError::NotEnoughBalance(_) => StatusCode::NOT_ACCEPTABLE,
+            WithdrawalError::WithdrawalIsNotBelongsToYou => StatusCode::UNAUTHORIZED,
+            WithdrawalError::UnexpectedStatus(_, _) => StatusCode::CONFLICT,
+            WithdrawalError::Internal(ref err) => err.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match *self {
+            WithdrawalError::Internal(ref err) => err.error_response(),
+            _ => HttpResponse::build(self.status_code()).body(self.to_string()),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    entities::api_key,
+    errors::{AuthError, NotFoundError},
+    models::dtos::CreateApiKey,
+    security::{api_key as api_key_security, hash, jwt::Claims},
+    services::{api_key_service, user_service},
+};
+use actix_web::web::ReqData;
+use actix_web::{
+    delete, get, post,
+    web::{Data, Path, ServiceConfig},
+    Error, HttpResponse, Responder,
+};
+use actix_web_grants::proc_macro::has_any_role;
+use actix_web_validator::Json;
+use chrono::Utc;
+use sea_orm::{DbConn, Set};
+use serde_json::json;
+
+#[get("/users/api-keys")]
+#[has_any_role("USER", "ADMIN")]
+async fn get_all_user_api_keys(
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let api_keys = api_key_service::find_all_by_user_id(&db, user.id).await?;
+
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+/// Create an API key, the key itself is only returned in this response
+#[post("/users/api-keys")]
+#[has_any_role("USER", "ADMIN")]
+async fn create_api_key(
+    new_api_key: Json<CreateApiKey>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let (prefix, key) = api_key_security::generate_key();
+
+    let mut scopes = new_api_key.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+
+    let api_key = api_key::ActiveModel {
+        user_id: Set(user.id),
+        name: Set(new_api_key.name.clone()),
+        prefix: Set(prefix),
+        key_hash: Set(hash::hash_password(&key)),
+        scopes: Set(api_key_security::join_scopes(&scopes)),
+        created_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    };
+
+    let api_key = api_key_service::create(&db, api_key).await?;
+    log::info!("API key with id {} is created", api_key.id);
+
+    Ok(HttpResponse::Created().json(json!({ "api_key": api_key, "key": key })))
+}
+
+#[delete("/users/api-keys/{id}")]
+#[has_any_role("USER", "ADMIN")]
+async fn revoke_api_key(
+    path: Path<i32>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let api_key_id = path.into_inner();
+
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let api_key = api_key_service::find_by_id(&db, api_key_id)
+        .await?
+        .ok_or(NotFoundError::ApiKeyNotFoundWithGivenId)?;
+
+    if api_key.user_id != user.id {
+        return Err(AuthError::ApiKeyIsNotBelongsToYou)?;
+    }
+
+    if api_key.revoked_at.is_some() {
+        return Err(AuthError::ApiKeyAlreadyRevoked)?;
+    }
+
+    let mut api_key = api_key::ActiveModel::from(api_key);
+    api_key.revoked_at = Set(Some(Utc::now().naive_utc()));
+
+    let api_key = api_key_service::update(&db, api_key).await?;
+    log::info!("API key with id {} is revoked", api_key.id);
+
+    Ok(HttpResponse::Ok().json(api_key))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_all_user_api_keys)
+        .service(create_api_key)
+        .service(revoke_api_key);
+}
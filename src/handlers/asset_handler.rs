@@ -1,20 +1,29 @@
 use crate::{
     entities::{
-        crypto_currency, fiat_currency, network,
+        crypto_currency, fiat_currency,
+        network::{self, NetworkKind},
         wallet::{self, WalletStatus},
     },
-    errors::NotFoundError,
-    models::dtos::{CreateCryptoCurrency, CreateFiatCurrency, CreateNetwork, CreateWallet},
-    services::{crypto_currency_service, fiat_currency_service, network_service, wallet_service},
+    errors::{ChainError, InternalError, NotFoundError, SignerError},
+    models::dtos::{
+        CreateCryptoCurrency, CreateFiatCurrency, CreateNetwork, CreateWallet, ExportWalletKey,
+        ImportWalletKey, SetNetworkTreasury,
+    },
+    security::keystore,
+    services::{
+        chain_watcher, crypto_currency_service, fiat_currency_service, network_service,
+        signer::{LocalSigner, Signer, WalletKeys},
+        wallet_service, web3_service,
+    },
 };
 use actix_web::{
-    get, post,
-    web::{Data, ServiceConfig},
+    delete, get, post, put,
+    web::{Data, Path, ServiceConfig},
     Error, HttpResponse, Responder,
 };
 use actix_web_grants::proc_macro::has_any_role;
 use actix_web_validator::Json;
-use sea_orm::{DbConn, Set};
+use sea_orm::{ActiveModelTrait, DbConn, Set, TransactionTrait};
 
 #[get("/networks")]
 async fn get_all_networks(db: Data<DbConn>) -> Result<impl Responder, Error> {
@@ -31,15 +40,57 @@ async fn create_network(
 ) -> Result<impl Responder, Error> {
     let network = network::ActiveModel {
         name: Set(network.name.clone()),
+        kind: Set(network.kind.clone()),
         http_address_url: Set(network.http_address_url.clone()),
         websocket_address_url: Set(network.websocket_address_url.clone()),
+        required_confirmations: Set(network
+            .required_confirmations
+            .unwrap_or(web3_service::DEFAULT_REQUIRED_CONFIRMATIONS)),
+        extended_public_key: Set(network.extended_public_key.clone()),
+        next_derivation_index: Set(0),
+        treasury_address: Set(network.treasury_address.clone()),
         ..Default::default()
     };
 
-    let network = network_service::create(&db, network).await?;
+    // the treasury is checked by the chain of the network, which needs the network created
+    let txn = db.begin().await.map_err(Into::<InternalError>::into)?;
+    let network = network
+        .insert(&txn)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+    if let Some(ref treasury_address) = network.treasury_address {
+        validate_treasury_address(&network, treasury_address).await?;
+    }
+    txn.commit().await.map_err(Into::<InternalError>::into)?;
+
     Ok(HttpResponse::Created().json(network))
 }
 
+/// Set where the network's released wallets are swept to
+#[put("/networks/{id}/treasury")]
+#[has_any_role("ADMIN")]
+async fn set_network_treasury(
+    path: Path<i32>,
+    treasury: Json<SetNetworkTreasury>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let network = network_service::find_by_id(&db, path.into_inner())
+        .await?
+        .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+    if let Some(ref treasury_address) = treasury.treasury_address {
+        validate_treasury_address(&network, treasury_address).await?;
+    }
+
+    let mut network = network::ActiveModel::from(network);
+    network.treasury_address = Set(treasury.treasury_address.clone());
+
+    let network = network_service::update(&db, network).await?;
+    log::info!("Treasury of network with id {} is set", network.id);
+
+    Ok(HttpResponse::Ok().json(network))
+}
+
 #[get("/crypto-currencies")]
 async fn get_all_crypto_currencies(db: Data<DbConn>) -> Result<impl Responder, Error> {
     let crypto_currencies = crypto_currency_service::find_all(&db).await?;
@@ -61,6 +112,10 @@ async fn create_crypto_currency(
         name: Set(crypto_currency.name.clone()),
         symbol: Set(crypto_currency.symbol.clone()),
         network_id: Set(crypto_currency.network_id.clone()),
+        contract_address: Set(crypto_currency.contract_address.clone()),
+        decimals: Set(crypto_currency
+            .decimals
+            .unwrap_or(web3_service::NATIVE_CURRENCY_DECIMALS)),
         ..Default::default()
     };
 
@@ -81,10 +136,15 @@ async fn create_wallet(
     wallet: Json<CreateWallet>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
-    network_service::find_by_id(&db, wallet.network_id)
+    let network = network_service::find_by_id(&db, wallet.network_id)
         .await?
         .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
 
+    let chain_watcher = chain_watcher::for_network(&network)?;
+    if !chain_watcher.validate_address(&wallet.address).await? {
+        return Err(ChainError::InvalidAddress(wallet.address.clone()))?;
+    }
+
     let wallet = wallet::ActiveModel {
         address: Set(wallet.address.clone()),
         network_id: Set(wallet.network_id.clone()),
@@ -96,6 +156,94 @@ async fn create_wallet(
     Ok(HttpResponse::Created().json(network))
 }
 
+/// Import the private key of a wallet, so that the gateway controls it. The key is stored
+/// encrypted with the master secret.
+#[put("/wallets/{id}/key")]
+#[has_any_role("ADMIN")]
+async fn import_wallet_key(
+    path: Path<i32>,
+    key: Json<ImportWalletKey>,
+    wallet_keys: Data<WalletKeys>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let wallet = find_evm_wallet(&db, path.into_inner()).await?;
+
+    // one of both is set, as validated
+    let signer = match (&key.private_key, &key.keystore, &key.password) {
+        (Some(private_key), _, _) => LocalSigner::from_hex(private_key)?,
+        (None, Some(keystore), Some(password)) => LocalSigner::from_bytes(
+            &keystore::decrypt_blocking(keystore.to_string(), password.clone()).await?,
+        )?,
+        _ => return Err(SignerError::InvalidKey)?,
+    };
+
+    if format!("{:?}", signer.address()) != wallet.address.to_lowercase() {
+        return Err(SignerError::AddressMismatch)?;
+    }
+
+    wallet_service::set_encrypted_key(&db, wallet.id, Some(wallet_keys.seal(&signer).await?))
+        .await?;
+    log::info!("Key of wallet with id {} is imported", wallet.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Export the private key of a wallet controlled by the gateway, as a keystore v3 encrypted
+/// with the given password
+#[post("/wallets/{id}/key/export")]
+#[has_any_role("ADMIN")]
+async fn export_wallet_key(
+    path: Path<i32>,
+    export: Json<ExportWalletKey>,
+    wallet_keys: Data<WalletKeys>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let wallet = find_evm_wallet(&db, path.into_inner()).await?;
+
+    let signer = wallet_keys.local_signer(&wallet).await?;
+    let keystore = keystore::encrypt_blocking(
+        signer.private_key(),
+        wallet.address.clone(),
+        export.password.clone(),
+    )
+    .await?;
+    log::warn!("Key of wallet with id {} is exported", wallet.id);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(keystore))
+}
+
+/// Forget the imported private key of a wallet
+#[delete("/wallets/{id}/key")]
+#[has_any_role("ADMIN")]
+async fn delete_wallet_key(path: Path<i32>, db: Data<DbConn>) -> Result<impl Responder, Error> {
+    let wallet = wallet_service::find_by_id(&db, path.into_inner())
+        .await?
+        .ok_or(NotFoundError::WalletNotFoundWithGivenId)?;
+
+    wallet_service::set_encrypted_key(&db, wallet.id, None).await?;
+    log::info!("Key of wallet with id {} is deleted", wallet.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Keys are only handled for wallets of EVM networks
+async fn find_evm_wallet(db: &DbConn, id: i32) -> Result<wallet::Model, Error> {
+    let wallet = wallet_service::find_by_id(db, id)
+        .await?
+        .ok_or(NotFoundError::WalletNotFoundWithGivenId)?;
+    let network = network_service::find_by_id(db, wallet.network_id)
+        .await?
+        .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+    if network.kind != NetworkKind::Evm {
+        return Err(SignerError::UnsupportedNetwork)?;
+    }
+
+    Ok(wallet)
+}
+
 #[get("/fiat-currencies")]
 async fn get_all_fiat_currencies(db: Data<DbConn>) -> Result<impl Responder, Error> {
     let crypto_currencies = fiat_currency_service::find_all(&db).await?;
@@ -119,13 +267,31 @@ async fn create_fiat_currency(
     Ok(HttpResponse::Created().json(fiat_currency))
 }
 
+/// Only EVM networks have their released wallets swept, to an address of the network
+async fn validate_treasury_address(
+    network: &network::Model,
+    treasury_address: &str,
+) -> Result<(), Error> {
+    let chain_watcher = chain_watcher::for_network(network)?;
+    if network.kind != NetworkKind::Evm || !chain_watcher.validate_address(treasury_address).await?
+    {
+        return Err(ChainError::InvalidAddress(treasury_address.to_owned()))?;
+    }
+
+    Ok(())
+}
+
 pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(get_all_networks)
         .service(create_network)
+        .service(set_network_treasury)
         .service(get_all_crypto_currencies)
         .service(create_crypto_currency)
         .service(get_all_wallets)
         .service(create_wallet)
+        .service(import_wallet_key)
+        .service(export_wallet_key)
+        .service(delete_wallet_key)
         .service(get_all_fiat_currencies)
         .service(create_fiat_currency);
 }
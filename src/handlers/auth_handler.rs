@@ -1,16 +1,20 @@
 use crate::{
     config::AppConfig,
     entities::user,
-    errors::{AuthError, NotFoundError},
-    models::dtos::{CreateUser, LoginUser},
-    security::{hash, jwt},
-    services::user_service,
+    errors::{AuthError, InternalError, NotFoundError},
+    models::dtos::{CreateUser, LoginUser, RefreshSession},
+    security::{
+        hash, jwt,
+        refresh_token::{self, REFRESH_TOKEN_HEADER},
+        webhook,
+    },
+    services::{refresh_token_service, user_service},
 };
 use actix_web::{
     http::header,
     post,
     web::{Data, ServiceConfig},
-    Error, HttpResponse, Responder,
+    Error, HttpResponse, HttpResponseBuilder, Responder,
 };
 use actix_web_validator::Json;
 use chrono::Utc;
@@ -35,21 +39,16 @@ async fn signup(
         password_hash: Set(password_hash),
         role: Set(user::UserRole::User),
         created_at: Set(Utc::now().naive_utc()),
+        webhook_secret: Set(Some(webhook::generate_secret())),
         ..Default::default()
     };
 
     let user = user_service::create(&db, user).await?;
 
-    Ok(HttpResponse::Created()
-        .insert_header((
-            header::AUTHORIZATION,
-            jwt::generate_jwt(
-                &user,
-                &jwt_encoding_key,
-                config.jwt_validity_duration_in_days,
-            ),
-        ))
-        .json(user))
+    let mut response = HttpResponse::Created();
+    start_session(&mut response, &user, &jwt_encoding_key, &config, &db).await?;
+
+    Ok(response.json(user))
 }
 
 #[post("/login")]
@@ -67,18 +66,105 @@ async fn login(
         return Err(AuthError::WrongPassword)?;
     }
 
+    if user.disabled {
+        return Err(AuthError::UserIsDisabled)?;
+    }
+
+    let mut response = HttpResponse::Ok();
+    start_session(&mut response, &user, &jwt_encoding_key, &config, &db).await?;
+
+    Ok(response.finish())
+}
+
+/// Exchange a refresh token for a new access token and refresh token of the same session
+#[post("/refresh")]
+async fn refresh(
+    refresh_session: Json<RefreshSession>,
+    jwt_encoding_key: Data<EncodingKey>,
+    config: Data<AppConfig>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let (old_refresh_token, refresh_token) = refresh_token_service::rotate(
+        &db,
+        &refresh_session.refresh_token,
+        config.refresh_token_validity_duration_in_days,
+    )
+    .await?
+    .ok_or(AuthError::InvalidRefreshToken)?;
+
+    let user = user_service::find_by_id(&db, old_refresh_token.user_id)
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    if user.disabled {
+        return Err(AuthError::UserIsDisabled)?;
+    }
+
     Ok(HttpResponse::Ok()
         .insert_header((
             header::AUTHORIZATION,
             jwt::generate_jwt(
                 &user,
+                &old_refresh_token.session_id,
                 &jwt_encoding_key,
-                config.jwt_validity_duration_in_days,
+                config.jwt_validity_duration_in_minutes,
             ),
         ))
+        .insert_header((REFRESH_TOKEN_HEADER, refresh_token))
         .finish())
 }
 
+/// Revoke the session of the refresh token, access tokens it issued stop working too
+#[post("/logout")]
+async fn logout(
+    refresh_session: Json<RefreshSession>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let refresh_token = refresh_token_service::find_by_token(&db, &refresh_session.refresh_token)
+        .await?
+        .ok_or(AuthError::InvalidRefreshToken)?;
+
+    refresh_token_service::revoke_session(&db, &refresh_token.session_id).await?;
+    log::info!("Session {} is logged out", refresh_token.session_id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Start a new session of the user, putting its tokens in the response headers
+async fn start_session(
+    response: &mut HttpResponseBuilder,
+    user: &user::Model,
+    jwt_encoding_key: &EncodingKey,
+    config: &AppConfig,
+    db: &DbConn,
+) -> Result<(), InternalError> {
+    let session_id = refresh_token::generate_session_id();
+    let refresh_token = refresh_token_service::issue(
+        db,
+        user.id,
+        &session_id,
+        config.refresh_token_validity_duration_in_days,
+    )
+    .await?;
+
+    response
+        .insert_header((
+            header::AUTHORIZATION,
+            jwt::generate_jwt(
+                user,
+                &session_id,
+                jwt_encoding_key,
+                config.jwt_validity_duration_in_minutes,
+            ),
+        ))
+        .insert_header((REFRESH_TOKEN_HEADER, refresh_token));
+
+    Ok(())
+}
+
 pub fn config(cfg: &mut ServiceConfig) {
-    cfg.service(signup).service(login);
+    cfg.service(signup)
+        .service(login)
+        .service(refresh)
+        .service(logout);
 }
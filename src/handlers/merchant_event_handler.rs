@@ -0,0 +1,40 @@
+use crate::{
+    models::{dtos::MerchantEventFilter, merchant_event::MerchantEvent, sse},
+    security::jwt::Claims,
+    services::merchant_event_bus::MerchantEventBus,
+};
+use actix_web::{
+    get,
+    web::{Bytes, Data, ReqData, ServiceConfig},
+    Error, Responder,
+};
+use actix_web_grants::proc_macro::has_permissions;
+use actix_web_validator::Query;
+
+/// Stream everything happening on the account of the authenticated user as Server-Sent
+/// Events: payment status changes, transactions received for their payments, and deposits
+/// and withdrawals of their balance.
+#[get("/users/events")]
+#[has_permissions("payments:read")]
+async fn get_user_events(
+    filter: Query<MerchantEventFilter>,
+    req_user: ReqData<Claims>,
+    merchant_events: Data<MerchantEventBus>,
+) -> Result<impl Responder, Error> {
+    let subscription = merchant_events.subscribe(req_user.sub.parse().unwrap());
+    let filter = filter.into_inner();
+
+    let events = sse::live_events(subscription, move |event| {
+        event.matches(&filter).then(|| encode_event(&event))
+    });
+
+    Ok(sse::response().streaming(events))
+}
+
+fn encode_event(event: &MerchantEvent) -> Bytes {
+    sse::encode_event(None, event, event.data())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_user_events);
+}
@@ -1,5 +1,12 @@
+pub mod api_key_handler;
 pub mod asset_handler;
 pub mod auth_handler;
+pub mod merchant_event_handler;
 pub mod payment_handler;
+pub mod payment_policy_handler;
+pub mod refund_handler;
+pub mod sse_handler;
+pub mod sweep_handler;
 pub mod user_handler;
+pub mod withdrawal_handler;
 pub mod ws_handler;
@@ -2,49 +2,93 @@ use crate::{
     config::AppConfig,
     entities::payment::{self, PaymentStatus},
     errors::{NotFoundError, PaymentError},
-    models::dtos::{CreatePayment, VerifyPayment},
+    models::{
+        dtos::{CreatePayment, PaymentFilter, VerifyPayment},
+        merchant_event::MerchantEvent,
+        pagination::Pagination,
+    },
     security::jwt::Claims,
-    services::{fiat_currency_service, payment_service, user_service},
+    services::{
+        fiat_currency_service, merchant_event_bus::MerchantEventBus, payment_service,
+        price_oracle::PriceOracle, user_service, webhook_service,
+    },
 };
 use actix_web::web::ReqData;
 use actix_web::{
     get, post,
     web::{Data, ServiceConfig},
-    Error, HttpResponse, Responder,
+    Error, HttpRequest, HttpResponse, Responder,
 };
-use actix_web_grants::proc_macro::has_any_role;
-use actix_web_validator::Json;
+use actix_web_grants::proc_macro::{has_any_role, has_permissions};
+use actix_web_validator::{Json, Query};
 use chrono::{Duration, Utc};
 use sea_orm::{DbConn, Set};
 use serde_json::json;
 
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const IDEMPOTENT_REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
 #[get("/payments")]
 #[has_any_role("ADMIN")]
-async fn get_all_payments(db: Data<DbConn>) -> Result<impl Responder, Error> {
-    let payments = payment_service::find_all(&db).await?;
+async fn get_all_payments(
+    filter: Query<PaymentFilter>,
+    pagination: Query<Pagination>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let payments = payment_service::find_filtered_page(&db, None, &filter, &pagination).await?;
 
     Ok(HttpResponse::Ok().json(payments))
 }
 
+/// Create a payment, or return the one already created for a retried request.
+///
+/// Retries are recognized by the `Idempotency-Key` header, or by the seller order id of a
+/// payment which hasn't expired nor been partially paid. A retry asking for another payment than
+/// the one it refers to is a conflict.
 #[post("/payments")]
+#[has_permissions("payments:create")]
 async fn create_payment(
     payment: Json<CreatePayment>,
+    req: HttpRequest,
     req_user: ReqData<Claims>,
     config: Data<AppConfig>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
     let user_id = req_user.sub.parse::<i32>().unwrap();
 
+    let idempotency_key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= 255 => Some(key.to_owned()),
+            _ => return Err(PaymentError::InvalidIdempotencyKey)?,
+        },
+        None => None,
+    };
+
     let user = user_service::find_by_id(&db, user_id)
         .await?
         .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
+    if let Some(existing_payment) = payment_service::find_existing(
+        &db,
+        user.id,
+        &payment.seller_order_id,
+        idempotency_key.as_deref(),
+    )
+    .await?
+    {
+        ensure_same_payment(&existing_payment, &payment)?;
+
+        return Ok(HttpResponse::Created()
+            .insert_header((IDEMPOTENT_REPLAYED_HEADER, "true"))
+            .json(payment_created_response(&existing_payment, &config)));
+    }
+
     fiat_currency_service::find_by_id(&db, payment.fiat_currency_id.clone())
         .await?
         .ok_or(NotFoundError::FiatCurrencyNotFoundWithGivenId)?;
 
     let payment_waiting_duration = Duration::minutes(config.payment_waiting_duration_in_minutes);
-    let payment = payment::ActiveModel {
+    let new_payment = payment::ActiveModel {
         user_id: Set(user.id),
         fiat_currency_id: Set(payment.fiat_currency_id.clone()),
         amount: Set(payment.amount.clone()),
@@ -57,23 +101,61 @@ async fn create_payment(
         status: Set(PaymentStatus::Waiting),
         created_at: Set(Utc::now().naive_utc()),
         expired_at: Set(Utc::now().naive_utc() + payment_waiting_duration),
+        idempotency_key: Set(idempotency_key.clone()),
         ..Default::default()
     };
-    let payment = payment_service::create(&db, payment).await?;
 
-    payment_service::spawn_payment_exp_scheduler(payment_waiting_duration, payment.id, db);
+    let created_payment = match payment_service::create(&db, new_payment).await {
+        Ok(created_payment) => created_payment,
+        // a concurrent retry created the payment first and won the unique index
+        Err(err) => {
+            let existing_payment = payment_service::find_existing(
+                &db,
+                user.id,
+                &payment.seller_order_id,
+                idempotency_key.as_deref(),
+            )
+            .await?
+            .ok_or(err)?;
+            ensure_same_payment(&existing_payment, &payment)?;
+
+            existing_payment
+        }
+    };
 
-    let payment_response = json!({
+    Ok(HttpResponse::Created().json(payment_created_response(&created_payment, &config)))
+}
+
+/// A retry should ask for the payment it refers to, not reuse its key for another one
+fn ensure_same_payment(
+    existing_payment: &payment::Model,
+    payment: &CreatePayment,
+) -> Result<(), PaymentError> {
+    if existing_payment.fiat_currency_id != payment.fiat_currency_id
+        || existing_payment.amount != payment.amount
+        || existing_payment.callback_url != payment.callback_url
+        || existing_payment.seller_order_id != payment.seller_order_id
+    {
+        return Err(PaymentError::IdempotencyConflict(existing_payment.id));
+    }
+
+    Ok(())
+}
+
+fn payment_created_response(payment: &payment::Model, config: &AppConfig) -> serde_json::Value {
+    json!({
         "id": payment.id,
         "link": format!("{}/{}", config.payment_gateway_base_url, payment.id),
-    });
-    Ok(HttpResponse::Created().json(payment_response))
+    })
 }
 
 #[post("/payments/verify")]
+#[has_permissions("payments:create")]
 async fn verify_payment(
     payment: Json<VerifyPayment>,
     req_user: ReqData<Claims>,
+    price_oracle: Data<dyn PriceOracle>,
+    merchant_events: Data<MerchantEventBus>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
     let user_id = req_user.sub.parse::<i32>().unwrap();
@@ -90,10 +172,12 @@ async fn verify_payment(
         return Err(PaymentError::PaymentIsNotBelongsToYou)?;
     }
 
-    if payment.status != PaymentStatus::Done {
+    // the surplus of an overpaid payment stays refundable once verified
+    if payment.status != PaymentStatus::Done && payment.status != PaymentStatus::Overpaid {
         return Err(PaymentError::PaymentShouldBeDone(payment.status))?;
     }
 
+    let previous_status = payment.status.clone();
     let mut payment = payment::ActiveModel::from(payment);
     payment.status = Set(PaymentStatus::Verified);
     payment.verified_at = Set(Some(Utc::now().naive_utc()));
@@ -101,7 +185,13 @@ async fn verify_payment(
     let payment = payment_service::update(&db, payment).await?;
     log::info!("Payment with id {} is verified", payment.id);
 
-    payment_service::spawn_crypto_seller(payment.clone(), db);
+    webhook_service::spawn_webhook_dispatcher(payment.clone(), previous_status.clone(), db.clone());
+    merchant_events.publish(
+        payment.user_id,
+        MerchantEvent::payment_status_changed(payment.clone(), previous_status),
+    );
+
+    payment_service::spawn_crypto_seller(payment.clone(), price_oracle, merchant_events, db);
 
     Ok(HttpResponse::Ok().json(payment))
 }
@@ -0,0 +1,135 @@
+use crate::{
+    entities::payment_policy,
+    errors::{NotFoundError, PaymentError},
+    models::dtos::SetPaymentPolicy,
+    security::jwt::Claims,
+    services::{crypto_currency_service, payment_policy_service, user_service},
+};
+use actix_web::web::ReqData;
+use actix_web::{
+    delete, get, put,
+    web::{Data, Path, ServiceConfig},
+    Error, HttpResponse, Responder,
+};
+use actix_web_grants::proc_macro::has_any_role;
+use actix_web_validator::Json;
+use chrono::Utc;
+use sea_orm::prelude::Decimal;
+use sea_orm::{DbConn, Set};
+
+#[get("/users/payment-policies")]
+#[has_any_role("USER", "ADMIN")]
+async fn get_all_user_payment_policies(
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let payment_policies = payment_policy_service::find_all_by_user_id(&db, user.id).await?;
+
+    Ok(HttpResponse::Ok().json(payment_policies))
+}
+
+/// Set how underpaid and overpaid payments of a crypto currency are settled, or by default
+/// when no crypto currency is given. Applies to payments whose wallet is watched afterwards.
+#[put("/users/payment-policies")]
+#[has_any_role("USER", "ADMIN")]
+async fn set_payment_policy(
+    new_policy: Json<SetPaymentPolicy>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let is_negative = |tolerance: Option<Decimal>| tolerance.unwrap_or_default() < Decimal::ZERO;
+    if is_negative(new_policy.underpayment_tolerance_amount)
+        || is_negative(new_policy.underpayment_tolerance_percent)
+        || new_policy
+            .underpayment_tolerance_percent
+            .unwrap_or_default()
+            > Decimal::ONE_HUNDRED
+    {
+        return Err(PaymentError::InvalidUnderpaymentTolerance)?;
+    }
+
+    if let Some(crypto_currency_id) = new_policy.crypto_currency_id {
+        crypto_currency_service::find_by_id(&db, crypto_currency_id)
+            .await?
+            .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
+    }
+
+    let existing_policy = payment_policy_service::find_by_user_id_and_crypto_currency_id(
+        &db,
+        user.id,
+        new_policy.crypto_currency_id,
+    )
+    .await?;
+
+    let payment_policy = match existing_policy {
+        Some(existing_policy) => {
+            let mut payment_policy = payment_policy::ActiveModel::from(existing_policy);
+            payment_policy.underpayment_tolerance_amount =
+                Set(new_policy.underpayment_tolerance_amount);
+            payment_policy.underpayment_tolerance_percent =
+                Set(new_policy.underpayment_tolerance_percent);
+            payment_policy.overpayment_action = Set(new_policy.overpayment_action.clone());
+            payment_policy.updated_at = Set(Some(Utc::now().naive_utc()));
+
+            payment_policy_service::update(&db, payment_policy).await?
+        }
+        None => {
+            let payment_policy = payment_policy::ActiveModel {
+                user_id: Set(user.id),
+                crypto_currency_id: Set(new_policy.crypto_currency_id),
+                underpayment_tolerance_amount: Set(new_policy.underpayment_tolerance_amount),
+                underpayment_tolerance_percent: Set(new_policy.underpayment_tolerance_percent),
+                overpayment_action: Set(new_policy.overpayment_action.clone()),
+                created_at: Set(Utc::now().naive_utc()),
+                ..Default::default()
+            };
+
+            payment_policy_service::create(&db, payment_policy).await?
+        }
+    };
+
+    log::info!("Payment policy with id {} is set", payment_policy.id);
+
+    Ok(HttpResponse::Ok().json(payment_policy))
+}
+
+#[delete("/users/payment-policies/{id}")]
+#[has_any_role("USER", "ADMIN")]
+async fn delete_payment_policy(
+    path: Path<i32>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let payment_policy_id = path.into_inner();
+
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let payment_policy = payment_policy_service::find_by_id(&db, payment_policy_id)
+        .await?
+        .ok_or(NotFoundError::PaymentPolicyNotFoundWithGivenId)?;
+
+    if payment_policy.user_id != user.id {
+        return Err(PaymentError::PaymentPolicyIsNotBelongsToYou)?;
+    }
+
+    payment_policy_service::delete(&db, payment_policy).await?;
+    log::info!("Payment policy with id {payment_policy_id} is deleted");
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_all_user_payment_policies)
+        .service(set_payment_policy)
+        .service(delete_payment_policy);
+}
@@ -0,0 +1,270 @@
+use crate::{
+    entities::{
+        network::NetworkKind,
+        refund::{self, RefundStatus},
+        wallet::WalletStatus,
+    },
+    errors::{NotFoundError, PaymentError, RefundError},
+    models::dtos::{CreateRefund, PaymentRefunds, SendRefund, SentRefund},
+    security::jwt::Claims,
+    services::{
+        crypto_currency_service, network_service, payment_service, refund_service,
+        signer::{Signer, WalletKeys},
+        user_service, wallet_service,
+        web3_service::{self, NonceLocks},
+    },
+};
+use actix_web::web::ReqData;
+use actix_web::{
+    get, post,
+    web::{Data, Path, ServiceConfig},
+    Error, HttpResponse, Responder,
+};
+use actix_web_grants::proc_macro::{has_any_role, has_permissions};
+use actix_web_validator::Json;
+use sea_orm::DbConn;
+use std::sync::Arc;
+
+#[get("/users/payments/{id}/refunds")]
+#[has_permissions("payments:read")]
+async fn get_user_payment_refunds(
+    path: Path<i32>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let payment_id = path.into_inner();
+
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let payment = payment_service::find_by_id(&db, payment_id)
+        .await?
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
+
+    if payment.user_id != user.id {
+        return Err(PaymentError::PaymentIsNotBelongsToYou)?;
+    }
+
+    let payment_refunds = PaymentRefunds {
+        refundable_amount: refund_service::refundable_amount(db.get_ref(), &payment).await?,
+        refunds: refund_service::find_all_by_payment_id(db.get_ref(), payment.id).await?,
+    };
+
+    Ok(HttpResponse::Ok().json(payment_refunds))
+}
+
+#[post("/users/payments/{id}/refunds")]
+#[has_any_role("USER", "ADMIN")]
+async fn request_refund(
+    path: Path<i32>,
+    refund: Json<CreateRefund>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let payment_id = path.into_inner();
+
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let payment = payment_service::find_by_id(&db, payment_id)
+        .await?
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
+
+    if payment.user_id != user.id {
+        return Err(PaymentError::PaymentIsNotBelongsToYou)?;
+    }
+
+    let refund = refund_service::request(
+        &db,
+        payment.id,
+        refund.amount,
+        refund.destination_address.clone(),
+    )
+    .await?;
+    log::info!(
+        "Refund with id {} is requested for payment with id {}",
+        refund.id,
+        payment.id
+    );
+
+    Ok(HttpResponse::Created().json(refund))
+}
+
+#[get("/refunds")]
+#[has_any_role("ADMIN")]
+async fn get_all_refunds(db: Data<DbConn>) -> Result<impl Responder, Error> {
+    let refunds = refund_service::find_all(&db).await?;
+
+    Ok(HttpResponse::Ok().json(refunds))
+}
+
+#[post("/refunds/{id}/approve")]
+#[has_any_role("ADMIN")]
+async fn approve_refund(path: Path<i32>, db: Data<DbConn>) -> Result<impl Responder, Error> {
+    let refund = find_refund_in_status(&db, path.into_inner(), RefundStatus::Requested).await?;
+
+    if !refund_service::approve(&db, refund.id).await? {
+        return Err(unexpected_status(&db, refund.id, RefundStatus::Requested).await?)?;
+    }
+
+    let refund = find_refund(&db, refund.id).await?;
+    log::info!("Refund with id {} is approved", refund.id);
+
+    Ok(HttpResponse::Ok().json(refund))
+}
+
+#[post("/refunds/{id}/sent")]
+#[has_any_role("ADMIN")]
+async fn mark_refund_sent(
+    path: Path<i32>,
+    sent_refund: Json<SentRefund>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let refund = find_refund_in_status(&db, path.into_inner(), RefundStatus::Approved).await?;
+
+    let transaction_hash = sent_refund.into_inner().transaction_hash;
+    if !refund_service::mark_sent(&db, refund.id, transaction_hash).await? {
+        return Err(unexpected_status(&db, refund.id, RefundStatus::Approved).await?)?;
+    }
+
+    let refund = find_refund(&db, refund.id).await?;
+    log::info!("Refund with id {} is sent", refund.id);
+
+    Ok(HttpResponse::Ok().json(refund))
+}
+
+/// Send the approved refund from the gas wallet, or from the given wallet controlled by the
+/// gateway as long as it neither holds a payment nor waits to be swept
+#[post("/refunds/{id}/send")]
+#[has_any_role("ADMIN")]
+async fn send_refund(
+    path: Path<i32>,
+    send_refund: Json<SendRefund>,
+    wallet_keys: Data<WalletKeys>,
+    gas_signer: Option<Data<dyn Signer>>,
+    nonce_locks: Data<NonceLocks>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let refund = find_refund_in_status(&db, path.into_inner(), RefundStatus::Approved).await?;
+
+    let crypto_currency = crypto_currency_service::find_by_id(&db, refund.crypto_currency_id)
+        .await?
+        .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
+    let network = network_service::find_by_id(&db, crypto_currency.network_id)
+        .await?
+        .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+    if network.kind != NetworkKind::Evm {
+        return Err(RefundError::UnsendableFromWallet)?;
+    }
+
+    let (signer, sent_from): (Arc<dyn Signer>, _) = match send_refund.wallet_id {
+        Some(wallet_id) => {
+            let wallet = wallet_service::find_by_id(&db, wallet_id)
+                .await?
+                .ok_or(NotFoundError::WalletNotFoundWithGivenId)?;
+            if wallet.network_id != network.id {
+                return Err(RefundError::UnsendableFromWallet)?;
+            }
+            // what such a wallet holds belongs to its payment or is the sweeper's to move
+            if wallet.status == WalletStatus::Busy || wallet.sweep_requested_at.is_some() {
+                return Err(RefundError::WalletIsInUse)?;
+            }
+
+            (
+                wallet_keys.signer(&wallet).await?.into(),
+                format!("wallet with id {}", wallet.id),
+            )
+        }
+        None => (
+            gas_signer.ok_or(RefundError::NoGasWallet)?.into_inner(),
+            "the gas wallet".to_owned(),
+        ),
+    };
+
+    if !refund_service::claim_for_sending(&db, refund.id).await? {
+        return Err(unexpected_status(&db, refund.id, RefundStatus::Approved).await?)?;
+    }
+
+    let sent = web3_service::send_transfer(
+        &network,
+        &crypto_currency,
+        signer.as_ref(),
+        &refund.destination_address,
+        refund.amount,
+        &nonce_locks,
+    )
+    .await;
+    let transaction_hash = match sent {
+        Ok(transaction_hash) => format!("{transaction_hash:?}"),
+        Err(err) => {
+            refund_service::release(&db, refund.id).await?;
+            return Err(err)?;
+        }
+    };
+
+    refund_service::record_transaction_hash(&db, refund.id, transaction_hash).await?;
+    log::info!("Refund with id {} is sent from {sent_from}", refund.id);
+
+    let refund = find_refund(&db, refund.id).await?;
+    Ok(HttpResponse::Ok().json(refund))
+}
+
+#[post("/refunds/{id}/failed")]
+#[has_any_role("ADMIN")]
+async fn mark_refund_failed(path: Path<i32>, db: Data<DbConn>) -> Result<impl Responder, Error> {
+    let refund = find_refund_in_status(&db, path.into_inner(), RefundStatus::Approved).await?;
+
+    if !refund_service::mark_failed(&db, refund.id).await? {
+        return Err(unexpected_status(&db, refund.id, RefundStatus::Approved).await?)?;
+    }
+
+    let refund = find_refund(&db, refund.id).await?;
+    log::info!("Refund with id {} is failed", refund.id);
+
+    Ok(HttpResponse::Ok().json(refund))
+}
+
+async fn find_refund(db: &DbConn, id: i32) -> Result<refund::Model, Error> {
+    Ok(refund_service::find_by_id(db, id)
+        .await?
+        .ok_or(NotFoundError::RefundNotFoundWithGivenId)?)
+}
+
+async fn find_refund_in_status(
+    db: &DbConn,
+    id: i32,
+    status: RefundStatus,
+) -> Result<refund::Model, Error> {
+    let refund = find_refund(db, id).await?;
+
+    if refund.status != status {
+        return Err(RefundError::UnexpectedStatus(status, refund.status))?;
+    }
+
+    Ok(refund)
+}
+
+/// Error of a status change lost to a concurrent one, with the status the refund has now
+async fn unexpected_status(
+    db: &DbConn,
+    id: i32,
+    expected: RefundStatus,
+) -> Result<RefundError, Error> {
+    Ok(RefundError::UnexpectedStatus(
+        expected,
+        find_refund(db, id).await?.status,
+    ))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_user_payment_refunds)
+        .service(request_refund)
+        .service(get_all_refunds)
+        .service(approve_refund)
+        .service(mark_refund_sent)
+        .service(send_refund)
+        .service(mark_refund_failed);
+}
@@ -0,0 +1,85 @@
+use crate::{
+    entities::payment::{self, PaymentStatus},
+    errors::NotFoundError,
+    models::{sse, ws::WsOutputMessage},
+    services::{payment_monitor::PaymentMonitor, payment_service},
+};
+use actix_web::{
+    get,
+    web::{Bytes, Data, Path, ServiceConfig},
+    Error, HttpRequest, Responder,
+};
+use futures_util::{stream, StreamExt};
+use sea_orm::DbConn;
+use std::convert::Infallible;
+
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// Stream the events of the payment, the same ones as the payment websocket sends.
+///
+/// The stream starts with the current state of the payment, unless it resumes from a
+/// `Last-Event-ID` whose following events are all still kept, in which case those are sent
+/// instead. It ends once the payment is finished.
+#[get("/sse/payments/{payment_id}")]
+async fn payment_sse(
+    path: Path<i32>,
+    req: HttpRequest,
+    payment_monitor: Data<PaymentMonitor>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let payment_id = path.into_inner();
+
+    let last_event_id = req
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|last_event_id| last_event_id.to_str().ok())
+        .and_then(|last_event_id| last_event_id.trim().parse::<u64>().ok());
+
+    // subscribe before reading the payment, so no event is missed in between
+    let mut subscription = payment_monitor.subscribe(payment_id, last_event_id);
+
+    let payment = payment_service::find_by_id(&db, payment_id)
+        .await?
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
+
+    let initial_events = match (last_event_id, subscription.missed_events.take()) {
+        (Some(_), Some(missed_events)) => missed_events
+            .into_iter()
+            .map(|event| encode_event(Some(event.id), event.event.into()))
+            .collect(),
+        _ => vec![encode_event(None, payment_state(&payment))],
+    };
+    let initial_events = stream::iter(initial_events.into_iter().map(Ok::<_, Infallible>));
+
+    let mut response = sse::response();
+
+    if payment.status != PaymentStatus::Waiting {
+        return Ok(response.streaming(initial_events));
+    }
+
+    // the channel of the payment is closed once it is finished
+    let live_events = sse::live_events(subscription, |event| {
+        Some(encode_event(Some(event.id), event.event.into()))
+    });
+
+    Ok(response.streaming(initial_events.chain(live_events)))
+}
+
+/// Message telling the current state of the payment
+fn payment_state(payment: &payment::Model) -> WsOutputMessage {
+    match payment.status {
+        PaymentStatus::Waiting => WsOutputMessage::PaymentUpdated(payment.clone()),
+        PaymentStatus::Expired => WsOutputMessage::PaymentExpired(payment.clone()),
+        PaymentStatus::PartiallyPaid => WsOutputMessage::PaymentPartiallyPaid(payment.clone()),
+        PaymentStatus::Overpaid => WsOutputMessage::PaymentOverpaid(payment.clone()),
+        _ => WsOutputMessage::PaymentDone(payment.clone()),
+    }
+}
+
+fn encode_event(id: Option<u64>, msg: WsOutputMessage) -> Bytes {
+    sse::encode_event(id, &msg, msg.data())
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(payment_sse);
+}
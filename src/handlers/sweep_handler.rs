@@ -0,0 +1,28 @@
+use crate::{
+    models::{dtos::SweepFilter, pagination::Pagination},
+    services::sweep_service,
+};
+use actix_web::{
+    get,
+    web::{Data, ServiceConfig},
+    Error, HttpResponse, Responder,
+};
+use actix_web_grants::proc_macro::has_any_role;
+use actix_web_validator::Query;
+use sea_orm::DbConn;
+
+#[get("/sweeps")]
+#[has_any_role("ADMIN")]
+async fn get_all_sweeps(
+    filter: Query<SweepFilter>,
+    pagination: Query<Pagination>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let sweeps = sweep_service::find_filtered_page(&db, &filter, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(sweeps))
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(get_all_sweeps);
+}
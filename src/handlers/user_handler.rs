@@ -1,9 +1,14 @@
 use crate::{
-    entities::{user_transaction, user_transaction::UserTransactionType},
-    errors::{NotFoundError, PaymentError},
-    models::dtos::{BalanceWithdrawal, FiatBalance},
-    security::jwt::Claims,
-    services::{fiat_currency_service, payment_service, user_service, user_transaction_service},
+    errors::{AuthError, NotFoundError, PaymentError},
+    models::{
+        dtos::{ChangePassword, FiatBalance, PaymentFilter, UserTransactionFilter},
+        pagination::Pagination,
+    },
+    security::{hash, jwt::Claims},
+    services::{
+        payment_quote_service, payment_service, user_service, user_transaction_service,
+        wallet_transaction_service, webhook_service, withdrawal_service,
+    },
 };
 use actix_web::web::ReqData;
 use actix_web::{
@@ -11,13 +16,16 @@ use actix_web::{
     web::{Data, Path, ServiceConfig},
     Error, HttpResponse, Responder,
 };
-use actix_web_validator::Json;
-use chrono::Utc;
-use sea_orm::prelude::Decimal;
-use sea_orm::{DbConn, Set};
+use actix_web_grants::proc_macro::{has_any_role, has_permissions};
+use actix_web_validator::{Json, Query};
+use sea_orm::DbConn;
+use serde_json::json;
 
 #[get("/users/payments")]
+#[has_permissions("payments:read")]
 async fn get_all_user_payments(
+    filter: Query<PaymentFilter>,
+    pagination: Query<Pagination>,
     req_user: ReqData<Claims>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
@@ -25,12 +33,14 @@ async fn get_all_user_payments(
         .await?
         .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    let user_payments = payment_service::find_all_by_user_id(&db, user.id).await?;
+    let user_payments =
+        payment_service::find_filtered_page(&db, Some(user.id), &filter, &pagination).await?;
 
     Ok(HttpResponse::Ok().json(user_payments))
 }
 
 #[get("/users/payments/{id}")]
+#[has_permissions("payments:read")]
 async fn get_user_payment(
     path: Path<i32>,
     req_user: ReqData<Claims>,
@@ -53,67 +63,89 @@ async fn get_user_payment(
     Ok(HttpResponse::Ok().json(payment))
 }
 
-#[get("/users/transactions")]
-async fn get_all_user_transactions(
+#[get("/users/payments/{id}/webhooks")]
+#[has_permissions("payments:read")]
+async fn get_user_payment_webhooks(
+    path: Path<i32>,
     req_user: ReqData<Claims>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
+    let payment_id = path.into_inner();
+
     let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
         .await?
         .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    let user_payments = user_transaction_service::find_all_by_user_id(&db, user.id).await?;
+    let payment = payment_service::find_by_id(&db, payment_id)
+        .await?
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
+
+    if payment.user_id != user.id {
+        return Err(PaymentError::PaymentIsNotBelongsToYou)?;
+    }
+
+    let webhook_deliveries = webhook_service::find_all_by_payment_id(&db, payment.id).await?;
 
-    Ok(HttpResponse::Ok().json(user_payments))
+    Ok(HttpResponse::Ok().json(webhook_deliveries))
 }
 
-#[get("/users/transactions/{id}")]
-async fn get_user_transaction(
+/// Exchange-rate quotes of the payment, the last one gives its current crypto amount
+#[get("/users/payments/{id}/quotes")]
+#[has_permissions("payments:read")]
+async fn get_user_payment_quotes(
     path: Path<i32>,
     req_user: ReqData<Claims>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
-    let transaction_id = path.into_inner();
+    let payment_id = path.into_inner();
 
     let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
         .await?
         .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    let user_transaction = user_transaction_service::find_by_id(&db, transaction_id)
+    let payment = payment_service::find_by_id(&db, payment_id)
         .await?
-        .ok_or(NotFoundError::UserTransactionNotFoundWithGivenId)?;
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
 
-    if user_transaction.user_id != user.id {
-        return Err(PaymentError::UserTransactionIsNotBelongsToYou)?;
+    if payment.user_id != user.id {
+        return Err(PaymentError::PaymentIsNotBelongsToYou)?;
     }
 
-    Ok(HttpResponse::Ok().json(user_transaction))
+    let quotes = payment_quote_service::find_all_by_payment_id(&db, payment.id).await?;
+
+    Ok(HttpResponse::Ok().json(quotes))
 }
 
-#[get("/users/balance")]
-async fn get_user_balance(
+/// On-chain transactions sent to the wallet of the payment, with their confirmation progress
+#[get("/users/payments/{id}/transactions")]
+#[has_permissions("payments:read")]
+async fn get_user_payment_transactions(
+    path: Path<i32>,
     req_user: ReqData<Claims>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
+    let payment_id = path.into_inner();
+
     let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
         .await?
         .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    let user_balance = user_transaction_service::get_user_balance(&db, user.id)
+    let payment = payment_service::find_by_id(&db, payment_id)
         .await?
-        .into_iter()
-        .map(|(fiat_currency_id, balance)| FiatBalance {
-            fiat_currency_id,
-            balance,
-        })
-        .collect::<Vec<_>>();
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
 
-    Ok(HttpResponse::Ok().json(user_balance))
+    if payment.user_id != user.id {
+        return Err(PaymentError::PaymentIsNotBelongsToYou)?;
+    }
+
+    let transactions = wallet_transaction_service::find_all_by_payment_id(&db, payment.id).await?;
+
+    Ok(HttpResponse::Ok().json(transactions))
 }
 
-#[post("/users/withdraw")]
-async fn withdraw_balance(
-    withdrawal: Json<BalanceWithdrawal>,
+#[get("/users/webhook-secret")]
+#[has_any_role("USER", "ADMIN")]
+async fn get_webhook_secret(
     req_user: ReqData<Claims>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
@@ -121,47 +153,152 @@ async fn withdraw_balance(
         .await?
         .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    fiat_currency_service::find_by_id(&db, withdrawal.fiat_currency_id)
+    let webhook_secret = user_service::get_or_create_webhook_secret(&db, user).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "webhook_secret": webhook_secret })))
+}
+
+#[post("/users/webhook-secret")]
+#[has_any_role("USER", "ADMIN")]
+async fn rotate_webhook_secret(
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
         .await?
-        .ok_or(NotFoundError::FiatCurrencyNotFoundWithGivenId)?;
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    let user_balance = user_transaction_service::get_user_balance(&db, user.id).await?;
+    let webhook_secret = user_service::rotate_webhook_secret(&db, user).await?;
 
-    let fiat_currency_balance = user_balance.get(&withdrawal.fiat_currency_id);
+    Ok(HttpResponse::Ok().json(json!({ "webhook_secret": webhook_secret })))
+}
 
-    if fiat_currency_balance.is_none() {
-        return Err(PaymentError::NotEnoughBalance(
-            Decimal::from_str_exact("0").unwrap(),
-        ))?;
+/// Change the password of the user, logging out all of their sessions
+#[post("/users/password")]
+#[has_any_role("USER", "ADMIN")]
+async fn change_password(
+    passwords: Json<ChangePassword>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    if !hash::verify_password(&user.password_hash, &passwords.current_password) {
+        return Err(AuthError::WrongPassword)?;
     }
-    let fiat_currency_balance = fiat_currency_balance.unwrap();
 
-    if withdrawal.amount > *fiat_currency_balance {
-        return Err(PaymentError::NotEnoughBalance(
-            fiat_currency_balance.clone(),
-        ))?;
+    let user = user_service::change_password(&db, user, &passwords.new_password).await?;
+    log::info!("Password of user with id {} is changed", user.id);
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/users/{id}/disable")]
+#[has_any_role("ADMIN")]
+async fn disable_user(path: Path<i32>, db: Data<DbConn>) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, path.into_inner())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let user = user_service::set_disabled(&db, user, true).await?;
+    log::info!("User with id {} is disabled", user.id);
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[post("/users/{id}/enable")]
+#[has_any_role("ADMIN")]
+async fn enable_user(path: Path<i32>, db: Data<DbConn>) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, path.into_inner())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let user = user_service::set_disabled(&db, user, false).await?;
+    log::info!("User with id {} is enabled", user.id);
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[get("/users/transactions")]
+#[has_permissions("payments:read")]
+async fn get_all_user_transactions(
+    filter: Query<UserTransactionFilter>,
+    pagination: Query<Pagination>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let user_payments =
+        user_transaction_service::find_filtered_page_by_user_id(&db, user.id, &filter, &pagination)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(user_payments))
+}
+
+#[get("/users/transactions/{id}")]
+#[has_permissions("payments:read")]
+async fn get_user_transaction(
+    path: Path<i32>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let transaction_id = path.into_inner();
+
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let user_transaction = user_transaction_service::find_by_id(&db, transaction_id)
+        .await?
+        .ok_or(NotFoundError::UserTransactionNotFoundWithGivenId)?;
+
+    if user_transaction.user_id != user.id {
+        return Err(PaymentError::UserTransactionIsNotBelongsToYou)?;
     }
 
-    let withdrawal_transaction = user_transaction::ActiveModel {
-        user_id: Set(user.id),
-        typ: Set(UserTransactionType::Withdrawal),
-        amount: Set(withdrawal.amount),
-        fiat_currency_id: Set(withdrawal.fiat_currency_id),
-        created_at: Set(Utc::now().naive_utc()),
-        ..Default::default()
-    };
+    Ok(HttpResponse::Ok().json(user_transaction))
+}
 
-    let withdrawal_transaction =
-        user_transaction_service::create(&db, withdrawal_transaction).await?;
+#[get("/users/balance")]
+#[has_permissions("payments:read")]
+async fn get_user_balance(
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
 
-    Ok(HttpResponse::Ok().json(withdrawal_transaction))
+    let user_balance = withdrawal_service::get_available_balance(db.get_ref(), user.id)
+        .await?
+        .into_iter()
+        .map(|(fiat_currency_id, (balance, held))| FiatBalance {
+            fiat_currency_id,
+            balance,
+            held,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(user_balance))
 }
 
 pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(get_all_user_payments)
         .service(get_user_payment)
+        .service(get_user_payment_webhooks)
+        .service(get_user_payment_quotes)
+        .service(get_user_payment_transactions)
+        .service(get_webhook_secret)
+        .service(rotate_webhook_secret)
+        .service(change_password)
+        .service(disable_user)
+        .service(enable_user)
         .service(get_all_user_transactions)
         .service(get_user_transaction)
-        .service(get_user_balance)
-        .service(withdraw_balance);
+        .service(get_user_balance);
 }
@@ -0,0 +1,224 @@
+use crate::{
+    entities::withdrawal::{self, WithdrawalDestinationType, WithdrawalStatus},
+    errors::{ChainError, NotFoundError, WithdrawalError},
+    models::{
+        dtos::{BalanceWithdrawal, RejectWithdrawal, WithdrawalFilter},
+        merchant_event::MerchantEvent,
+        pagination::Pagination,
+    },
+    security::jwt::Claims,
+    services::{
+        chain_watcher, crypto_currency_service, fiat_currency_service,
+        merchant_event_bus::MerchantEventBus, network_service, user_service, withdrawal_service,
+    },
+};
+use actix_web::web::ReqData;
+use actix_web::{
+    get, post,
+    web::{Data, Path, ServiceConfig},
+    Error, HttpResponse, Responder,
+};
+use actix_web_grants::proc_macro::{has_any_role, has_permissions};
+use actix_web_validator::{Json, Query};
+use sea_orm::{DbConn, Set};
+
+/// Request a withdrawal of the balance, which holds its amount until it is paid or rejected
+#[post("/users/withdraw")]
+#[has_permissions("balance:withdraw")]
+async fn withdraw_balance(
+    withdrawal: Json<BalanceWithdrawal>,
+    req_user: ReqData<Claims>,
+    merchant_events: Data<MerchantEventBus>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    fiat_currency_service::find_by_id(&db, withdrawal.fiat_currency_id)
+        .await?
+        .ok_or(NotFoundError::FiatCurrencyNotFoundWithGivenId)?;
+
+    let destination = withdrawal.normalized_destination();
+
+    if withdrawal.destination_type == WithdrawalDestinationType::CryptoAddress {
+        // unwrap: crypto address destinations are validated to have a crypto currency
+        let crypto_currency =
+            crypto_currency_service::find_by_id(&db, withdrawal.crypto_currency_id.unwrap())
+                .await?
+                .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
+        let network = network_service::find_by_id(&db, crypto_currency.network_id)
+            .await?
+            .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+        let chain_watcher = chain_watcher::for_network(&network)?;
+        if !chain_watcher.validate_address(&destination).await? {
+            return Err(ChainError::InvalidAddress(destination))?;
+        }
+    }
+
+    let withdrawal = withdrawal::ActiveModel {
+        user_id: Set(user.id),
+        fiat_currency_id: Set(withdrawal.fiat_currency_id),
+        amount: Set(withdrawal.amount),
+        destination_type: Set(withdrawal.destination_type.clone()),
+        destination: Set(destination),
+        destination_reference: Set(withdrawal.destination_reference.clone()),
+        crypto_currency_id: Set(withdrawal.crypto_currency_id),
+        ..Default::default()
+    };
+
+    let withdrawal = withdrawal_service::request(&db, withdrawal).await?;
+    log::info!(
+        "Withdrawal with id {} is requested by user with id {}",
+        withdrawal.id,
+        user.id
+    );
+
+    merchant_events.publish(
+        user.id,
+        MerchantEvent::WithdrawalStatusChanged(withdrawal.clone()),
+    );
+
+    Ok(HttpResponse::Created().json(withdrawal))
+}
+
+#[get("/users/withdrawals")]
+#[has_permissions("payments:read")]
+async fn get_all_user_withdrawals(
+    filter: Query<WithdrawalFilter>,
+    pagination: Query<Pagination>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let withdrawals =
+        withdrawal_service::find_filtered_page(&db, Some(user.id), &filter, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(withdrawals))
+}
+
+#[get("/users/withdrawals/{id}")]
+#[has_permissions("payments:read")]
+async fn get_user_withdrawal(
+    path: Path<i32>,
+    req_user: ReqData<Claims>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let withdrawal_id = path.into_inner();
+
+    let user = user_service::find_by_id(&db, req_user.sub.parse().unwrap())
+        .await?
+        .ok_or(NotFoundError::UserNotFoundWithGivenId)?;
+
+    let withdrawal = withdrawal_service::find_by_id(&db, withdrawal_id)
+        .await?
+        .ok_or(NotFoundError::WithdrawalNotFoundWithGivenId)?;
+
+    if withdrawal.user_id != user.id {
+        return Err(WithdrawalError::WithdrawalIsNotBelongsToYou)?;
+    }
+
+    Ok(HttpResponse::Ok().json(withdrawal))
+}
+
+#[get("/withdrawals")]
+#[has_any_role("ADMIN")]
+async fn get_all_withdrawals(
+    filter: Query<WithdrawalFilter>,
+    pagination: Query<Pagination>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let withdrawals =
+        withdrawal_service::find_filtered_page(&db, None, &filter, &pagination).await?;
+
+    Ok(HttpResponse::Ok().json(withdrawals))
+}
+
+/// Approve the pending withdrawal, it is then submitted to the payout provider
+#[post("/withdrawals/{id}/approve")]
+#[has_any_role("ADMIN")]
+async fn approve_withdrawal(
+    path: Path<i32>,
+    merchant_events: Data<MerchantEventBus>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let withdrawal = find_withdrawal(&db, path.into_inner()).await?;
+
+    if !withdrawal_service::approve(&db, withdrawal.id).await? {
+        return Err(WithdrawalError::UnexpectedStatus(
+            WithdrawalStatus::Pending,
+            withdrawal.status,
+        ))?;
+    }
+
+    let withdrawal = find_withdrawal(&db, withdrawal.id).await?;
+    log::info!("Withdrawal with id {} is approved", withdrawal.id);
+
+    merchant_events.publish(
+        withdrawal.user_id,
+        MerchantEvent::WithdrawalStatusChanged(withdrawal.clone()),
+    );
+
+    Ok(HttpResponse::Ok().json(withdrawal))
+}
+
+/// Reject the withdrawal before it is submitted to the payout provider, releasing what it holds
+#[post("/withdrawals/{id}/reject")]
+#[has_any_role("ADMIN")]
+async fn reject_withdrawal(
+    path: Path<i32>,
+    rejection: Json<RejectWithdrawal>,
+    merchant_events: Data<MerchantEventBus>,
+    db: Data<DbConn>,
+) -> Result<impl Responder, Error> {
+    let withdrawal = find_withdrawal(&db, path.into_inner()).await?;
+
+    let from = match withdrawal.status {
+        WithdrawalStatus::Pending | WithdrawalStatus::Approved => withdrawal.status.clone(),
+        _ => {
+            return Err(WithdrawalError::UnexpectedStatus(
+                WithdrawalStatus::Pending,
+                withdrawal.status,
+            ))?
+        }
+    };
+
+    if !withdrawal_service::reject(&db, withdrawal.id, from.clone(), rejection.reason.clone())
+        .await?
+    {
+        // submitted to the payout provider in the meantime
+        return Err(WithdrawalError::UnexpectedStatus(
+            from,
+            find_withdrawal(&db, withdrawal.id).await?.status,
+        ))?;
+    }
+
+    let withdrawal = find_withdrawal(&db, withdrawal.id).await?;
+    log::info!("Withdrawal with id {} is rejected", withdrawal.id);
+
+    merchant_events.publish(
+        withdrawal.user_id,
+        MerchantEvent::WithdrawalStatusChanged(withdrawal.clone()),
+    );
+
+    Ok(HttpResponse::Ok().json(withdrawal))
+}
+
+async fn find_withdrawal(db: &DbConn, id: i32) -> Result<withdrawal::Model, Error> {
+    Ok(withdrawal_service::find_by_id(db, id)
+        .await?
+        .ok_or(NotFoundError::WithdrawalNotFoundWithGivenId)?)
+}
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(withdraw_balance)
+        .service(get_all_user_withdrawals)
+        .service(get_user_withdrawal)
+        .service(get_all_withdrawals)
+        .service(approve_withdrawal)
+        .service(reject_withdrawal);
+}
@@ -1,34 +1,39 @@
 use crate::{
     entities::payment::{self, PaymentStatus},
     errors::{NotFoundError, PaymentError},
-    models::ws::{WsInputMessage, WsOutputMessage},
+    models::{
+        payment_event::PaymentEvent,
+        ws::{
+            WsInputMessage, WsOutputMessage, WsProtocol, WsRequestId, WsSession, JSON_PROTOCOL_V1,
+        },
+    },
     services::{
-        crypto_currency_service, fiat_currency_service, kucoin_api_service, network_service,
-        payment_service, wallet_service, web3_service,
+        crypto_currency_service, fiat_currency_service, network_service,
+        payment_monitor::PaymentMonitor,
+        payment_quote_service::{self, QuotePolicy},
+        payment_service,
+        price_oracle::PriceOracle,
+        wallet_service,
     },
 };
 use actix_web::{
     get,
+    http::header::{self, HeaderValue},
     web::{Data, Path, Payload, ServiceConfig},
     Error, HttpRequest, Responder,
 };
-use actix_ws::Message;
+use actix_ws::{CloseCode, Message};
 use anyhow::Result;
-use chrono::Utc;
 use futures_util::{
     future::{self, Either},
     StreamExt,
 };
-use sea_orm::{DbConn, Set};
+use sea_orm::DbConn;
 use std::{
-    sync::{Arc, Mutex},
+    sync::Arc,
     time::{Duration, Instant},
 };
-use tokio::{
-    pin,
-    task::{self, JoinHandle},
-    time::interval,
-};
+use tokio::{pin, sync::broadcast::error::RecvError, task, time::interval};
 
 /// How often heartbeat pings are sent
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
@@ -38,8 +43,10 @@ const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
 struct SocketData {
     db: Data<DbConn>,
-    payment: Mutex<payment::Model>,
-    payment_task_handle: Mutex<Option<JoinHandle<()>>>,
+    price_oracle: Data<dyn PriceOracle>,
+    quote_policy: Data<QuotePolicy>,
+    payment_monitor: Data<PaymentMonitor>,
+    payment_id: i32,
 }
 
 #[get("/ws/payments/{payment_id}")]
@@ -47,6 +54,9 @@ async fn payment_ws_handshake(
     path: Path<i32>,
     req: HttpRequest,
     body: Payload,
+    price_oracle: Data<dyn PriceOracle>,
+    quote_policy: Data<QuotePolicy>,
+    payment_monitor: Data<PaymentMonitor>,
     db: Data<DbConn>,
 ) -> Result<impl Responder, Error> {
     let payment_id = path.into_inner();
@@ -59,55 +69,94 @@ async fn payment_ws_handshake(
         return Err(PaymentError::PaymentIsNotPayable(payment.status))?;
     }
 
-    let (response, session, msg_stream) = actix_ws::handle(&req, body)?;
+    let protocol = WsProtocol::negotiate(
+        req.headers()
+            .get(header::SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|protocols| protocols.to_str().ok()),
+    );
+
+    let (mut response, session, msg_stream) = actix_ws::handle(&req, body)?;
+    if protocol == WsProtocol::JsonV1 {
+        response.headers_mut().insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(JSON_PROTOCOL_V1),
+        );
+    }
+
+    let socket_data = SocketData {
+        db,
+        price_oracle,
+        quote_policy,
+        payment_monitor,
+        payment_id: payment.id,
+    };
 
     // spawn websocket handler (and don't await it) so that the response is returned immediately
-    task::spawn_local(payment_ws(payment, session, msg_stream, db));
+    task::spawn_local(payment_ws(
+        payment,
+        session,
+        msg_stream,
+        protocol,
+        socket_data,
+    ));
 
     Ok(response)
 }
-/// Process messages received from the client, respond to ping messages, and monitor
-/// connection health to detect network issues and free up resources.
-pub async fn payment_ws(
+
+/// Process messages received from the client, respond to ping messages, forward the events of
+/// the payment, and monitor connection health to detect network issues and free up resources.
+///
+/// The payment is watched by the payment monitor, so closing the connection never stops it.
+async fn payment_ws(
     payment: payment::Model,
     mut session: actix_ws::Session,
     mut msg_stream: actix_ws::MessageStream,
-    db: Data<DbConn>,
+    protocol: WsProtocol,
+    socket_data: SocketData,
 ) {
-    log::info!("connected to websocket");
+    log::info!("connected to websocket with {protocol:?} protocol");
+
+    let mut ws_session = WsSession::new(session.clone(), protocol);
 
     let mut last_heartbeat = Instant::now();
     let mut interval = interval(HEARTBEAT_INTERVAL);
 
-    let socket_data = Arc::new(SocketData {
-        db,
-        payment: Mutex::new(payment),
-        payment_task_handle: Mutex::new(None),
-    });
+    let socket_data = Arc::new(socket_data);
+    let mut events = socket_data.payment_monitor.subscribe(payment.id, None);
+
+    // a reconnecting client gets the state of the payment it already chose a crypto for
+    if payment.crypto_currency_id.is_some() {
+        let _ = ws_session
+            .send(WsOutputMessage::PaymentUpdated(payment.clone()))
+            .await;
+    }
 
     let reason = loop {
         // create "next client timeout check" future
         let tick = interval.tick();
+        // create "next payment event" future
+        let event = events.recv();
         // required for select()
         pin!(tick);
+        pin!(event);
 
-        // waits for either `msg_stream` to receive a message from the client or the heartbeat
-        // interval timer to tick, yielding the value of whichever one is ready first
-        match future::select(msg_stream.next(), tick).await {
+        // waits for either `msg_stream` to receive a message from the client, the heartbeat
+        // interval timer to tick or the payment to have a new event, yielding the value of
+        // whichever one is ready first
+        match future::select(msg_stream.next(), future::select(tick, event)).await {
             // received message from WebSocket client
             Either::Left((Some(Ok(msg)), _)) => {
                 log::debug!("msg: {msg:?}");
 
                 match msg {
                     Message::Text(text) => {
-                        let res =
-                            process_text_msg(&mut session, &text, Arc::clone(&socket_data)).await;
-                        if let Err(err) = res {
-                            session
-                                .text(WsOutputMessage::Error(err).into_str())
-                                .await
-                                .unwrap();
-                        }
+                        process_text_msg(
+                            &mut ws_session,
+                            &text,
+                            protocol,
+                            Arc::clone(&socket_data),
+                        )
+                        .await;
                     }
 
                     Message::Binary(_) => {
@@ -146,7 +195,7 @@ pub async fn payment_ws(
             Either::Left((None, _)) => break None,
 
             // heartbeat interval ticked
-            Either::Right((_inst, _)) => {
+            Either::Right((Either::Left((_inst, _)), _)) => {
                 // if no heartbeat ping/pong received recently, close the connection
                 if Instant::now().duration_since(last_heartbeat) > CLIENT_TIMEOUT {
                     log::info!(
@@ -159,6 +208,20 @@ pub async fn payment_ws(
                 // send heartbeat ping
                 let _ = session.ping(b"").await;
             }
+
+            // payment monitor published an event of the payment
+            Either::Right((Either::Right((event, _)), _)) => match event {
+                Ok(event) => {
+                    let _ = ws_session.send(event.event.into()).await;
+                }
+
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("client is {skipped} payment events behind; skipping them");
+                }
+
+                // the payment is finished, there is nothing left to follow
+                Err(RecvError::Closed) => break Some(CloseCode::Normal.into()),
+            },
         }
     };
 
@@ -168,131 +231,117 @@ pub async fn payment_ws(
     log::info!("disconnected from websocket");
 }
 
+/// Handle a request of the client, replying errors with their code
 async fn process_text_msg(
-    session: &mut actix_ws::Session,
+    session: &mut WsSession,
     text: &str,
+    protocol: WsProtocol,
     socket_data: Arc<SocketData>,
-) -> Result<()> {
-    let input_msg = WsInputMessage::try_from(text)?;
+) {
+    let (request_id, input_msg) = match WsInputMessage::parse(text, protocol) {
+        Ok(parsed) => parsed,
+        Err((request_id, err)) => {
+            let _ = session
+                .reply(request_id, WsOutputMessage::Error(err.into()))
+                .await;
+            return;
+        }
+    };
 
-    match input_msg {
+    let res = match input_msg {
         WsInputMessage::ChooseCrypto(crypto_currency_id) => {
-            choose_crypto(crypto_currency_id, session, socket_data).await
+            choose_crypto(crypto_currency_id, request_id.clone(), session, socket_data).await
         }
+    };
+
+    if let Err(err) = res {
+        let _ = session
+            .reply(request_id, WsOutputMessage::Error(err.into()))
+            .await;
     }
 }
 
 async fn choose_crypto(
     crypto_currency_id: i32,
-    session: &mut actix_ws::Session,
+    request_id: WsRequestId,
+    session: &mut WsSession,
     socket_data: Arc<SocketData>,
 ) -> Result<()> {
-    let crypto_currency = crypto_currency_service::find_by_id(&socket_data.db, crypto_currency_id)
+    let payment = payment_service::find_by_id(&socket_data.db, socket_data.payment_id)
         .await?
-        .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
-
-    let fiat_currency = fiat_currency_service::find_by_id(
-        &socket_data.db,
-        socket_data.payment.lock().unwrap().fiat_currency_id,
-    )
-    .await?
-    .ok_or(NotFoundError::FiatCurrencyNotFoundWithGivenId)?;
-
-    let crypto_amount = kucoin_api_service::fiat_to_crypto(
-        &fiat_currency.symbol,
-        socket_data.payment.lock().unwrap().amount,
-        &crypto_currency.symbol,
-    )
-    .await
-    .unwrap();
-
-    if let Some(dest_wallet_id) = socket_data.payment.lock().unwrap().dest_wallet_id {
-        let mut payment_task_handle = socket_data.payment_task_handle.lock().unwrap();
-        if payment_task_handle.is_some() {
-            log::info!("Abort previous payment task");
+        .ok_or(NotFoundError::PaymentNotFoundWithGivenId)?;
 
-            payment_task_handle.as_ref().unwrap().abort();
-            *payment_task_handle = None;
-        }
-        wallet_service::free(&socket_data.db, dest_wallet_id).await?;
+    if payment.status != PaymentStatus::Waiting {
+        return Err(PaymentError::PaymentIsNotPayable(payment.status))?;
     }
 
-    let wallet = wallet_service::reserve(&socket_data.db, crypto_currency.network_id).await?;
-
-    let mut payment = payment::ActiveModel::from(socket_data.payment.lock().unwrap().clone());
-    payment.crypto_currency_id = Set(Some(crypto_currency.id));
-    payment.crypto_amount = Set(Some(crypto_amount));
-    payment.dest_wallet_id = Set(Some(wallet.id));
-
-    let payment = payment_service::update(&socket_data.db, payment).await?;
-
-    *socket_data.payment.lock().unwrap() = payment;
+    let crypto_currency = crypto_currency_service::find_by_id(&socket_data.db, crypto_currency_id)
+        .await?
+        .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
 
-    session
-        .text(
-            WsOutputMessage::PaymentUpdated(socket_data.payment.lock().unwrap().clone()).into_str(),
-        )
-        .await
-        .unwrap();
+    let fiat_currency =
+        fiat_currency_service::find_by_id(&socket_data.db, payment.fiat_currency_id)
+            .await?
+            .ok_or(NotFoundError::FiatCurrencyNotFoundWithGivenId)?;
+
+    let quote = payment_quote_service::fetch(
+        socket_data.price_oracle.get_ref(),
+        &socket_data.quote_policy,
+        &payment,
+        &fiat_currency,
+        &crypto_currency,
+    )
+    .await?;
 
     let network = network_service::find_by_id(&socket_data.db, crypto_currency.network_id)
         .await?
         .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
 
-    let socket_data_clone = Arc::clone(&socket_data);
-    let mut session = session.clone();
-
-    // ***** start payment task *****
-
-    let payment_task_handle = tokio::spawn(async move {
-        log::info!(
-            "Start subscribing transactions of payment with id: {}",
-            socket_data.payment.lock().unwrap().id
-        );
+    let wallet = wallet_service::reserve(&socket_data.db, &network, payment.id).await?;
 
-        // TODO: maybe remove this?
-        let payment = socket_data.payment.lock().unwrap().clone();
-
-        let transaction_result = web3_service::subscribe_transactions(
-            &network.websocket_address_url,
-            &wallet,
-            payment.crypto_amount.unwrap(),
-            payment.expired_at,
-            &mut session,
-            socket_data.db.clone(),
-        )
-        .await;
-
-        if !transaction_result {
-            session
-                .text(WsOutputMessage::PaymentExpired(payment).into_str())
-                .await
-                .unwrap();
-            // payment_exp_scheduler will free payment wallet and update it's status
-            return;
+    // the switch only happens if no concurrent request switched the payment since it was read,
+    // the loser gives its wallet back
+    let held_wallet_id = payment.dest_wallet_id;
+    let switched = payment_service::switch_crypto(
+        &socket_data.db,
+        payment.id,
+        held_wallet_id,
+        crypto_currency.id,
+        quote.crypto_amount.clone().unwrap(),
+        wallet.id,
+    )
+    .await?;
+    let payment = match switched {
+        Some(payment) => payment,
+        None => {
+            wallet_service::free(socket_data.db.get_ref(), wallet.id, payment.id).await?;
+            return Err(PaymentError::PaymentIsChangedConcurrently)?;
         }
+    };
 
-        wallet_service::free(&socket_data.db, payment.dest_wallet_id.unwrap())
-            .await
-            .unwrap();
-
-        let mut payment = payment::ActiveModel::from(socket_data.payment.lock().unwrap().clone());
-        payment.done_at = Set(Some(Utc::now().naive_utc()));
-        payment.status = Set(PaymentStatus::Done);
-
-        let payment = payment_service::update(&socket_data.db, payment)
-            .await
-            .unwrap();
+    if let Some(held_wallet_id) = held_wallet_id {
+        socket_data.payment_monitor.stop(payment.id);
+        wallet_service::free(socket_data.db.get_ref(), held_wallet_id, payment.id).await?;
+    }
 
-        log::info!("Payment with id {} is done!", payment.id);
+    payment_quote_service::create(&socket_data.db, quote).await?;
 
-        session
-            .text(WsOutputMessage::PaymentDone(payment).into_str())
-            .await
-            .unwrap();
-    });
+    let _ = session
+        .reply(request_id, WsOutputMessage::PaymentUpdated(payment.clone()))
+        .await;
 
-    *socket_data_clone.payment_task_handle.lock().unwrap() = Some(payment_task_handle);
+    // other clients following the payment
+    socket_data
+        .payment_monitor
+        .publish(payment.id, PaymentEvent::PaymentUpdated(payment.clone()));
+
+    socket_data.payment_monitor.start(
+        payment,
+        network,
+        crypto_currency,
+        wallet,
+        socket_data.db.clone(),
+    );
 
     Ok(())
 }
@@ -11,6 +11,40 @@ macro_rules! impl_crud {
                 .map_err(Into::<$into_err>::into)?)
         }
 
+        /// Find a page of the items matching the condition, sorted by the given column and
+        /// then by id so that pages are stable
+        #[allow(dead_code)]
+        pub async fn find_page(
+            db: &DbConn,
+            condition: sea_orm::Condition,
+            sort_by: $mod::Column,
+            pagination: &$crate::models::pagination::Pagination,
+        ) -> Result<$crate::models::pagination::Page<$mod::Model>, $into_err> {
+            use sea_orm::{EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+
+            let order: sea_orm::Order = pagination.order.into();
+            let paginator = <$struct>::find()
+                .filter(condition)
+                .order_by(sort_by, order.clone())
+                .order_by($mod::Column::Id, order)
+                .paginate(db, pagination.page_size);
+
+            let total_items = paginator
+                .num_items()
+                .await
+                .map_err(Into::<$into_err>::into)?;
+            let items = paginator
+                .fetch_page(pagination.page - 1)
+                .await
+                .map_err(Into::<$into_err>::into)?;
+
+            Ok($crate::models::pagination::Page::new(
+                items,
+                pagination,
+                total_items,
+            ))
+        }
+
         #[allow(dead_code)]
         pub async fn find_by_id(db: &DbConn, id: $id) -> Result<Option<$mod::Model>, $into_err> {
             use sea_orm::EntityTrait;
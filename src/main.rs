@@ -6,11 +6,19 @@ mod macros;
 mod models;
 mod security;
 mod services;
+#[cfg(test)]
+mod test_utils;
 
 use crate::config::AppConfig;
+use crate::services::{
+    bitcoin_service, chain_reconciler, merchant_event_bus::MerchantEventBus,
+    payment_monitor::PaymentMonitor, payment_service, signer::Signer, sweeper,
+    web3_service::NonceLocks, withdrawal_service,
+};
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 use actix_web_httpauth::middleware::HttpAuthentication;
+use std::sync::Arc;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -24,12 +32,64 @@ async fn main() -> std::io::Result<()> {
 
     let jwt_encoding_key = config.create_jwt_encoding_key().await;
     let jwt_decoding_key = config.create_jwt_decoding_key().await;
+    let price_oracle = config
+        .create_price_oracle()
+        .expect("Failed to setup the price oracle");
+    let wallet_keys = config
+        .create_wallet_keys()
+        .expect("Failed to setup the wallet keys");
+    let payout_provider = config.create_payout_provider();
+    let sweep_gas_signer = config
+        .create_sweep_gas_signer()
+        .expect("Failed to setup the sweep gas signer");
 
     let db_data = web::Data::new(db);
     let jwt_encoding_key_data = web::Data::new(jwt_encoding_key);
     let jwt_decoding_key_data = web::Data::new(jwt_decoding_key);
+    let price_oracle_data = web::Data::from(price_oracle);
+    let merchant_event_bus_data = web::Data::new(MerchantEventBus::default());
+    let quote_policy_data = web::Data::new(config.quote_policy());
+    let payment_monitor_data = web::Data::new(PaymentMonitor::new(
+        merchant_event_bus_data.get_ref().clone(),
+        price_oracle_data.clone(),
+        config.quote_policy(),
+    ));
+    let payout_provider_data = web::Data::from(payout_provider);
+    let wallet_keys_data = web::Data::new(wallet_keys);
+    let gas_signer_data =
+        sweep_gas_signer.map(|gas_signer| web::Data::from(Arc::new(gas_signer) as Arc<dyn Signer>));
+    // the gas wallet sends from both the sweeper and the refund handler
+    let nonce_locks_data = web::Data::new(NonceLocks::default());
     let config_data = web::Data::new(config.clone());
 
+    payment_service::spawn_payment_expiration_job(merchant_event_bus_data.clone(), db_data.clone());
+    withdrawal_service::spawn_payout_job(
+        payout_provider_data,
+        merchant_event_bus_data.clone(),
+        db_data.clone(),
+    );
+
+    if let Err(err) = payment_monitor_data
+        .resume_waiting_payments(db_data.clone())
+        .await
+    {
+        log::error!("Failed to resume watching waiting payments: {err}");
+    }
+
+    // picks up what was paid while the gateway was down
+    chain_reconciler::spawn_chain_reconciler(payment_monitor_data.clone(), db_data.clone());
+    bitcoin_service::spawn_address_recycler(db_data.clone());
+
+    match gas_signer_data {
+        Some(ref gas_signer) => sweeper::spawn_sweeper(
+            wallet_keys_data.clone(),
+            gas_signer.clone(),
+            nonce_locks_data.clone(),
+            db_data.clone(),
+        ),
+        None => log::warn!("Sweep gas key is not set, released wallets are not swept"),
+    }
+
     HttpServer::new(move || {
         App::new()
             .wrap(Logger::default())
@@ -37,14 +97,32 @@ async fn main() -> std::io::Result<()> {
             .app_data(config_data.clone())
             .app_data(jwt_encoding_key_data.clone())
             .app_data(jwt_decoding_key_data.clone())
+            .app_data(price_oracle_data.clone())
+            .app_data(quote_policy_data.clone())
+            .app_data(merchant_event_bus_data.clone())
+            .app_data(payment_monitor_data.clone())
+            .app_data(wallet_keys_data.clone())
+            .app_data(nonce_locks_data.clone())
             .app_data(db_data.clone())
+            .configure(|cfg| {
+                if let Some(ref gas_signer) = gas_signer_data {
+                    cfg.app_data(gas_signer.clone());
+                }
+            })
             .configure(handlers::auth_handler::config)
             .configure(handlers::ws_handler::config)
+            .configure(handlers::sse_handler::config)
             .service(
                 web::scope("/api")
                     .wrap(HttpAuthentication::with_fn(security::jwt::validator))
                     .configure(handlers::user_handler::config)
+                    .configure(handlers::api_key_handler::config)
+                    .configure(handlers::merchant_event_handler::config)
                     .configure(handlers::payment_handler::config)
+                    .configure(handlers::payment_policy_handler::config)
+                    .configure(handlers::refund_handler::config)
+                    .configure(handlers::sweep_handler::config)
+                    .configure(handlers::withdrawal_handler::config)
                     .configure(handlers::asset_handler::config),
             )
     })
@@ -1,6 +1,15 @@
-use sea_orm::prelude::Decimal;
+use crate::entities::network::NetworkKind;
+use crate::entities::payment::PaymentStatus;
+use crate::entities::payment_policy::OverpaymentAction;
+use crate::entities::refund;
+use crate::entities::sweep::SweepStatus;
+use crate::entities::user_transaction::UserTransactionType;
+use crate::entities::withdrawal::{WithdrawalDestinationType, WithdrawalStatus};
+use crate::security::api_key;
+use crate::services::web3_service;
+use sea_orm::prelude::{DateTime, Decimal};
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 #[derive(Deserialize, Clone, Debug, Validate)]
 pub struct CreateUser {
@@ -21,14 +30,82 @@ pub struct LoginUser {
 }
 
 #[derive(Deserialize, Clone, Debug, Validate)]
+pub struct RefreshSession {
+    #[validate(length(min = 1))]
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct ChangePassword {
+    #[validate(length(min = 3))]
+    pub current_password: String,
+
+    #[validate(length(min = 3))]
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct CreateApiKey {
+    #[validate(length(min = 1, max = 50))]
+    pub name: String,
+
+    #[validate(length(min = 1), custom = "validate_scopes")]
+    pub scopes: Vec<String>,
+}
+
+fn validate_scopes(scopes: &Vec<String>) -> Result<(), ValidationError> {
+    if scopes.iter().all(|scope| api_key::is_valid_scope(scope)) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("scopes"))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+#[validate(schema(function = "validate_network"))]
 pub struct CreateNetwork {
     pub name: String,
 
+    #[serde(default)]
+    pub kind: NetworkKind,
+
     #[validate(url)]
     pub http_address_url: String,
 
     #[validate(url)]
     pub websocket_address_url: String,
+
+    #[validate(range(min = 1))]
+    pub required_confirmations: Option<i32>,
+
+    #[validate(custom = "validate_extended_public_key")]
+    pub extended_public_key: Option<String>,
+
+    pub treasury_address: Option<String>,
+}
+
+/// Addresses are only derived, and funds only swept, on EVM networks
+fn validate_network(network: &CreateNetwork) -> Result<(), ValidationError> {
+    if network.kind != NetworkKind::Evm && network.extended_public_key.is_some() {
+        return Err(ValidationError::new("extended_public_key"));
+    }
+    if network.kind != NetworkKind::Evm && network.treasury_address.is_some() {
+        return Err(ValidationError::new("treasury_address"));
+    }
+
+    Ok(())
+}
+
+fn validate_extended_public_key(extended_public_key: &str) -> Result<(), ValidationError> {
+    web3_service::parse_extended_public_key(extended_public_key)
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("extended_public_key"))
+}
+
+/// Treasury the network's wallets are swept to, or none to stop sweeping them
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct SetNetworkTreasury {
+    pub treasury_address: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Debug, Validate)]
@@ -36,6 +113,19 @@ pub struct CreateCryptoCurrency {
     pub name: String,
     pub symbol: String,
     pub network_id: i32,
+
+    #[validate(custom = "validate_evm_address")]
+    pub contract_address: Option<String>,
+
+    #[validate(range(min = 0, max = 28))]
+    pub decimals: Option<i32>,
+}
+
+fn validate_evm_address(address: &str) -> Result<(), ValidationError> {
+    address
+        .parse::<ethers::types::Address>()
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("address"))
 }
 
 #[derive(Deserialize, Clone, Debug, Validate)]
@@ -44,6 +134,29 @@ pub struct CreateWallet {
     pub network_id: i32,
 }
 
+/// Private key of a wallet, in hex or as a keystore v3 with its password
+#[derive(Deserialize, Clone, Debug, Validate)]
+#[validate(schema(function = "validate_wallet_key"))]
+pub struct ImportWalletKey {
+    pub private_key: Option<String>,
+    pub keystore: Option<serde_json::Value>,
+    pub password: Option<String>,
+}
+
+fn validate_wallet_key(key: &ImportWalletKey) -> Result<(), ValidationError> {
+    match (&key.private_key, &key.keystore, &key.password) {
+        (Some(_), None, None) | (None, Some(_), Some(_)) => Ok(()),
+        _ => Err(ValidationError::new("key")),
+    }
+}
+
+/// Password of the keystore a wallet key is exported in
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct ExportWalletKey {
+    #[validate(length(min = 8))]
+    pub password: String,
+}
+
 #[derive(Deserialize, Clone, Debug, Validate)]
 pub struct CreateFiatCurrency {
     pub name: String,
@@ -75,18 +188,204 @@ pub struct CreatePayment {
     pub payer_mail: Option<String>,
 }
 
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentSortField {
+    #[default]
+    CreatedAt,
+    ExpiredAt,
+    Amount,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct PaymentFilter {
+    pub status: Option<PaymentStatus>,
+    pub fiat_currency_id: Option<i32>,
+    pub crypto_currency_id: Option<i32>,
+
+    #[validate(length(max = 50))]
+    pub seller_order_id: Option<String>,
+
+    pub created_from: Option<DateTime>,
+    pub created_to: Option<DateTime>,
+
+    #[serde(default)]
+    pub sort_by: PaymentSortField,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UserTransactionSortField {
+    #[default]
+    CreatedAt,
+    Amount,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct SweepFilter {
+    pub network_id: Option<i32>,
+    pub wallet_id: Option<i32>,
+    pub status: Option<SweepStatus>,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct UserTransactionFilter {
+    #[serde(rename = "type")]
+    pub typ: Option<UserTransactionType>,
+    pub fiat_currency_id: Option<i32>,
+    pub created_from: Option<DateTime>,
+    pub created_to: Option<DateTime>,
+
+    #[serde(default)]
+    pub sort_by: UserTransactionSortField,
+}
+
+/// Narrows the merchant event stream, events without a filtered field are left out, so
+/// filtering by status only keeps payment status changes
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct MerchantEventFilter {
+    pub status: Option<PaymentStatus>,
+    pub fiat_currency_id: Option<i32>,
+    pub crypto_currency_id: Option<i32>,
+}
+
+/// Policy of the merchant for a crypto currency, or their default one without currency
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct SetPaymentPolicy {
+    pub crypto_currency_id: Option<i32>,
+    pub underpayment_tolerance_amount: Option<Decimal>,
+    pub underpayment_tolerance_percent: Option<Decimal>,
+    pub overpayment_action: OverpaymentAction,
+}
+
 #[derive(Deserialize, Clone, Debug, Validate)]
 pub struct VerifyPayment {
     pub id: i32,
 }
 
+/// Withdrawal of the balance to an IBAN, with an optional reference for the bank transfer, or to
+/// an address of the network of a crypto currency
 #[derive(Deserialize, Clone, Debug, Validate)]
+#[validate(schema(function = "validate_withdrawal_destination"))]
 pub struct BalanceWithdrawal {
     pub fiat_currency_id: i32,
     pub amount: Decimal,
+    pub destination_type: WithdrawalDestinationType,
+
+    #[validate(length(min = 1, max = 255))]
+    pub destination: String,
+
+    #[validate(length(min = 1, max = 140))]
+    pub destination_reference: Option<String>,
+
+    pub crypto_currency_id: Option<i32>,
 }
+
+impl BalanceWithdrawal {
+    /// Destination as it is stored, IBANs are written without spaces and in upper case
+    pub fn normalized_destination(&self) -> String {
+        match self.destination_type {
+            WithdrawalDestinationType::BankAccount => normalize_iban(&self.destination),
+            WithdrawalDestinationType::CryptoAddress => self.destination.trim().to_owned(),
+        }
+    }
+}
+
+fn validate_withdrawal_destination(withdrawal: &BalanceWithdrawal) -> Result<(), ValidationError> {
+    let valid = match withdrawal.destination_type {
+        WithdrawalDestinationType::BankAccount => {
+            withdrawal.crypto_currency_id.is_none() && is_valid_iban(&withdrawal.destination)
+        }
+        WithdrawalDestinationType::CryptoAddress => {
+            withdrawal.crypto_currency_id.is_some() && withdrawal.destination_reference.is_none()
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("destination"))
+    }
+}
+
+fn normalize_iban(iban: &str) -> String {
+    iban.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Check the structure and the ISO 7064 mod 97-10 check digits of the IBAN
+fn is_valid_iban(iban: &str) -> bool {
+    let iban = normalize_iban(iban);
+
+    if !(15..=34).contains(&iban.len())
+        || !iban.chars().all(|c| c.is_ascii_alphanumeric())
+        || !iban[..2].chars().all(|c| c.is_ascii_alphabetic())
+        || !iban[2..4].chars().all(|c| c.is_ascii_digit())
+    {
+        return false;
+    }
+
+    // the country code and check digits go last, and letters count as 10 to 35
+    let remainder = iban[4..]
+        .chars()
+        .chain(iban[..4].chars())
+        .fold(0, |remainder, c| {
+            // unwrap: the IBAN is alphanumeric
+            let value = c.to_digit(36).unwrap();
+            let shift = if value < 10 { 10 } else { 100 };
+
+            (remainder * shift + value) % 97
+        });
+
+    remainder == 1
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct WithdrawalFilter {
+    pub status: Option<WithdrawalStatus>,
+    pub fiat_currency_id: Option<i32>,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct RejectWithdrawal {
+    #[validate(length(min = 1, max = 255))]
+    pub reason: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct CreateRefund {
+    pub amount: Decimal,
+
+    /// Checked against the network of the payment's crypto currency
+    #[validate(length(min = 1))]
+    pub destination_address: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct SentRefund {
+    #[validate(length(min = 1))]
+    pub transaction_hash: String,
+}
+
+/// Wallet controlled by the gateway to send a refund from, the gas wallet by default
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct SendRefund {
+    pub wallet_id: Option<i32>,
+}
+
 #[derive(Serialize)]
 pub struct FiatBalance {
     pub fiat_currency_id: i32,
+    /// What can be withdrawn
     pub balance: Decimal,
+    /// What withdrawals in progress hold, until they are paid or rejected
+    pub held: Decimal,
+}
+
+#[derive(Serialize)]
+pub struct PaymentRefunds {
+    pub refundable_amount: Decimal,
+    pub refunds: Vec<refund::Model>,
 }
@@ -0,0 +1,136 @@
+use crate::entities::payment::{self, PaymentStatus};
+use crate::entities::{user_transaction, withdrawal};
+use crate::models::dtos::MerchantEventFilter;
+use crate::models::payment_event::Utxo;
+use derive_more::Display;
+use ethers::types::{Log, Transaction};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaymentStatusChange {
+    pub payment: payment::Model,
+    pub previous_status: PaymentStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedTransaction {
+    pub payment_id: i32,
+    pub fiat_currency_id: i32,
+    pub crypto_currency_id: i32,
+    pub transaction: Transaction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedTokenTransfer {
+    pub payment_id: i32,
+    pub fiat_currency_id: i32,
+    pub crypto_currency_id: i32,
+    pub log: Log,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedUtxo {
+    pub payment_id: i32,
+    pub fiat_currency_id: i32,
+    pub crypto_currency_id: i32,
+    pub utxo: Utxo,
+}
+
+/// Activity on the account of a merchant, published to their event streams
+#[derive(Debug, Clone, Display)]
+pub enum MerchantEvent {
+    #[display(fmt = "PAYMENT_STATUS_CHANGED")]
+    PaymentStatusChanged(PaymentStatusChange),
+
+    #[display(fmt = "TRANSACTION_RECEIVED")]
+    TransactionReceived(ReceivedTransaction),
+
+    #[display(fmt = "TOKEN_TRANSFER_RECEIVED")]
+    TokenTransferReceived(ReceivedTokenTransfer),
+
+    #[display(fmt = "UTXO_RECEIVED")]
+    UtxoReceived(ReceivedUtxo),
+
+    /// A deposit of a finished payment or a paid withdrawal of the balance
+    #[display(fmt = "USER_TRANSACTION_CREATED")]
+    UserTransactionCreated(user_transaction::Model),
+
+    #[display(fmt = "WITHDRAWAL_STATUS_CHANGED")]
+    WithdrawalStatusChanged(withdrawal::Model),
+}
+
+impl MerchantEvent {
+    pub fn payment_status_changed(payment: payment::Model, previous_status: PaymentStatus) -> Self {
+        MerchantEvent::PaymentStatusChanged(PaymentStatusChange {
+            payment,
+            previous_status,
+        })
+    }
+
+    /// Payload of the event, its type is given by `Display`
+    pub fn data(&self) -> serde_json::Value {
+        match self {
+            MerchantEvent::PaymentStatusChanged(ref change) => {
+                serde_json::to_value(change).unwrap()
+            }
+            MerchantEvent::TransactionReceived(ref transaction) => {
+                serde_json::to_value(transaction).unwrap()
+            }
+            MerchantEvent::TokenTransferReceived(ref transfer) => {
+                serde_json::to_value(transfer).unwrap()
+            }
+            MerchantEvent::UtxoReceived(ref utxo) => serde_json::to_value(utxo).unwrap(),
+            MerchantEvent::UserTransactionCreated(ref transaction) => {
+                serde_json::to_value(transaction).unwrap()
+            }
+            MerchantEvent::WithdrawalStatusChanged(ref withdrawal) => {
+                serde_json::to_value(withdrawal).unwrap()
+            }
+        }
+    }
+
+    /// An event passes a filter only if it has every filtered field and they all match
+    pub fn matches(&self, filter: &MerchantEventFilter) -> bool {
+        let (status, fiat_currency_id, crypto_currency_id) = match self {
+            MerchantEvent::PaymentStatusChanged(ref change) => (
+                Some(&change.payment.status),
+                Some(change.payment.fiat_currency_id),
+                change.payment.crypto_currency_id,
+            ),
+            MerchantEvent::TransactionReceived(ref transaction) => (
+                None,
+                Some(transaction.fiat_currency_id),
+                Some(transaction.crypto_currency_id),
+            ),
+            MerchantEvent::TokenTransferReceived(ref transfer) => (
+                None,
+                Some(transfer.fiat_currency_id),
+                Some(transfer.crypto_currency_id),
+            ),
+            MerchantEvent::UtxoReceived(ref utxo) => (
+                None,
+                Some(utxo.fiat_currency_id),
+                Some(utxo.crypto_currency_id),
+            ),
+            MerchantEvent::UserTransactionCreated(ref transaction) => {
+                (None, Some(transaction.fiat_currency_id), None)
+            }
+            MerchantEvent::WithdrawalStatusChanged(ref withdrawal) => (
+                None,
+                Some(withdrawal.fiat_currency_id),
+                withdrawal.crypto_currency_id,
+            ),
+        };
+
+        filter
+            .status
+            .as_ref()
+            .map_or(true, |filtered| status == Some(filtered))
+            && filter
+                .fiat_currency_id
+                .map_or(true, |filtered| fiat_currency_id == Some(filtered))
+            && filter
+                .crypto_currency_id
+                .map_or(true, |filtered| crypto_currency_id == Some(filtered))
+    }
+}
@@ -1,2 +1,7 @@
 pub mod dtos;
+pub mod merchant_event;
+pub mod pagination;
+pub mod payment_event;
+pub mod sse;
+pub mod webhook;
 pub mod ws;
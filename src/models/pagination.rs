@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+
+#[derive(Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl From<SortOrder> for sea_orm::Order {
+    fn from(value: SortOrder) -> Self {
+        match value {
+            SortOrder::Asc => sea_orm::Order::Asc,
+            SortOrder::Desc => sea_orm::Order::Desc,
+        }
+    }
+}
+
+/// Page based pagination query parameters, pages start from 1
+#[derive(Deserialize, Clone, Debug, Validate)]
+pub struct Pagination {
+    #[serde(default = "default_page")]
+    #[validate(range(min = 1))]
+    pub page: u64,
+
+    #[serde(default = "default_page_size")]
+    #[validate(range(min = 1, max = 100))]
+    pub page_size: u64,
+
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+fn default_page() -> u64 {
+    1
+}
+
+fn default_page_size() -> u64 {
+    DEFAULT_PAGE_SIZE
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: u64,
+    pub page_size: u64,
+    pub total_items: u64,
+    pub total_pages: u64,
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, pagination: &Pagination, total_items: u64) -> Self {
+        Page {
+            items,
+            page: pagination.page,
+            page_size: pagination.page_size,
+            total_items,
+            total_pages: (total_items + pagination.page_size - 1) / pagination.page_size,
+        }
+    }
+}
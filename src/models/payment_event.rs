@@ -0,0 +1,43 @@
+use crate::entities::payment;
+use ethers::types::{Log, Transaction, TransactionReceipt, U64};
+use sea_orm::prelude::Decimal;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionConfirmation {
+    pub hash: String,
+    pub block_number: U64,
+    pub confirmations: u64,
+    pub required_confirmations: u64,
+}
+
+/// Output of a bitcoin transaction paying the wallet of a payment
+#[derive(Debug, Clone, Serialize)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    pub address: String,
+    pub value: Decimal,
+}
+
+/// Progress of a monitored payment, published to every client watching it
+#[derive(Debug, Clone)]
+pub enum PaymentEvent {
+    PaymentUpdated(payment::Model),
+    PaymentDone(payment::Model),
+    PaymentExpired(payment::Model),
+    PaymentPartiallyPaid(payment::Model),
+    PaymentOverpaid(payment::Model),
+    TransactionReceived(Transaction),
+    TokenTransferReceived(Log),
+    UtxoReceived(Utxo),
+    TransactionConfirmation(TransactionConfirmation),
+    TransactionFailed(TransactionReceipt),
+}
+
+/// Payment event with its id, which increases with every published event
+#[derive(Debug, Clone)]
+pub struct SequencedPaymentEvent {
+    pub id: u64,
+    pub event: PaymentEvent,
+}
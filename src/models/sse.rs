@@ -0,0 +1,114 @@
+use crate::models::{merchant_event::MerchantEvent, payment_event::SequencedPaymentEvent};
+use crate::services::{
+    merchant_event_bus::MerchantSubscription, payment_monitor::PaymentSubscription,
+};
+use actix_web::{
+    http::header::{CacheControl, CacheDirective},
+    web::Bytes,
+    HttpResponse, HttpResponseBuilder,
+};
+use async_trait::async_trait;
+use futures_util::{
+    future::{self, Either},
+    stream, Stream,
+};
+use std::{convert::Infallible, fmt::Display, time::Duration};
+use tokio::{
+    pin,
+    sync::broadcast::error::RecvError,
+    time::{interval_at, Instant},
+};
+
+/// How often a comment is sent so that proxies don't close an idle stream
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Events followed by a client of a Server-Sent Events stream
+#[async_trait]
+pub trait EventSubscription: Send + 'static {
+    type Event: Send;
+
+    async fn recv(&mut self) -> Result<Self::Event, RecvError>;
+}
+
+#[async_trait]
+impl EventSubscription for PaymentSubscription {
+    type Event = SequencedPaymentEvent;
+
+    async fn recv(&mut self) -> Result<SequencedPaymentEvent, RecvError> {
+        PaymentSubscription::recv(self).await
+    }
+}
+
+#[async_trait]
+impl EventSubscription for MerchantSubscription {
+    type Event = MerchantEvent;
+
+    async fn recv(&mut self) -> Result<MerchantEvent, RecvError> {
+        MerchantSubscription::recv(self).await
+    }
+}
+
+/// Response of an event stream, its body is still to be given
+pub fn response() -> HttpResponseBuilder {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        // ask buffering proxies to pass events through as they come
+        .insert_header(("X-Accel-Buffering", "no"))
+        .take()
+}
+
+pub fn encode_event(id: Option<u64>, event: impl Display, data: impl Display) -> Bytes {
+    let id = id.map(|id| format!("id: {id}\n")).unwrap_or_default();
+
+    Bytes::from(format!("{id}event: {event}\ndata: {data}\n\n"))
+}
+
+/// Stream the events of the subscription as `encode` gives them, skipping those it gives
+/// `None` for, with keep-alive comments while nothing happens. The stream ends with the
+/// subscription's channel.
+pub fn live_events<S, F>(
+    subscription: S,
+    encode: F,
+) -> impl Stream<Item = Result<Bytes, Infallible>>
+where
+    S: EventSubscription,
+    F: FnMut(S::Event) -> Option<Bytes> + Send + 'static,
+{
+    let keep_alive = interval_at(Instant::now() + KEEP_ALIVE_INTERVAL, KEEP_ALIVE_INTERVAL);
+
+    stream::unfold(
+        (subscription, keep_alive, encode),
+        |(mut subscription, mut keep_alive, mut encode)| async move {
+            let chunk = loop {
+                let tick = keep_alive.tick();
+                let event = subscription.recv();
+                // required for select()
+                pin!(tick);
+                pin!(event);
+
+                match future::select(event, tick).await {
+                    Either::Left((Ok(event), _)) => {
+                        if let Some(chunk) = encode(event) {
+                            break chunk;
+                        }
+                    }
+
+                    Either::Left((Err(RecvError::Lagged(skipped)), _)) => {
+                        log::warn!("client is {skipped} events behind; skipping them");
+                    }
+
+                    // nothing will be published anymore, end the stream
+                    Either::Left((Err(RecvError::Closed), _)) => return None,
+
+                    Either::Right(_) => break Bytes::from_static(b": keep-alive\n\n"),
+                }
+            };
+
+            Some((
+                Ok::<_, Infallible>(chunk),
+                (subscription, keep_alive, encode),
+            ))
+        },
+    )
+}
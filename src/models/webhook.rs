@@ -0,0 +1,33 @@
+use crate::entities::payment::{self, PaymentStatus};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::prelude::Decimal;
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PaymentWebhookEvent {
+    pub payment_id: i32,
+    pub seller_order_id: String,
+    pub old_status: PaymentStatus,
+    pub new_status: PaymentStatus,
+    pub fiat_currency_id: i32,
+    pub amount: Decimal,
+    pub crypto_currency_id: Option<i32>,
+    pub crypto_amount: Option<Decimal>,
+    pub occurred_at: NaiveDateTime,
+}
+
+impl PaymentWebhookEvent {
+    pub fn new(payment: &payment::Model, old_status: PaymentStatus) -> Self {
+        Self {
+            payment_id: payment.id,
+            seller_order_id: payment.seller_order_id.clone(),
+            old_status,
+            new_status: payment.status.clone(),
+            fiat_currency_id: payment.fiat_currency_id,
+            amount: payment.amount,
+            crypto_currency_id: payment.crypto_currency_id,
+            crypto_amount: payment.crypto_amount,
+            occurred_at: Utc::now().naive_utc(),
+        }
+    }
+}
@@ -1,8 +1,50 @@
 use crate::entities::payment;
+use crate::errors::{NotFoundError, PaymentError, PriceOracleError};
+use crate::models::payment_event::{PaymentEvent, TransactionConfirmation, Utxo};
 use derive_more::Display;
-use ethers::types::Transaction;
+use ethers::types::{Log, Transaction, TransactionReceipt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use thiserror::Error;
 
+/// WebSocket subprotocol of the JSON protocol, clients which don't ask for it get the
+/// legacy text protocol
+pub const JSON_PROTOCOL_V1: &str = "payment-gateway.v1.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsProtocol {
+    /// `/COMMAND arg` requests and `MESSAGE_TYPE {json}` replies
+    LegacyText,
+    /// `{"type": ..., "data": ...}` envelopes
+    JsonV1,
+}
+
+impl WsProtocol {
+    /// Pick the protocol from the subprotocols offered in `Sec-WebSocket-Protocol`
+    pub fn negotiate(offered_protocols: Option<&str>) -> Self {
+        let offers_json_v1 = offered_protocols
+            .map(|protocols| {
+                protocols
+                    .split(',')
+                    .any(|protocol| protocol.trim() == JSON_PROTOCOL_V1)
+            })
+            .unwrap_or(false);
+
+        if offers_json_v1 {
+            WsProtocol::JsonV1
+        } else {
+            WsProtocol::LegacyText
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            WsProtocol::LegacyText => 0,
+            WsProtocol::JsonV1 => 1,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum WsInputMessage {
     ChooseCrypto(i32),
@@ -16,6 +58,55 @@ pub enum WsInputMessageParseError {
     NoCommandArgument,
     #[error("Bad command argument")]
     BadCommandArgument,
+    #[error("Message is not a valid envelope: {0}")]
+    BadEnvelope(String),
+}
+
+/// Request id chosen by the client, echoed back in the replies to the request
+pub type WsRequestId = Option<serde_json::Value>;
+
+#[derive(Deserialize)]
+struct WsInputEnvelope {
+    request_id: WsRequestId,
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ChooseCryptoData {
+    crypto_currency_id: i32,
+}
+
+impl WsInputMessage {
+    pub fn parse(
+        msg: &str,
+        protocol: WsProtocol,
+    ) -> Result<(WsRequestId, Self), (WsRequestId, WsInputMessageParseError)> {
+        match protocol {
+            WsProtocol::LegacyText => WsInputMessage::try_from(msg)
+                .map(|input_msg| (None, input_msg))
+                .map_err(|err| (None, err)),
+            WsProtocol::JsonV1 => {
+                let envelope = serde_json::from_str::<WsInputEnvelope>(msg).map_err(|err| {
+                    (None, WsInputMessageParseError::BadEnvelope(err.to_string()))
+                })?;
+
+                let input_msg = match envelope.typ.as_str() {
+                    "CHOOSE_CRYPTO" => serde_json::from_value::<ChooseCryptoData>(envelope.data)
+                        .map(|data| WsInputMessage::ChooseCrypto(data.crypto_currency_id))
+                        .map_err(|_| WsInputMessageParseError::BadCommandArgument),
+                    _ => Err(WsInputMessageParseError::CommandNotFound),
+                };
+
+                match input_msg {
+                    Ok(input_msg) => Ok((envelope.request_id, input_msg)),
+                    Err(err) => Err((envelope.request_id, err)),
+                }
+            }
+        }
+    }
 }
 
 impl TryFrom<&str> for WsInputMessage {
@@ -47,10 +138,71 @@ impl TryFrom<&str> for WsInputMessage {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum WsErrorCode {
+    UnknownCommand,
+    InvalidArgument,
+    InvalidMessage,
+    NotFound,
+    PaymentNotPayable,
+    NoFreeWallet,
+    PriceUnavailable,
+    Internal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WsError {
+    pub code: WsErrorCode,
+    pub message: String,
+}
+
+impl From<WsInputMessageParseError> for WsError {
+    fn from(value: WsInputMessageParseError) -> Self {
+        let code = match value {
+            WsInputMessageParseError::CommandNotFound => WsErrorCode::UnknownCommand,
+            WsInputMessageParseError::NoCommandArgument
+            | WsInputMessageParseError::BadCommandArgument => WsErrorCode::InvalidArgument,
+            WsInputMessageParseError::BadEnvelope(_) => WsErrorCode::InvalidMessage,
+        };
+
+        WsError {
+            code,
+            message: value.to_string(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for WsError {
+    fn from(value: anyhow::Error) -> Self {
+        let code = if value.is::<NotFoundError>() {
+            WsErrorCode::NotFound
+        } else if value.is::<PriceOracleError>() {
+            WsErrorCode::PriceUnavailable
+        } else {
+            match value.downcast_ref::<PaymentError>() {
+                Some(PaymentError::PaymentIsNotPayable(_)) => WsErrorCode::PaymentNotPayable,
+                Some(PaymentError::NotFreeWallet) => WsErrorCode::NoFreeWallet,
+                _ => WsErrorCode::Internal,
+            }
+        };
+
+        // internal details are only logged, like in HTTP responses
+        let message = if code == WsErrorCode::Internal {
+            log::error!("Payment websocket error: {value:?}");
+            "Internal server error".to_owned()
+        } else {
+            value.to_string()
+        };
+
+        WsError { code, message }
+    }
+}
+
 #[derive(Debug, Display)]
 pub enum WsOutputMessage {
     #[display(fmt = "ERROR")]
-    Error(anyhow::Error),
+    Error(WsError),
 
     #[display(fmt = "PAYMENT_UPDATED")]
     PaymentUpdated(payment::Model),
@@ -61,25 +213,136 @@ pub enum WsOutputMessage {
     #[display(fmt = "PAYMENT_EXPIRED")]
     PaymentExpired(payment::Model),
 
+    #[display(fmt = "PAYMENT_PARTIALLY_PAID")]
+    PaymentPartiallyPaid(payment::Model),
+
+    #[display(fmt = "PAYMENT_OVERPAID")]
+    PaymentOverpaid(payment::Model),
+
     #[display(fmt = "TRANSACTION_RECEIVED")]
     TransactionReceived(Transaction),
+
+    #[display(fmt = "TOKEN_TRANSFER_RECEIVED")]
+    TokenTransferReceived(Log),
+
+    #[display(fmt = "UTXO_RECEIVED")]
+    UtxoReceived(Utxo),
+
+    #[display(fmt = "TRANSACTION_CONFIRMATION")]
+    TransactionConfirmation(TransactionConfirmation),
+
+    #[display(fmt = "TRANSACTION_FAILED")]
+    TransactionFailed(TransactionReceipt),
 }
 
 impl WsOutputMessage {
+    /// Encode the message in the legacy text protocol
     pub fn into_str(self) -> String {
         let param = match self {
-            WsOutputMessage::Error(ref err) => serde_json::to_value(err.to_string()).unwrap(),
+            WsOutputMessage::Error(ref err) => serde_json::to_value(&err.message).unwrap(),
+            _ => self.data(),
+        };
+        format!("{} {}", self, param)
+    }
+
+    /// Encode the message in the given protocol, replying to the request with given id if any
+    pub fn encode(self, protocol: WsProtocol, request_id: WsRequestId) -> String {
+        match protocol {
+            WsProtocol::LegacyText => self.into_str(),
+            WsProtocol::JsonV1 => {
+                let mut envelope = json!({
+                    "type": self.to_string(),
+                    "version": protocol.version(),
+                    "data": self.data(),
+                });
+                if let Some(request_id) = request_id {
+                    envelope["request_id"] = request_id;
+                }
+
+                envelope.to_string()
+            }
+        }
+    }
+
+    /// Payload of the message, its type is given by `Display`
+    pub fn data(&self) -> serde_json::Value {
+        match self {
+            WsOutputMessage::Error(ref err) => serde_json::to_value(err).unwrap(),
 
             WsOutputMessage::PaymentUpdated(ref payment)
             | WsOutputMessage::PaymentDone(ref payment)
-            | WsOutputMessage::PaymentExpired(ref payment) => {
+            | WsOutputMessage::PaymentExpired(ref payment)
+            | WsOutputMessage::PaymentPartiallyPaid(ref payment)
+            | WsOutputMessage::PaymentOverpaid(ref payment) => {
                 serde_json::to_value(payment).unwrap()
             }
 
             WsOutputMessage::TransactionReceived(ref transaction) => {
                 serde_json::to_value(transaction).unwrap()
             }
-        };
-        format!("{} {}", self.to_string(), param)
+
+            WsOutputMessage::TokenTransferReceived(ref log) => serde_json::to_value(log).unwrap(),
+
+            WsOutputMessage::UtxoReceived(ref utxo) => serde_json::to_value(utxo).unwrap(),
+
+            WsOutputMessage::TransactionConfirmation(ref confirmation) => {
+                serde_json::to_value(confirmation).unwrap()
+            }
+
+            WsOutputMessage::TransactionFailed(ref receipt) => {
+                serde_json::to_value(receipt).unwrap()
+            }
+        }
+    }
+}
+
+impl From<PaymentEvent> for WsOutputMessage {
+    fn from(value: PaymentEvent) -> Self {
+        match value {
+            PaymentEvent::PaymentUpdated(payment) => WsOutputMessage::PaymentUpdated(payment),
+            PaymentEvent::PaymentDone(payment) => WsOutputMessage::PaymentDone(payment),
+            PaymentEvent::PaymentExpired(payment) => WsOutputMessage::PaymentExpired(payment),
+            PaymentEvent::PaymentPartiallyPaid(payment) => {
+                WsOutputMessage::PaymentPartiallyPaid(payment)
+            }
+            PaymentEvent::PaymentOverpaid(payment) => WsOutputMessage::PaymentOverpaid(payment),
+            PaymentEvent::TransactionReceived(transaction) => {
+                WsOutputMessage::TransactionReceived(transaction)
+            }
+            PaymentEvent::TokenTransferReceived(log) => WsOutputMessage::TokenTransferReceived(log),
+            PaymentEvent::UtxoReceived(utxo) => WsOutputMessage::UtxoReceived(utxo),
+            PaymentEvent::TransactionConfirmation(confirmation) => {
+                WsOutputMessage::TransactionConfirmation(confirmation)
+            }
+            PaymentEvent::TransactionFailed(receipt) => WsOutputMessage::TransactionFailed(receipt),
+        }
+    }
+}
+
+/// Payment websocket session which sends messages in the protocol negotiated with the client
+#[derive(Clone)]
+pub struct WsSession {
+    session: actix_ws::Session,
+    protocol: WsProtocol,
+}
+
+impl WsSession {
+    pub fn new(session: actix_ws::Session, protocol: WsProtocol) -> Self {
+        WsSession { session, protocol }
+    }
+
+    /// Send a message which isn't a reply to any request
+    pub async fn send(&mut self, msg: WsOutputMessage) -> Result<(), actix_ws::Closed> {
+        self.reply(None, msg).await
+    }
+
+    pub async fn reply(
+        &mut self,
+        request_id: WsRequestId,
+        msg: WsOutputMessage,
+    ) -> Result<(), actix_ws::Closed> {
+        self.session
+            .text(msg.encode(self.protocol, request_id))
+            .await
     }
 }
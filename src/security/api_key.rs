@@ -0,0 +1,62 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+pub const PAYMENTS_CREATE_SCOPE: &str = "payments:create";
+pub const PAYMENTS_READ_SCOPE: &str = "payments:read";
+pub const BALANCE_WITHDRAW_SCOPE: &str = "balance:withdraw";
+
+pub const SCOPES: [&str; 3] = [
+    PAYMENTS_CREATE_SCOPE,
+    PAYMENTS_READ_SCOPE,
+    BALANCE_WITHDRAW_SCOPE,
+];
+
+/// Marks bearer tokens which are API keys rather than JWTs
+const KEY_MARKER: &str = "cpg_";
+
+const PREFIX_LENGTH_IN_BYTES: usize = 8;
+const SECRET_LENGTH_IN_BYTES: usize = 32;
+
+/// Generate a new API key, returning its lookup prefix and the full key.
+///
+/// Keys look like `cpg_<prefix>_<secret>`, only their hash is stored so the full key
+/// can't be shown again after creation.
+pub fn generate_key() -> (String, String) {
+    let mut prefix = [0u8; PREFIX_LENGTH_IN_BYTES];
+    OsRng.fill_bytes(&mut prefix);
+    let prefix = hex::encode(prefix);
+
+    let mut secret = [0u8; SECRET_LENGTH_IN_BYTES];
+    OsRng.fill_bytes(&mut secret);
+
+    let key = format!("{KEY_MARKER}{prefix}_{}", hex::encode(secret));
+
+    (prefix, key)
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(KEY_MARKER)
+}
+
+/// Lookup prefix of the key, `None` if the token isn't a well formed API key
+pub fn parse_prefix(key: &str) -> Option<&str> {
+    let (prefix, secret) = key.strip_prefix(KEY_MARKER)?.split_once('_')?;
+
+    if prefix.len() != PREFIX_LENGTH_IN_BYTES * 2 || secret.len() != SECRET_LENGTH_IN_BYTES * 2 {
+        return None;
+    }
+
+    Some(prefix)
+}
+
+pub fn is_valid_scope(scope: &str) -> bool {
+    SCOPES.contains(&scope)
+}
+
+/// Scopes are stored space separated, like OAuth scopes
+pub fn join_scopes(scopes: &[String]) -> String {
+    scopes.join(" ")
+}
+
+pub fn split_scopes(scopes: &str) -> Vec<String> {
+    scopes.split_whitespace().map(ToOwned::to_owned).collect()
+}
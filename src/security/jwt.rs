@@ -1,4 +1,6 @@
-use crate::entities::user;
+use crate::entities::{api_key, user};
+use crate::security::api_key as api_key_security;
+use crate::services::{api_key_service, refresh_token_service, user_service};
 use actix_web::{dev::ServiceRequest, web::Data, Error, HttpMessage};
 use actix_web_grants::permissions::AttachPermissions;
 use actix_web_httpauth::extractors::{
@@ -9,6 +11,7 @@ use chrono::{Duration, Utc};
 use jsonwebtoken::{
     decode, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
 };
+use sea_orm::DbConn;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,18 +20,39 @@ pub struct Claims {
     pub role: String,
     pub iat: i64,
     pub exp: i64,
+    /// Session which issued the token, revoked on logout
+    pub sid: Option<String>,
+    /// Token version of the user, bumped to invalidate all of their sessions
+    pub ver: i32,
+}
+
+impl Claims {
+    /// Claims of a request authenticated by an API key, which doesn't expire until revoked
+    fn from_api_key(api_key: &api_key::Model, user: &user::Model) -> Self {
+        Claims {
+            sub: user.id.to_string(),
+            role: user.role.to_role_str(),
+            iat: api_key.created_at.timestamp(),
+            exp: i64::MAX,
+            sid: None,
+            ver: user.token_version,
+        }
+    }
 }
 
 pub fn generate_jwt(
     user: &user::Model,
+    session_id: &str,
     encoding_key: &EncodingKey,
-    validity_duration_in_days: i64,
+    validity_duration_in_minutes: i64,
 ) -> String {
     let claims = Claims {
         sub: user.id.to_string(),
         role: user.role.to_role_str(),
         iat: Utc::now().timestamp(),
-        exp: (Utc::now() + Duration::days(validity_duration_in_days)).timestamp(),
+        exp: (Utc::now() + Duration::minutes(validity_duration_in_minutes)).timestamp(),
+        sid: Some(session_id.to_owned()),
+        ver: user.token_version,
     };
 
     let token = encode(&Header::new(Algorithm::HS512), &claims, encoding_key).unwrap();
@@ -57,18 +81,37 @@ pub async fn validator(
     }
 
     if credentials.is_some() {
-        let jwt_decoding_key = req.app_data::<Data<DecodingKey>>().unwrap();
-
         let credentials = credentials.unwrap();
         let token = credentials.token();
-        let verify_res = verify_jwt(token, &jwt_decoding_key);
 
-        if verify_res.is_some() {
-            let claims = verify_res.unwrap().claims;
+        if api_key_security::is_api_key(token) {
+            if let Some((api_key, user)) = verify_api_key(&req, token).await {
+                // API keys only get their scopes, never the role of their owner
+                req.attach(api_key_security::split_scopes(&api_key.scopes));
+                req.extensions_mut()
+                    .insert(Claims::from_api_key(&api_key, &user));
+                return Ok(req);
+            }
+        } else {
+            let jwt_decoding_key = req.app_data::<Data<DecodingKey>>().unwrap();
+            let verify_res = verify_jwt(token, &jwt_decoding_key);
+
+            if verify_res.is_some()
+                && verify_session(&req, &verify_res.as_ref().unwrap().claims).await
+            {
+                let claims = verify_res.unwrap().claims;
 
-            req.attach(vec![claims.role.clone()]);
-            req.extensions_mut().insert(claims);
-            return Ok(req);
+                // logged in users may do everything an API key may be scoped to
+                let mut permissions = api_key_security::SCOPES
+                    .iter()
+                    .map(|scope| scope.to_string())
+                    .collect::<Vec<_>>();
+                permissions.push(claims.role.clone());
+
+                req.attach(permissions);
+                req.extensions_mut().insert(claims);
+                return Ok(req);
+            }
         }
     }
 
@@ -80,3 +123,43 @@ pub async fn validator(
 
     Err((AuthenticationError::from(config).into(), req))
 }
+
+async fn verify_api_key(req: &ServiceRequest, key: &str) -> Option<(api_key::Model, user::Model)> {
+    let db = req.app_data::<Data<DbConn>>().unwrap();
+
+    let api_key = api_key_service::authenticate(db, key)
+        .await
+        .map_err(|err| log::error!("Failed to authenticate API key: {err}"))
+        .ok()??;
+    let user = user_service::find_by_id(db, api_key.user_id)
+        .await
+        .map_err(|err| log::error!("Failed to find owner of API key: {err}"))
+        .ok()??;
+
+    if user.disabled {
+        return None;
+    }
+
+    Some((api_key, user))
+}
+
+/// Check the token wasn't revoked by a logout, a password change or disabling its user
+async fn verify_session(req: &ServiceRequest, claims: &Claims) -> bool {
+    let db = req.app_data::<Data<DbConn>>().unwrap();
+
+    let user = match user_service::find_by_id(db, claims.sub.parse().unwrap()).await {
+        Ok(Some(user)) => user,
+        _ => return false,
+    };
+
+    if user.disabled || user.token_version != claims.ver {
+        return false;
+    }
+
+    match &claims.sid {
+        Some(session_id) => refresh_token_service::is_session_active(db, session_id)
+            .await
+            .unwrap_or(false),
+        None => false,
+    }
+}
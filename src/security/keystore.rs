@@ -0,0 +1,246 @@
+//! Ethereum keystore v3 (Web3 Secret Storage): a private key encrypted with AES-128-CTR under a
+//! key derived from a password by scrypt or pbkdf2, authenticated by a keccak MAC
+
+use crate::errors::KeystoreError;
+use actix_web::web;
+use aes::Aes128;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use ethers::utils::keccak256;
+use hmac::Hmac;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+const VERSION: u8 = 3;
+const CIPHER: &str = "aes-128-ctr";
+const PBKDF2_PRF: &str = "hmac-sha256";
+
+/// Costs of the keystores written by the gateway, as the standard ones of geth. Unlocking a key
+/// then takes a fraction of a second and 256 MiB, so it is done off the async runtime.
+const SCRYPT_LOG_N: u8 = 18;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+const DERIVED_KEY_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    version: u8,
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    #[serde(alias = "Crypto")]
+    crypto: KeystoreCrypto,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreCrypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: KdfParams,
+    mac: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum KdfParams {
+    Scrypt {
+        dklen: usize,
+        n: u32,
+        p: u32,
+        r: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        c: u32,
+        dklen: usize,
+        prf: String,
+        salt: String,
+    },
+}
+
+/// Encrypt the private key of the address (`0x` prefixed) with the password, into the JSON of a
+/// scrypt keystore
+pub fn encrypt(private_key: &[u8], address: &str, password: &str) -> Result<String, KeystoreError> {
+    let salt = random_bytes::<32>();
+    let iv = random_bytes::<16>();
+
+    let kdfparams = KdfParams::Scrypt {
+        dklen: DERIVED_KEY_LENGTH,
+        n: 1 << SCRYPT_LOG_N,
+        p: SCRYPT_P,
+        r: SCRYPT_R,
+        salt: hex::encode(salt),
+    };
+    let derived_key = derive_key(password, &kdfparams)?;
+
+    let mut ciphertext = private_key.to_vec();
+    Aes128Ctr::new(derived_key[..16].into(), iv[..].into()).apply_keystream(&mut ciphertext);
+
+    let keystore = Keystore {
+        version: VERSION,
+        id: random_uuid(),
+        address: Some(address.trim_start_matches("0x").to_lowercase()),
+        crypto: KeystoreCrypto {
+            cipher: CIPHER.to_owned(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            mac: hex::encode(mac(&derived_key, &ciphertext)),
+            ciphertext: hex::encode(ciphertext),
+            kdf: "scrypt".to_owned(),
+            kdfparams,
+        },
+    };
+
+    serde_json::to_string(&keystore).map_err(|err| KeystoreError::Malformed(err.to_string()))
+}
+
+/// Decrypt the private key of a keystore with its password
+pub fn decrypt(keystore: &str, password: &str) -> Result<Vec<u8>, KeystoreError> {
+    let keystore: Keystore =
+        serde_json::from_str(keystore).map_err(|err| KeystoreError::Malformed(err.to_string()))?;
+
+    if keystore.version != VERSION {
+        return Err(KeystoreError::Unsupported(format!(
+            "version {}",
+            keystore.version
+        )));
+    }
+    let crypto = keystore.crypto;
+    if crypto.cipher != CIPHER {
+        return Err(KeystoreError::Unsupported(format!(
+            "cipher {}",
+            crypto.cipher
+        )));
+    }
+
+    let derived_key = derive_key(password, &crypto.kdfparams)?;
+    let mut ciphertext = decode_hex(&crypto.ciphertext)?;
+    // compared in constant time, not to tell how much of a forged MAC is right
+    if !bool::from(mac(&derived_key, &ciphertext)[..].ct_eq(&decode_hex(&crypto.mac)?)) {
+        return Err(KeystoreError::WrongPassword);
+    }
+
+    let iv = decode_hex(&crypto.cipherparams.iv)?;
+    Aes128Ctr::new_from_slices(&derived_key[..16], &iv)
+        .map_err(|_| KeystoreError::Malformed("iv should be 16 bytes long".to_owned()))?
+        .apply_keystream(&mut ciphertext);
+
+    Ok(ciphertext)
+}
+
+/// [`encrypt`] on the blocking thread pool, as the key derivation is meant to be slow
+pub async fn encrypt_blocking(
+    private_key: Vec<u8>,
+    address: String,
+    password: String,
+) -> Result<String, KeystoreError> {
+    web::block(move || encrypt(&private_key, &address, &password))
+        .await
+        .map_err(|err| KeystoreError::Interrupted(err.to_string()))?
+}
+
+/// [`decrypt`] on the blocking thread pool, as the key derivation is meant to be slow
+pub async fn decrypt_blocking(
+    keystore: String,
+    password: String,
+) -> Result<Vec<u8>, KeystoreError> {
+    web::block(move || decrypt(&keystore, &password))
+        .await
+        .map_err(|err| KeystoreError::Interrupted(err.to_string()))?
+}
+
+fn derive_key(password: &str, kdfparams: &KdfParams) -> Result<Vec<u8>, KeystoreError> {
+    match kdfparams {
+        KdfParams::Scrypt {
+            dklen,
+            n,
+            p,
+            r,
+            salt,
+        } => {
+            if *dklen < DERIVED_KEY_LENGTH || !n.is_power_of_two() {
+                return Err(KeystoreError::Malformed("invalid scrypt params".to_owned()));
+            }
+
+            let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p)
+                .map_err(|_| KeystoreError::Unsupported("scrypt params".to_owned()))?;
+            let mut derived_key = vec![0; *dklen];
+            scrypt::scrypt(
+                password.as_bytes(),
+                &decode_hex(salt)?,
+                &params,
+                &mut derived_key,
+            )
+            .map_err(|_| KeystoreError::Malformed("invalid scrypt params".to_owned()))?;
+
+            Ok(derived_key)
+        }
+        KdfParams::Pbkdf2 {
+            c,
+            dklen,
+            prf,
+            salt,
+        } => {
+            if prf != PBKDF2_PRF {
+                return Err(KeystoreError::Unsupported(format!("pbkdf2 prf {prf}")));
+            }
+            if *dklen < DERIVED_KEY_LENGTH {
+                return Err(KeystoreError::Malformed("invalid pbkdf2 params".to_owned()));
+            }
+
+            let mut derived_key = vec![0; *dklen];
+            pbkdf2::pbkdf2::<Hmac<Sha256>>(
+                password.as_bytes(),
+                &decode_hex(salt)?,
+                *c,
+                &mut derived_key,
+            );
+
+            Ok(derived_key)
+        }
+    }
+}
+
+fn mac(derived_key: &[u8], ciphertext: &[u8]) -> [u8; 32] {
+    keccak256([&derived_key[16..32], ciphertext].concat())
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, KeystoreError> {
+    hex::decode(value).map_err(|err| KeystoreError::Malformed(err.to_string()))
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    OsRng.fill_bytes(&mut bytes);
+
+    bytes
+}
+
+/// Random (version 4) UUID identifying a keystore
+fn random_uuid() -> String {
+    let mut bytes = random_bytes::<16>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -1,2 +1,6 @@
+pub mod api_key;
 pub mod hash;
 pub mod jwt;
+pub mod keystore;
+pub mod refresh_token;
+pub mod webhook;
@@ -0,0 +1,28 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Header carrying the refresh token of a newly started or refreshed session
+pub const REFRESH_TOKEN_HEADER: &str = "X-Refresh-Token";
+
+const TOKEN_LENGTH_IN_BYTES: usize = 32;
+const SESSION_ID_LENGTH_IN_BYTES: usize = 16;
+
+fn random_hex(length_in_bytes: usize) -> String {
+    let mut bytes = vec![0u8; length_in_bytes];
+    OsRng.fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+pub fn generate_token() -> String {
+    random_hex(TOKEN_LENGTH_IN_BYTES)
+}
+
+pub fn generate_session_id() -> String {
+    random_hex(SESSION_ID_LENGTH_IN_BYTES)
+}
+
+/// Refresh tokens are random enough for a fast hash, which also lets them be looked up by it
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
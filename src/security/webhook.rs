@@ -0,0 +1,30 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Header carrying the hex encoded HMAC-SHA256 of the timestamp and the webhook request body,
+/// as signed by [`sign_payload`]
+pub const SIGNATURE_HEADER: &str = "X-Gateway-Signature";
+
+/// Header carrying when the webhook request is signed, in seconds since the Unix epoch.
+/// Receivers should reject requests signed more than 5 minutes ago, so that a captured request
+/// can't be replayed.
+pub const TIMESTAMP_HEADER: &str = "X-Gateway-Timestamp";
+
+const SECRET_LENGTH_IN_BYTES: usize = 32;
+
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LENGTH_IN_BYTES];
+    OsRng.fill_bytes(&mut secret);
+
+    hex::encode(secret)
+}
+
+/// Sign `{timestamp}.{payload}`, binding the body to when it is sent
+pub fn sign_payload(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(format!("{timestamp}.{payload}").as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
@@ -0,0 +1,78 @@
+use crate::impl_crud;
+use crate::security::{api_key as api_key_security, hash};
+use crate::{
+    entities::{api_key, prelude::*},
+    errors::InternalError,
+};
+use actix_web::web;
+use chrono::{Duration, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder};
+use sea_orm::{DbConn, DeleteResult};
+
+/// `last_used_at` is only written when it is older than this, not on every request
+const LAST_USED_AT_PRECISION_IN_SECONDS: i64 = 60;
+
+impl_crud!(ApiKey, api_key, InternalError, i32);
+
+pub async fn find_all_by_user_id(
+    db: &DbConn,
+    user_id: i32,
+) -> Result<Vec<api_key::Model>, InternalError> {
+    Ok(ApiKey::find()
+        .filter(api_key::Column::UserId.eq(user_id))
+        .order_by_asc(api_key::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Find the not revoked API key matching the given key
+pub async fn authenticate(db: &DbConn, key: &str) -> Result<Option<api_key::Model>, InternalError> {
+    let prefix = match api_key_security::parse_prefix(key) {
+        Some(prefix) => prefix,
+        None => return Ok(None),
+    };
+
+    let api_key = ApiKey::find()
+        .filter(api_key::Column::Prefix.eq(prefix))
+        .filter(api_key::Column::RevokedAt.is_null())
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+
+    let Some(api_key) = api_key else {
+        return Ok(None);
+    };
+
+    // argon2 takes tens of milliseconds, which would stall every other request of the worker
+    let (key_hash, key) = (api_key.key_hash.clone(), key.to_owned());
+    let verified = web::block(move || hash::verify_password(&key_hash, &key))
+        .await
+        // the blocking pool is gone only when the server is shutting down
+        .unwrap_or(false);
+    if !verified {
+        return Ok(None);
+    }
+
+    let now = Utc::now().naive_utc();
+    let stale_from = now - Duration::seconds(LAST_USED_AT_PRECISION_IN_SECONDS);
+    if api_key
+        .last_used_at
+        .map_or(true, |last_used_at| last_used_at < stale_from)
+    {
+        ApiKey::update_many()
+            .col_expr(api_key::Column::LastUsedAt, Expr::value(now))
+            .filter(api_key::Column::Id.eq(api_key.id))
+            .filter(
+                Condition::any()
+                    .add(api_key::Column::LastUsedAt.is_null())
+                    .add(api_key::Column::LastUsedAt.lt(stale_from)),
+            )
+            .exec(db)
+            .await
+            .map_err(Into::<InternalError>::into)?;
+    }
+
+    Ok(Some(api_key))
+}
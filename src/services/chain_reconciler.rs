@@ -0,0 +1,325 @@
+use super::chain_watcher::{self, ChainTransfer, ChainWatcher};
+use super::{
+    crypto_currency_service, network_service, payment_quote_service, payment_service,
+    wallet_service, wallet_transaction_service,
+};
+use crate::entities::network;
+use crate::entities::payment::{self, PaymentStatus};
+use crate::entities::wallet;
+use crate::entities::wallet_transaction::{self, WalletTransactionStatus};
+use crate::errors::{InternalError, NotFoundError};
+use crate::services::payment_monitor::PaymentMonitor;
+use actix_web::web::Data;
+use anyhow::Context;
+use chrono::{Duration, NaiveDateTime, Utc};
+use sea_orm::prelude::Decimal;
+use sea_orm::{DbConn, Set};
+
+/// How often payments are reconciled with the chain
+const RECONCILIATION_INTERVAL_IN_SECONDS: i64 = 60;
+
+/// How long expired payments are still reconciled, for transfers mined before they expired
+const RECONCILIATION_LOOKBACK_IN_HOURS: i64 = 24;
+
+/// Most blocks scanned for a payment in a single run, the rest is left to the next runs
+const MAX_BLOCKS_PER_RUN: u64 = 500;
+
+/// Reconcile payments with the chain right away, then periodically
+pub fn spawn_chain_reconciler(payment_monitor: Data<PaymentMonitor>, db: Data<DbConn>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = reconcile_payments(&payment_monitor, &db).await {
+                log::error!("Failed to reconcile payments with the chain: {err}");
+            }
+
+            tokio::time::sleep(
+                Duration::seconds(RECONCILIATION_INTERVAL_IN_SECONDS)
+                    .to_std()
+                    .unwrap(),
+            )
+            .await;
+        }
+    });
+}
+
+/// Catch up on the transfers which the watchers missed, e.g. while the gateway was down or its
+/// websocket connection was lost.
+///
+/// The wallet of every waiting or recently expired payment is scanned from where it was
+/// reserved, through the chain watcher of its network. Missing transfers are recorded as wallet
+/// transactions of the payment; waiting payments are then watched again with them, and payments
+/// which are past their expiration date are settled by what was paid for them in time.
+pub async fn reconcile_payments(
+    payment_monitor: &PaymentMonitor,
+    db: &Data<DbConn>,
+) -> Result<(), InternalError> {
+    let expired_since = Utc::now().naive_utc() - Duration::hours(RECONCILIATION_LOOKBACK_IN_HOURS);
+
+    for payment in payment_service::find_all_reconcilable(db, expired_since).await? {
+        let payment_id = payment.id;
+
+        if let Err(err) = reconcile_payment(payment_monitor, payment, db).await {
+            log::error!("Failed to reconcile payment with id {payment_id}: {err:#}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn reconcile_payment(
+    payment_monitor: &PaymentMonitor,
+    payment: payment::Model,
+    db: &Data<DbConn>,
+) -> Result<(), anyhow::Error> {
+    // unwrap: reconcilable payments have both
+    let crypto_currency =
+        crypto_currency_service::find_by_id(db, payment.crypto_currency_id.unwrap())
+            .await?
+            .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
+    let wallet = wallet_service::find_by_id(db, payment.dest_wallet_id.unwrap())
+        .await?
+        .context("Wallet of the payment not found")?;
+    let network = network_service::find_by_id(db, crypto_currency.network_id)
+        .await?
+        .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+    let chain_watcher = chain_watcher::for_network(&network)?;
+    let expired =
+        payment.status == PaymentStatus::Expired || payment.expired_at < Utc::now().naive_utc();
+
+    if let (true, Some(reconciled_block_number)) = (expired, payment.reconciled_block_number) {
+        let reconciled_at = chain_watcher
+            .block_time(reconciled_block_number as u64)
+            .await?;
+
+        // everything the payment was paid in time is reconciled already
+        if reconciled_at.map_or(false, |reconciled_at| reconciled_at > payment.expired_at) {
+            return Ok(());
+        }
+    }
+
+    let head = chain_watcher.head().await?;
+    let from = match payment.reconciled_block_number {
+        Some(reconciled_block_number) => reconciled_block_number as u64 + 1,
+        None => {
+            chain_watcher
+                .block_number_at(reserved_at(&payment, &wallet, db).await?)
+                .await?
+        }
+    };
+    let to = head.min(from + MAX_BLOCKS_PER_RUN - 1);
+
+    let transfers = if from <= to {
+        chain_watcher
+            .find_transfers(&crypto_currency, &wallet.address, from, to)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let mut found_missing_transfer = false;
+    for transfer in transfers {
+        // sent after the payment expired, possibly to the next payment of the wallet
+        if transfer.mined_at > payment.expired_at {
+            continue;
+        }
+
+        found_missing_transfer |=
+            record_transfer(&transfer, &payment, &network, &wallet, head, db).await?;
+    }
+
+    // blocks short of the required confirmations are scanned again by the next runs
+    let reconciled_block_number = to
+        .min(head.saturating_sub(network.required_confirmations as u64))
+        .max(from.saturating_sub(1));
+    payment_service::record_reconciled_block_number(db, payment.id, reconciled_block_number as i64)
+        .await?;
+
+    if !expired {
+        if found_missing_transfer {
+            log::info!(
+                "Missed transfers found for payment with id {}, watching it again",
+                payment.id
+            );
+
+            payment_monitor.start(payment, network, crypto_currency, wallet, db.clone());
+        }
+
+        return Ok(());
+    }
+
+    // what was sent in the currencies the payment used before is not paid in this one
+    let mut wallet_transactions =
+        wallet_transaction_service::find_all_by_payment_id_wallet_id_and_crypto_currency_id(
+            db,
+            payment.id,
+            wallet.id,
+            crypto_currency.id,
+        )
+        .await?;
+
+    // the watcher of the payment may have stopped before they were confirmed
+    for wallet_transaction in wallet_transactions.iter_mut() {
+        if wallet_transaction.status == WalletTransactionStatus::Pending {
+            refresh_progress(chain_watcher.as_ref(), &network, wallet_transaction, db).await?;
+        }
+    }
+
+    // wait for mined transfers to be confirmed, those never mined are not counted
+    let awaiting_confirmations = wallet_transactions.iter().any(|wallet_transaction| {
+        wallet_transaction.status == WalletTransactionStatus::Pending
+            && wallet_transaction.block_number.is_some()
+    });
+    if awaiting_confirmations {
+        return Ok(());
+    }
+
+    let paid_crypto: Decimal = wallet_transactions
+        .iter()
+        .filter(|wallet_transaction| {
+            wallet_transaction.status == WalletTransactionStatus::Confirmed
+        })
+        .filter_map(|wallet_transaction| wallet_transaction.value)
+        .sum();
+
+    if paid_crypto > payment.paid_crypto_amount.unwrap_or_default() {
+        log::info!(
+            "Payment with id {} was paid {paid_crypto} without being noticed",
+            payment.id
+        );
+
+        payment_monitor
+            .settle(payment, &crypto_currency, paid_crypto, db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Record the transfer as a wallet transaction of the payment along with its progress, telling
+/// whether it was missing
+async fn record_transfer(
+    transfer: &ChainTransfer,
+    payment: &payment::Model,
+    network: &network::Model,
+    wallet: &wallet::Model,
+    head: u64,
+    db: &DbConn,
+) -> Result<bool, InternalError> {
+    let hash = transfer.hash.clone();
+
+    let recorded = wallet_transaction_service::find_by_network_id_hash_and_wallet_id(
+        db, network.id, &hash, wallet.id,
+    )
+    .await?;
+    if let Some(ref recorded) = recorded {
+        // recorded for the payment the wallet was reserved for at the time
+        if recorded
+            .payment_id
+            .map_or(false, |payment_id| payment_id != payment.id)
+        {
+            return Ok(false);
+        }
+    }
+
+    let confirmations = head.saturating_sub(transfer.block_number) + 1;
+    let status = if !transfer.succeeded {
+        WalletTransactionStatus::Failed
+    } else if confirmations >= network.required_confirmations as u64 {
+        WalletTransactionStatus::Confirmed
+    } else {
+        WalletTransactionStatus::Pending
+    };
+
+    let wallet_transaction = wallet_transaction::ActiveModel {
+        hash: Set(hash.clone()),
+        wallet_id: Set(wallet.id),
+        network_id: Set(network.id),
+        payment_id: Set(Some(payment.id)),
+        crypto_currency_id: Set(payment.crypto_currency_id),
+        from_address: Set(transfer.from.clone()),
+        value: Set(Some(transfer.value)),
+        block_number: Set(Some(transfer.block_number as i64)),
+        block_hash: Set(Some(transfer.block_hash.clone())),
+        ..Default::default()
+    };
+    wallet_transaction_service::record(db, wallet_transaction).await?;
+
+    wallet_transaction_service::update_progress(
+        db,
+        network.id,
+        &hash,
+        Some(transfer.block_number as i64),
+        Some(transfer.block_hash.clone()),
+        confirmations as i32,
+        status,
+    )
+    .await?;
+
+    Ok(recorded.is_none())
+}
+
+/// Update the wallet transaction with where its transaction stands on the chain
+async fn refresh_progress(
+    chain_watcher: &dyn ChainWatcher,
+    network: &network::Model,
+    wallet_transaction: &mut wallet_transaction::Model,
+    db: &DbConn,
+) -> Result<(), anyhow::Error> {
+    let Some(transfer_status) = chain_watcher
+        .transfer_status(&wallet_transaction.hash)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let status = if !transfer_status.succeeded {
+        WalletTransactionStatus::Failed
+    } else if transfer_status.confirmations >= network.required_confirmations as u64 {
+        WalletTransactionStatus::Confirmed
+    } else {
+        WalletTransactionStatus::Pending
+    };
+    let block_number = transfer_status
+        .block_number
+        .map(|block_number| block_number as i64);
+
+    wallet_transaction_service::update_progress(
+        db,
+        network.id,
+        &wallet_transaction.hash,
+        block_number,
+        transfer_status.block_hash.clone(),
+        transfer_status.confirmations as i32,
+        status.clone(),
+    )
+    .await?;
+
+    wallet_transaction.block_number = block_number;
+    wallet_transaction.block_hash = transfer_status.block_hash;
+    wallet_transaction.confirmations = transfer_status.confirmations as i32;
+    wallet_transaction.status = status;
+
+    Ok(())
+}
+
+/// When the wallet was reserved for the payment, where its scan starts
+async fn reserved_at(
+    payment: &payment::Model,
+    wallet: &wallet::Model,
+    db: &DbConn,
+) -> Result<NaiveDateTime, InternalError> {
+    if let (Some(payment_id), Some(reserved_at)) = (wallet.payment_id, wallet.reserved_at) {
+        if payment_id == payment.id {
+            return Ok(reserved_at);
+        }
+    }
+
+    // the wallet is freed already, it was reserved right after the first quote of the payment
+    let quotes = payment_quote_service::find_all_by_payment_id(db, payment.id).await?;
+
+    Ok(quotes
+        .first()
+        .map(|quote| quote.fetched_at)
+        .unwrap_or(payment.created_at))
+}
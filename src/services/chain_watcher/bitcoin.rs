@@ -0,0 +1,125 @@
+use super::{ChainTransfer, ChainWatcher, TransferStatus, WatchOutcome, WatchTarget};
+use crate::entities::{crypto_currency, network, wallet};
+use crate::errors::{BitcoinRpcError, ChainError};
+use crate::services::bitcoin_service::{self, BitcoinRpc};
+use crate::services::payment_monitor::PaymentEventPublisher;
+use actix_web::web::Data;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sea_orm::prelude::Decimal;
+use sea_orm::DbConn;
+
+/// bitcoind error code of transactions unknown to its wallet
+const INVALID_ADDRESS_OR_KEY: i64 = -5;
+
+/// Bitcoin, through the JSON-RPC interface of a bitcoind node whose watch-only wallet tracks the
+/// payment addresses
+pub struct BitcoinWatcher {
+    network: network::Model,
+    rpc: BitcoinRpc,
+}
+
+impl BitcoinWatcher {
+    pub fn new(network: network::Model) -> Self {
+        let rpc = BitcoinRpc::new(&network.http_address_url);
+
+        BitcoinWatcher { network, rpc }
+    }
+}
+
+#[async_trait]
+impl ChainWatcher for BitcoinWatcher {
+    /// Addresses are checked by the node, so only those of its chain are valid
+    async fn validate_address(&self, address: &str) -> Result<bool, ChainError> {
+        Ok(self.rpc.validate_address(address).await?)
+    }
+
+    async fn watch(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        wallet: &wallet::Model,
+        target: &WatchTarget,
+        events: &PaymentEventPublisher,
+        db: Data<DbConn>,
+    ) -> Result<WatchOutcome, ChainError> {
+        bitcoin_service::subscribe_transactions(
+            &self.network,
+            crypto_currency,
+            wallet,
+            target,
+            events,
+            db,
+        )
+        .await
+    }
+
+    async fn transfer_status(&self, hash: &str) -> Result<Option<TransferStatus>, ChainError> {
+        let transaction = match self.rpc.transaction(hash).await {
+            Ok(transaction) => transaction,
+            Err(BitcoinRpcError::Rpc {
+                code: INVALID_ADDRESS_OR_KEY,
+                ..
+            }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Some(TransferStatus {
+            block_number: transaction.blockheight,
+            block_hash: transaction.blockhash,
+            confirmations: transaction.confirmations.max(0) as u64,
+            succeeded: transaction.confirmations >= 0,
+        }))
+    }
+
+    async fn head(&self) -> Result<u64, ChainError> {
+        Ok(self.rpc.block_count().await?)
+    }
+
+    async fn block_time(&self, block_number: u64) -> Result<Option<NaiveDateTime>, ChainError> {
+        Ok(self.rpc.block_time(block_number).await?)
+    }
+
+    async fn find_transfers(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<ChainTransfer>, ChainError> {
+        let decimals = crypto_currency.decimals as u32;
+        let mut transfers = Vec::new();
+
+        for height in from..=to {
+            let block_hash = self.rpc.block_hash(height).await?;
+            let block = self.rpc.block(&block_hash).await?;
+            let Some(mined_at) = NaiveDateTime::from_timestamp_opt(block.time, 0) else {
+                continue;
+            };
+
+            for transaction in block.tx {
+                // every output of the transaction paying the address
+                let value = transaction
+                    .vout
+                    .iter()
+                    .filter(|output| output.script_pub_key.address.as_deref() == Some(address))
+                    .map(|output| bitcoin_service::convert_amount(output.value, decimals))
+                    .sum::<Result<Decimal, ChainError>>()?;
+                if value.is_zero() {
+                    continue;
+                }
+
+                transfers.push(ChainTransfer {
+                    hash: transaction.txid,
+                    from: None,
+                    value,
+                    block_number: block.height,
+                    block_hash: block.hash.clone(),
+                    mined_at,
+                    succeeded: true,
+                });
+            }
+        }
+
+        Ok(transfers)
+    }
+}
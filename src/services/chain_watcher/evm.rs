@@ -0,0 +1,74 @@
+use super::{ChainTransfer, ChainWatcher, TransferStatus, WatchOutcome, WatchTarget};
+use crate::entities::{crypto_currency, network, wallet};
+use crate::errors::ChainError;
+use crate::services::payment_monitor::PaymentEventPublisher;
+use crate::services::web3_service::{self, ChainScanner};
+use actix_web::web::Data;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sea_orm::DbConn;
+
+/// Ethereum and compatible chains: transfers are followed over the websocket endpoint of the
+/// network and past blocks are read over its HTTP endpoint
+pub struct EvmWatcher {
+    network: network::Model,
+    scanner: ChainScanner,
+}
+
+impl EvmWatcher {
+    pub fn new(network: network::Model) -> Result<Self, ChainError> {
+        let scanner = ChainScanner::new(&network)?;
+
+        Ok(EvmWatcher { network, scanner })
+    }
+}
+
+#[async_trait]
+impl ChainWatcher for EvmWatcher {
+    async fn validate_address(&self, address: &str) -> Result<bool, ChainError> {
+        Ok(web3_service::parse_address(address).is_ok())
+    }
+
+    async fn watch(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        wallet: &wallet::Model,
+        target: &WatchTarget,
+        events: &PaymentEventPublisher,
+        db: Data<DbConn>,
+    ) -> Result<WatchOutcome, ChainError> {
+        web3_service::subscribe_transactions(
+            &self.network,
+            crypto_currency,
+            wallet,
+            target,
+            events,
+            db,
+        )
+        .await
+    }
+
+    async fn transfer_status(&self, hash: &str) -> Result<Option<TransferStatus>, ChainError> {
+        self.scanner.transfer_status(hash).await
+    }
+
+    async fn head(&self) -> Result<u64, ChainError> {
+        self.scanner.head().await
+    }
+
+    async fn block_time(&self, block_number: u64) -> Result<Option<NaiveDateTime>, ChainError> {
+        self.scanner.block_time(block_number).await
+    }
+
+    async fn find_transfers(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<ChainTransfer>, ChainError> {
+        self.scanner
+            .find_transfers(crypto_currency, address, from, to)
+            .await
+    }
+}
@@ -0,0 +1,208 @@
+mod bitcoin;
+mod evm;
+
+pub use bitcoin::BitcoinWatcher;
+pub use evm::EvmWatcher;
+
+use crate::entities::network::{self, NetworkKind};
+use crate::entities::{crypto_currency, wallet};
+use crate::errors::ChainError;
+use crate::services::payment_monitor::PaymentEventPublisher;
+use actix_web::web::Data;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sea_orm::prelude::Decimal;
+use sea_orm::DbConn;
+
+/// How watching the wallet of a payment ended
+pub enum WatchOutcome {
+    /// Enough was confirmed or the payment expired, with the confirmed amount
+    Ended(Decimal),
+    /// The quote of the payment lapsed before any transfer to the wallet was seen
+    QuoteLapsed,
+}
+
+/// What a payment waits for on its wallet
+pub struct WatchTarget {
+    pub payment_id: i32,
+    /// Confirmed amount which ends the watch
+    pub crypto_amount: Decimal,
+    pub expiration_date: NaiveDateTime,
+    /// Watching stops early once the quote lapses if nothing was sent to the wallet yet
+    pub quote_valid_until: Option<NaiveDateTime>,
+}
+
+/// A transfer to a wallet found by scanning past blocks
+pub struct ChainTransfer {
+    pub hash: String,
+    /// Not known for transfers which may have several senders, like bitcoin transactions
+    pub from: Option<String>,
+    pub value: Decimal,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub mined_at: NaiveDateTime,
+    /// Reverted transactions never count as paid
+    pub succeeded: bool,
+}
+
+/// Where a transaction stands on the chain
+pub struct TransferStatus {
+    pub block_number: Option<u64>,
+    pub block_hash: Option<String>,
+    pub confirmations: u64,
+    /// Reverted or double spent transactions never count as paid
+    pub succeeded: bool,
+}
+
+/// Everything the gateway needs from a chain to accept payments on it
+#[async_trait]
+pub trait ChainWatcher: Send + Sync {
+    async fn validate_address(&self, address: &str) -> Result<bool, ChainError>;
+
+    /// Convert an amount of the currency's smallest unit into the currency, e.g. wei into ether
+    fn from_base_units(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        amount: u128,
+    ) -> Result<Decimal, ChainError> {
+        from_base_units(amount, crypto_currency.decimals as u32)
+    }
+
+    /// Convert an amount of currency into its smallest unit, e.g. bitcoin into satoshis
+    fn to_base_units(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        amount: Decimal,
+    ) -> Result<u128, ChainError> {
+        to_base_units(amount, crypto_currency.decimals as u32)
+    }
+
+    /// Follow the transfers to the wallet until transfers worth the target amount reach the
+    /// network's required number of confirmations, or until the payment expires. Every transfer
+    /// is published to the payment's followers and recorded as a wallet transaction of the
+    /// payment, along with its progress. Fails when the node can't be followed anymore, what is
+    /// recorded so far is then picked up by the next watch.
+    async fn watch(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        wallet: &wallet::Model,
+        target: &WatchTarget,
+        events: &PaymentEventPublisher,
+        db: Data<DbConn>,
+    ) -> Result<WatchOutcome, ChainError>;
+
+    /// Status of a transaction, none if it is not mined nor known to the node
+    async fn transfer_status(&self, hash: &str) -> Result<Option<TransferStatus>, ChainError>;
+
+    /// Number of the last block of the chain
+    async fn head(&self) -> Result<u64, ChainError>;
+
+    async fn block_time(&self, block_number: u64) -> Result<Option<NaiveDateTime>, ChainError>;
+
+    /// Number of the first block mined at or after `timestamp`, or the head if there is none yet
+    async fn block_number_at(&self, timestamp: NaiveDateTime) -> Result<u64, ChainError> {
+        let mut low = 0;
+        let mut high = self.head().await?;
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+
+            match self.block_time(middle).await? {
+                Some(mined_at) if mined_at < timestamp => low = middle + 1,
+                _ => high = middle,
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// Transfers of the crypto currency to the address mined in blocks `from..=to`
+    async fn find_transfers(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<ChainTransfer>, ChainError>;
+}
+
+/// Watcher of the chain of the network, picked by its kind
+pub fn for_network(network: &network::Model) -> Result<Box<dyn ChainWatcher>, ChainError> {
+    let chain_watcher: Box<dyn ChainWatcher> = match network.kind {
+        NetworkKind::Evm => Box::new(EvmWatcher::new(network.clone())?),
+        NetworkKind::Bitcoin => Box::new(BitcoinWatcher::new(network.clone())),
+    };
+
+    Ok(chain_watcher)
+}
+
+/// Largest mantissa of a `Decimal`, which has 96 bits for it
+const MAX_DECIMAL_MANTISSA: u128 = (1 << 96) - 1;
+
+/// Largest scale of a `Decimal`
+const MAX_DECIMAL_SCALE: u32 = 28;
+
+/// Convert an amount of the currency's smallest unit into the currency. The last digits are
+/// rounded off when a `Decimal` has no room for them, amounts whose whole part doesn't fit fail.
+pub fn from_base_units(amount: u128, decimals: u32) -> Result<Decimal, ChainError> {
+    let (mut mantissa, mut scale) = (amount, decimals);
+    while mantissa > MAX_DECIMAL_MANTISSA || scale > MAX_DECIMAL_SCALE {
+        if scale == 0 {
+            return Err(ChainError::AmountOutOfRange(amount.to_string()));
+        }
+        mantissa /= 10;
+        scale -= 1;
+    }
+
+    Ok(Decimal::from_i128_with_scale(mantissa as i128, scale))
+}
+
+/// Convert an amount of currency into its smallest unit. Negative amounts, amounts with more
+/// decimals than the currency has and amounts whose base units don't fit fail.
+pub fn to_base_units(amount: Decimal, decimals: u32) -> Result<u128, ChainError> {
+    let unrepresentable = || ChainError::UnrepresentableAmount(amount.to_string());
+
+    let normalized = amount.normalize();
+    let mantissa = u128::try_from(normalized.mantissa()).map_err(|_| unrepresentable())?;
+    let missing_decimals = decimals
+        .checked_sub(normalized.scale())
+        .ok_or_else(unrepresentable)?;
+
+    10u128
+        .checked_pow(missing_decimals)
+        .and_then(|factor| mantissa.checked_mul(factor))
+        .ok_or_else(unrepresentable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_base_units_rounds_off_what_does_not_fit() {
+        assert_eq!(
+            from_base_units(1_500_000_000_000_000_000, 18).unwrap(),
+            Decimal::new(15, 1)
+        );
+
+        // 10^33 wei is more than 96 bits
+        let amount = from_base_units(10u128.pow(33) + 1, 18).unwrap();
+        assert_eq!(amount, Decimal::from(10u64.pow(15)));
+
+        assert!(from_base_units(u128::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn to_base_units_fails_on_what_is_not_a_whole_number_of_base_units() {
+        assert_eq!(
+            to_base_units(Decimal::new(1500, 3), 18).unwrap(),
+            1_500_000_000_000_000_000
+        );
+
+        assert!(to_base_units(Decimal::new(-1, 0), 18).is_err());
+        // one satoshi is 10^-8 bitcoin
+        assert!(to_base_units(Decimal::new(1, 9), 8).is_err());
+        assert!(to_base_units(Decimal::from(10u64.pow(15)), 24).is_err());
+        assert!(to_base_units(Decimal::ONE, 39).is_err());
+    }
+}
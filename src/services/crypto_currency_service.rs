@@ -3,6 +3,17 @@ use crate::{
     entities::{crypto_currency, prelude::*},
     errors::InternalError,
 };
-use sea_orm::{DbConn, DeleteResult};
+use sea_orm::{ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter};
 
 impl_crud!(CryptoCurrency, crypto_currency, InternalError, i32);
+
+pub async fn find_all_by_network_id(
+    db: &DbConn,
+    network_id: i32,
+) -> Result<Vec<crypto_currency::Model>, InternalError> {
+    Ok(CryptoCurrency::find()
+        .filter(crypto_currency::Column::NetworkId.eq(network_id))
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
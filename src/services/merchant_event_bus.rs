@@ -0,0 +1,74 @@
+use crate::models::merchant_event::MerchantEvent;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// How many events a slow subscriber may fall behind before missing some
+const EVENT_BUFFER_SIZE: usize = 256;
+
+/// Events of a merchant followed by one of their clients, stops following them when dropped
+pub struct MerchantSubscription {
+    events: Option<broadcast::Receiver<MerchantEvent>>,
+    bus: MerchantEventBus,
+    user_id: i32,
+}
+
+impl MerchantSubscription {
+    pub async fn recv(&mut self) -> Result<MerchantEvent, RecvError> {
+        // unwrap: only taken when dropped
+        self.events.as_mut().unwrap().recv().await
+    }
+}
+
+impl Drop for MerchantSubscription {
+    fn drop(&mut self) {
+        // unsubscribe first, so the channel is released if this was the last follower
+        self.events.take();
+        self.bus.release(self.user_id);
+    }
+}
+
+/// Delivers what happens on the account of a merchant to the clients following it.
+///
+/// Each merchant has their own channel, so a busy merchant never makes the clients of another
+/// one miss events. Events of merchants no one follows are dropped.
+#[derive(Clone, Default)]
+pub struct MerchantEventBus {
+    merchants: Arc<Mutex<HashMap<i32, broadcast::Sender<MerchantEvent>>>>,
+}
+
+impl MerchantEventBus {
+    pub fn subscribe(&self, user_id: i32) -> MerchantSubscription {
+        let events = self
+            .merchants
+            .lock()
+            .unwrap()
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(EVENT_BUFFER_SIZE).0)
+            .subscribe();
+
+        MerchantSubscription {
+            events: Some(events),
+            bus: self.clone(),
+            user_id,
+        }
+    }
+
+    pub fn publish(&self, user_id: i32, event: MerchantEvent) {
+        if let Some(events) = self.merchants.lock().unwrap().get(&user_id) {
+            // followers may all have just left
+            let _ = events.send(event);
+        }
+    }
+
+    /// Forget the merchant if no one follows them anymore
+    fn release(&self, user_id: i32) {
+        let mut merchants = self.merchants.lock().unwrap();
+
+        if let Some(events) = merchants.get(&user_id) {
+            if events.receiver_count() == 0 {
+                merchants.remove(&user_id);
+            }
+        }
+    }
+}
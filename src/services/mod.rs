@@ -1,10 +1,26 @@
+pub mod api_key_service;
+pub mod bitcoin_service;
+pub mod chain_reconciler;
+pub mod chain_watcher;
 pub mod crypto_currency_service;
 pub mod fiat_currency_service;
-pub mod kucoin_api_service;
+pub mod merchant_event_bus;
 pub mod network_service;
+pub mod payment_monitor;
+pub mod payment_policy_service;
+pub mod payment_quote_service;
 pub mod payment_service;
+pub mod payout_provider;
+pub mod price_oracle;
+pub mod refresh_token_service;
+pub mod refund_service;
+pub mod signer;
+pub mod sweep_service;
+pub mod sweeper;
 pub mod user_service;
 pub mod user_transaction_service;
 pub mod wallet_service;
 pub mod wallet_transaction_service;
 pub mod web3_service;
+pub mod webhook_service;
+pub mod withdrawal_service;
@@ -0,0 +1,552 @@
+use crate::entities::payment::{self, PaymentStatus};
+use crate::entities::{crypto_currency, network, payment_policy, payment_quote, wallet};
+use crate::errors::{InternalError, NotFoundError};
+use crate::models::merchant_event::{
+    MerchantEvent, ReceivedTokenTransfer, ReceivedTransaction, ReceivedUtxo,
+};
+use crate::models::payment_event::{PaymentEvent, SequencedPaymentEvent};
+use crate::services::chain_watcher::{WatchOutcome, WatchTarget};
+use crate::services::merchant_event_bus::MerchantEventBus;
+use crate::services::payment_policy_service::{self, Settlement};
+use crate::services::payment_quote_service::{self, QuotePolicy};
+use crate::services::price_oracle::PriceOracle;
+use crate::services::{
+    chain_watcher, crypto_currency_service, fiat_currency_service, network_service,
+    payment_service, wallet_service, webhook_service,
+};
+use actix_web::web::Data;
+use chrono::Utc;
+use futures_util::FutureExt;
+use sea_orm::prelude::Decimal;
+use sea_orm::DbConn;
+use std::collections::{HashMap, VecDeque};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+/// How many events a slow subscriber may fall behind before missing some, and how many are
+/// kept for subscribers resuming from an event id
+const EVENT_BUFFER_SIZE: usize = 64;
+
+/// Delay before watching a payment again when its watch fails, doubled after every failure
+const INITIAL_WATCH_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest delay between two watches of a payment
+const MAX_WATCH_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+struct MonitoredPayment {
+    events: broadcast::Sender<SequencedPaymentEvent>,
+    /// Latest events, oldest first
+    history: VecDeque<SequencedPaymentEvent>,
+    /// Events after this id are all in the history
+    replayable_after: u64,
+    watcher: Option<(u64, JoinHandle<()>)>,
+}
+
+impl MonitoredPayment {
+    fn new(last_event_id: u64) -> Self {
+        let (events, _) = broadcast::channel(EVENT_BUFFER_SIZE);
+
+        MonitoredPayment {
+            events,
+            history: VecDeque::with_capacity(EVENT_BUFFER_SIZE),
+            replayable_after: last_event_id,
+            watcher: None,
+        }
+    }
+}
+
+/// Events of a payment followed by a client, stops following the payment when dropped
+pub struct PaymentSubscription {
+    /// Events published after the event id the client resumes from, `None` when some of them
+    /// are not kept anymore
+    pub missed_events: Option<Vec<SequencedPaymentEvent>>,
+    events: Option<broadcast::Receiver<SequencedPaymentEvent>>,
+    monitor: PaymentMonitor,
+    payment_id: i32,
+}
+
+impl PaymentSubscription {
+    pub async fn recv(&mut self) -> Result<SequencedPaymentEvent, RecvError> {
+        // unwrap: only taken when dropped
+        self.events.as_mut().unwrap().recv().await
+    }
+}
+
+impl Drop for PaymentSubscription {
+    fn drop(&mut self) {
+        // unsubscribe first, so the payment is released if this was its last follower
+        self.events.take();
+        self.monitor.release(self.payment_id);
+    }
+}
+
+/// Publishes the events of a watched payment, forwarding incoming transactions to its merchant
+pub struct PaymentEventPublisher {
+    monitor: PaymentMonitor,
+    payment_id: i32,
+    user_id: i32,
+    fiat_currency_id: i32,
+    crypto_currency_id: i32,
+}
+
+impl PaymentEventPublisher {
+    pub fn publish(&self, event: PaymentEvent) {
+        let merchant_event = match event {
+            PaymentEvent::TransactionReceived(ref transaction) => {
+                Some(MerchantEvent::TransactionReceived(ReceivedTransaction {
+                    payment_id: self.payment_id,
+                    fiat_currency_id: self.fiat_currency_id,
+                    crypto_currency_id: self.crypto_currency_id,
+                    transaction: transaction.clone(),
+                }))
+            }
+            PaymentEvent::TokenTransferReceived(ref log) => Some(
+                MerchantEvent::TokenTransferReceived(ReceivedTokenTransfer {
+                    payment_id: self.payment_id,
+                    fiat_currency_id: self.fiat_currency_id,
+                    crypto_currency_id: self.crypto_currency_id,
+                    log: log.clone(),
+                }),
+            ),
+            PaymentEvent::UtxoReceived(ref utxo) => {
+                Some(MerchantEvent::UtxoReceived(ReceivedUtxo {
+                    payment_id: self.payment_id,
+                    fiat_currency_id: self.fiat_currency_id,
+                    crypto_currency_id: self.crypto_currency_id,
+                    utxo: utxo.clone(),
+                }))
+            }
+            _ => None,
+        };
+
+        if let Some(merchant_event) = merchant_event {
+            self.monitor
+                .merchant_events
+                .publish(self.user_id, merchant_event);
+        }
+
+        self.monitor.publish(self.payment_id, event);
+    }
+}
+
+/// Runs the on-chain watchers of payments, independently of the clients following them.
+///
+/// Clients subscribe to the events of a payment whether or not its watcher is running, so
+/// several of them can follow the same payment and a reconnecting client picks up where the
+/// running watcher is. This is the single source of payment events for every live channel.
+#[derive(Clone)]
+pub struct PaymentMonitor {
+    payments: Arc<Mutex<HashMap<i32, MonitoredPayment>>>,
+    next_watcher_id: Arc<AtomicU64>,
+    /// Event ids start from the creation time of the monitor, so they keep increasing across
+    /// restarts of the gateway
+    last_event_id: Arc<AtomicU64>,
+    merchant_events: MerchantEventBus,
+    /// Used to quote again payments whose quote lapsed before they were paid
+    price_oracle: Data<dyn PriceOracle>,
+    quote_policy: QuotePolicy,
+}
+
+impl PaymentMonitor {
+    pub fn new(
+        merchant_events: MerchantEventBus,
+        price_oracle: Data<dyn PriceOracle>,
+        quote_policy: QuotePolicy,
+    ) -> Self {
+        PaymentMonitor {
+            payments: Default::default(),
+            next_watcher_id: Default::default(),
+            last_event_id: Arc::new(AtomicU64::new(Utc::now().timestamp_micros() as u64)),
+            merchant_events,
+            price_oracle,
+            quote_policy,
+        }
+    }
+
+    /// Follow the events of the payment, the subscription is closed once the payment is
+    /// finished. Clients resuming from an event id get the events they missed if still kept.
+    pub fn subscribe(&self, payment_id: i32, last_event_id: Option<u64>) -> PaymentSubscription {
+        let mut payments = self.payments.lock().unwrap();
+        let monitored_payment = payments
+            .entry(payment_id)
+            .or_insert_with(|| MonitoredPayment::new(self.last_event_id.load(Ordering::SeqCst)));
+
+        let missed_events = match last_event_id {
+            Some(last_event_id) if last_event_id >= monitored_payment.replayable_after => Some(
+                monitored_payment
+                    .history
+                    .iter()
+                    .filter(|event| event.id > last_event_id)
+                    .cloned()
+                    .collect(),
+            ),
+            Some(_) => None,
+            None => Some(Vec::new()),
+        };
+
+        PaymentSubscription {
+            missed_events,
+            events: Some(monitored_payment.events.subscribe()),
+            monitor: self.clone(),
+            payment_id,
+        }
+    }
+
+    /// Forget the payment if it is neither watched nor followed anymore
+    fn release(&self, payment_id: i32) {
+        let mut payments = self.payments.lock().unwrap();
+
+        if let Some(monitored_payment) = payments.get(&payment_id) {
+            if monitored_payment.watcher.is_none() && monitored_payment.events.receiver_count() == 0
+            {
+                payments.remove(&payment_id);
+            }
+        }
+    }
+
+    pub fn publish(&self, payment_id: i32, event: PaymentEvent) {
+        let mut payments = self.payments.lock().unwrap();
+        let Some(monitored_payment) = payments.get_mut(&payment_id) else {
+            return;
+        };
+
+        let event = SequencedPaymentEvent {
+            id: self.last_event_id.fetch_add(1, Ordering::SeqCst) + 1,
+            event,
+        };
+
+        if monitored_payment.history.len() == EVENT_BUFFER_SIZE {
+            // unwrap: history is full
+            let evicted_event = monitored_payment.history.pop_front().unwrap();
+            monitored_payment.replayable_after = evicted_event.id;
+        }
+        monitored_payment.history.push_back(event.clone());
+
+        // no one following the payment is fine
+        let _ = monitored_payment.events.send(event);
+    }
+
+    /// Stop watching the payment, leaving its wallet reserved
+    pub fn stop(&self, payment_id: i32) {
+        if let Some(monitored_payment) = self.payments.lock().unwrap().get_mut(&payment_id) {
+            if let Some((_, watcher)) = monitored_payment.watcher.take() {
+                log::info!("Stop watching payment with id {payment_id}");
+                watcher.abort();
+            }
+        }
+    }
+
+    /// Watch the payment's wallet until it is paid or expires, replacing its previous watcher
+    pub fn start(
+        &self,
+        payment: payment::Model,
+        network: network::Model,
+        crypto_currency: crypto_currency::Model,
+        wallet: wallet::Model,
+        db: Data<DbConn>,
+    ) {
+        let payment_id = payment.id;
+        let watcher_id = self.next_watcher_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut payments = self.payments.lock().unwrap();
+        let monitored_payment = payments
+            .entry(payment_id)
+            .or_insert_with(|| MonitoredPayment::new(self.last_event_id.load(Ordering::SeqCst)));
+
+        if let Some((_, watcher)) = monitored_payment.watcher.take() {
+            log::info!("Abort previous watcher of payment with id {payment_id}");
+            watcher.abort();
+        }
+
+        let events = self.publisher(&payment, &crypto_currency);
+        let monitor = self.clone();
+
+        let watcher = tokio::spawn(async move {
+            let res = AssertUnwindSafe(watch_payment(
+                payment,
+                &network,
+                &crypto_currency,
+                &wallet,
+                &events,
+                db,
+            ))
+            .catch_unwind()
+            .await;
+
+            match res {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    log::error!("Watcher of payment with id {payment_id} failed: {err}")
+                }
+                Err(_) => log::error!("Watcher of payment with id {payment_id} panicked"),
+            }
+
+            monitor.finish(payment_id, watcher_id);
+        });
+
+        monitored_payment.watcher = Some((watcher_id, watcher));
+    }
+
+    /// Settle the payment by what was found paid for it outside of its watcher, which is stopped
+    pub async fn settle(
+        &self,
+        payment: payment::Model,
+        crypto_currency: &crypto_currency::Model,
+        paid_crypto: Decimal,
+        db: &Data<DbConn>,
+    ) -> Result<(), InternalError> {
+        self.stop(payment.id);
+
+        let policy =
+            payment_policy_service::resolve(db, payment.user_id, crypto_currency.id).await?;
+        let events = self.publisher(&payment, crypto_currency);
+
+        settle_payment(payment, paid_crypto, policy.as_ref(), &events, db).await
+    }
+
+    pub fn publisher(
+        &self,
+        payment: &payment::Model,
+        crypto_currency: &crypto_currency::Model,
+    ) -> PaymentEventPublisher {
+        PaymentEventPublisher {
+            monitor: self.clone(),
+            payment_id: payment.id,
+            user_id: payment.user_id,
+            fiat_currency_id: payment.fiat_currency_id,
+            crypto_currency_id: crypto_currency.id,
+        }
+    }
+
+    /// Restart the watchers of the payments which were waiting for funds when the gateway
+    /// stopped
+    pub async fn resume_waiting_payments(&self, db: Data<DbConn>) -> Result<(), InternalError> {
+        for payment in payment_service::find_all_watchable(&db).await? {
+            let (Some(crypto_currency_id), Some(dest_wallet_id)) =
+                (payment.crypto_currency_id, payment.dest_wallet_id)
+            else {
+                continue;
+            };
+
+            let crypto_currency =
+                crypto_currency_service::find_by_id(&db, crypto_currency_id).await?;
+            let wallet = wallet_service::find_by_id(&db, dest_wallet_id).await?;
+            let (Some(crypto_currency), Some(wallet)) = (crypto_currency, wallet) else {
+                continue;
+            };
+            let Some(network) =
+                network_service::find_by_id(&db, crypto_currency.network_id).await?
+            else {
+                continue;
+            };
+
+            log::info!("Resume watching payment with id {}", payment.id);
+            self.start(payment, network, crypto_currency, wallet, db.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Forget a payment whose watcher is done, which closes the channel of its followers
+    fn finish(&self, payment_id: i32, watcher_id: u64) {
+        let mut payments = self.payments.lock().unwrap();
+
+        // the watcher may have been replaced by a newer one in the meantime
+        let is_current_watcher = payments
+            .get(&payment_id)
+            .and_then(|monitored_payment| monitored_payment.watcher.as_ref())
+            .map(|(id, _)| *id == watcher_id)
+            .unwrap_or(false);
+
+        if is_current_watcher {
+            payments.remove(&payment_id);
+        }
+    }
+}
+
+async fn watch_payment(
+    mut payment: payment::Model,
+    network: &network::Model,
+    crypto_currency: &crypto_currency::Model,
+    wallet: &wallet::Model,
+    events: &PaymentEventPublisher,
+    db: Data<DbConn>,
+) -> Result<(), anyhow::Error> {
+    log::info!(
+        "Start subscribing transactions of payment with id: {}",
+        payment.id
+    );
+
+    let mut quote_valid_until = payment_quote_service::find_current_by_payment_id(&db, payment.id)
+        .await?
+        .map(|quote| quote.valid_until);
+
+    let policy = payment_policy_service::resolve(&db, payment.user_id, crypto_currency.id).await?;
+    let chain_watcher = chain_watcher::for_network(network)?;
+    let mut retry_delay = INITIAL_WATCH_RETRY_DELAY;
+
+    let paid_crypto = loop {
+        let target = WatchTarget {
+            payment_id: payment.id,
+            crypto_amount: payment_policy_service::accepted_crypto_amount(
+                policy.as_ref(),
+                payment.crypto_amount.unwrap(),
+            ),
+            expiration_date: payment.expired_at,
+            quote_valid_until,
+        };
+
+        let outcome = match chain_watcher
+            .watch(crypto_currency, wallet, &target, events, db.clone())
+            .await
+        {
+            Ok(outcome) => outcome,
+            // what is paid is left to the reconciler, the expiration job expires the payment
+            Err(err) if Utc::now().naive_utc() > payment.expired_at => return Err(err.into()),
+            Err(err) => {
+                log::error!(
+                    "Failed to watch payment with id {}, retrying in {retry_delay:?}: {err}",
+                    payment.id
+                );
+
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_WATCH_RETRY_DELAY);
+                continue;
+            }
+        };
+        retry_delay = INITIAL_WATCH_RETRY_DELAY;
+
+        match outcome {
+            WatchOutcome::Ended(paid_crypto) => break paid_crypto,
+            WatchOutcome::QuoteLapsed => {
+                match requote(&payment, crypto_currency, events, &db).await {
+                    Ok(Some((requoted_payment, quote))) => {
+                        log::info!(
+                            "Payment with id {} is quoted again at {}",
+                            payment.id,
+                            quote.rate
+                        );
+
+                        payment = requoted_payment;
+                        quote_valid_until = Some(quote.valid_until);
+                        events.publish(PaymentEvent::PaymentUpdated(payment.clone()));
+                    }
+                    // the payment changed in the meantime, it is watched by its new watcher
+                    Ok(None) => return Ok(()),
+                    Err(err) => {
+                        log::error!(
+                            "Failed to quote payment with id {} again: {err}",
+                            payment.id
+                        );
+
+                        // keep the lapsed quote rather than stop watching the wallet
+                        quote_valid_until = None;
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(settle_payment(payment, paid_crypto, policy.as_ref(), events, &db).await?)
+}
+
+/// Settle the payment by what was paid for it under the merchant's payment policy
+async fn settle_payment(
+    payment: payment::Model,
+    paid_crypto: Decimal,
+    policy: Option<&payment_policy::Model>,
+    events: &PaymentEventPublisher,
+    db: &Data<DbConn>,
+) -> Result<(), InternalError> {
+    let payment = payment_service::record_paid_crypto(db, payment.id, paid_crypto).await?;
+    let previous_status = payment.status.clone();
+
+    let (settled_crypto_amount, overpaid) =
+        match payment_policy_service::settle(policy, payment.crypto_amount.unwrap(), paid_crypto) {
+            Settlement::Unpaid => {
+                events.publish(PaymentEvent::PaymentExpired(payment));
+                // payment expiration job will free payment wallet and update its status
+                return Ok(());
+            }
+
+            Settlement::PartiallyPaid => {
+                // the expiration job may have expired it already
+                let Some(payment) = payment_service::settle_partially_paid(db, payment).await?
+                else {
+                    return Ok(());
+                };
+
+                log::info!("Payment with id {} is partially paid", payment.id);
+
+                notify_status_change(&payment, previous_status, events, db);
+                events.publish(PaymentEvent::PaymentPartiallyPaid(payment));
+
+                return Ok(());
+            }
+
+            Settlement::Paid {
+                settled_crypto_amount,
+                overpaid,
+            } => (settled_crypto_amount, overpaid),
+        };
+
+    // settled by the reconciler in the meantime
+    let Some(payment) =
+        payment_service::settle_paid(db, payment, settled_crypto_amount, overpaid).await?
+    else {
+        return Ok(());
+    };
+
+    log::info!("Payment with id {} is {}!", payment.id, payment.status);
+
+    notify_status_change(&payment, previous_status, events, db);
+
+    if overpaid {
+        events.publish(PaymentEvent::PaymentOverpaid(payment));
+    } else {
+        events.publish(PaymentEvent::PaymentDone(payment));
+    }
+
+    Ok(())
+}
+
+/// Tell the merchant about the new status of the payment, by webhook and on their event streams
+fn notify_status_change(
+    payment: &payment::Model,
+    previous_status: PaymentStatus,
+    events: &PaymentEventPublisher,
+    db: &Data<DbConn>,
+) {
+    webhook_service::spawn_webhook_dispatcher(payment.clone(), previous_status.clone(), db.clone());
+    events.monitor.merchant_events.publish(
+        payment.user_id,
+        MerchantEvent::payment_status_changed(payment.clone(), previous_status),
+    );
+}
+
+/// Quote the payment again at the current rate
+async fn requote(
+    payment: &payment::Model,
+    crypto_currency: &crypto_currency::Model,
+    events: &PaymentEventPublisher,
+    db: &DbConn,
+) -> Result<Option<(payment::Model, payment_quote::Model)>, anyhow::Error> {
+    let fiat_currency = fiat_currency_service::find_by_id(db, payment.fiat_currency_id)
+        .await?
+        .ok_or(NotFoundError::FiatCurrencyNotFoundWithGivenId)?;
+
+    let quote = payment_quote_service::fetch(
+        events.monitor.price_oracle.get_ref(),
+        &events.monitor.quote_policy,
+        payment,
+        &fiat_currency,
+        crypto_currency,
+    )
+    .await?;
+
+    Ok(payment_quote_service::requote(db, quote).await?)
+}
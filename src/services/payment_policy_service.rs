@@ -0,0 +1,135 @@
+use crate::entities::payment_policy::OverpaymentAction;
+use crate::impl_crud;
+use crate::{
+    entities::{payment_policy, prelude::*},
+    errors::InternalError,
+};
+use sea_orm::prelude::Decimal;
+use sea_orm::{ColumnTrait, Condition, DbConn, DeleteResult, EntityTrait, QueryFilter, QueryOrder};
+
+impl_crud!(PaymentPolicy, payment_policy, InternalError, i32);
+
+/// How the paid amount of a payment is settled once its wallet is no longer watched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Settlement {
+    /// Nothing was paid
+    Unpaid,
+    /// Less than the accepted amount was paid
+    PartiallyPaid,
+    /// The payment is paid, crediting the merchant for the settled amount
+    Paid {
+        settled_crypto_amount: Decimal,
+        overpaid: bool,
+    },
+}
+
+pub async fn find_all_by_user_id(
+    db: &DbConn,
+    user_id: i32,
+) -> Result<Vec<payment_policy::Model>, InternalError> {
+    Ok(PaymentPolicy::find()
+        .filter(payment_policy::Column::UserId.eq(user_id))
+        .order_by_asc(payment_policy::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// The policy of the merchant for the crypto currency, or their default policy
+pub async fn find_by_user_id_and_crypto_currency_id(
+    db: &DbConn,
+    user_id: i32,
+    crypto_currency_id: Option<i32>,
+) -> Result<Option<payment_policy::Model>, InternalError> {
+    let crypto_currency_condition = match crypto_currency_id {
+        Some(crypto_currency_id) => payment_policy::Column::CryptoCurrencyId.eq(crypto_currency_id),
+        None => payment_policy::Column::CryptoCurrencyId.is_null(),
+    };
+
+    Ok(PaymentPolicy::find()
+        .filter(payment_policy::Column::UserId.eq(user_id))
+        .filter(crypto_currency_condition)
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// The policy payments of the merchant in the crypto currency are settled with: the one of the
+/// currency if any, else the default one of the merchant
+pub async fn resolve(
+    db: &DbConn,
+    user_id: i32,
+    crypto_currency_id: i32,
+) -> Result<Option<payment_policy::Model>, InternalError> {
+    Ok(PaymentPolicy::find()
+        .filter(payment_policy::Column::UserId.eq(user_id))
+        .filter(
+            Condition::any()
+                .add(payment_policy::Column::CryptoCurrencyId.eq(crypto_currency_id))
+                .add(payment_policy::Column::CryptoCurrencyId.is_null()),
+        )
+        // nulls come last in ascending order
+        .order_by_asc(payment_policy::Column::CryptoCurrencyId)
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Least amount paid for `crypto_amount` that is accepted as paid. When both tolerances are
+/// set, the largest one applies.
+pub fn accepted_crypto_amount(
+    policy: Option<&payment_policy::Model>,
+    crypto_amount: Decimal,
+) -> Decimal {
+    let Some(policy) = policy else {
+        return crypto_amount;
+    };
+
+    let tolerance = policy
+        .underpayment_tolerance_amount
+        .unwrap_or_default()
+        .max(
+            crypto_amount * policy.underpayment_tolerance_percent.unwrap_or_default()
+                / Decimal::ONE_HUNDRED,
+        );
+
+    (crypto_amount - tolerance).max(Decimal::ZERO)
+}
+
+/// Settle `paid_crypto` paid for `crypto_amount` according to the policy, payments without
+/// policy only accept the exact amount and leave any surplus refundable
+pub fn settle(
+    policy: Option<&payment_policy::Model>,
+    crypto_amount: Decimal,
+    paid_crypto: Decimal,
+) -> Settlement {
+    if paid_crypto <= Decimal::ZERO {
+        return Settlement::Unpaid;
+    }
+
+    if paid_crypto < accepted_crypto_amount(policy, crypto_amount) {
+        return Settlement::PartiallyPaid;
+    }
+
+    if paid_crypto <= crypto_amount {
+        return Settlement::Paid {
+            settled_crypto_amount: paid_crypto,
+            overpaid: false,
+        };
+    }
+
+    let overpayment_action = policy
+        .map(|policy| policy.overpayment_action.clone())
+        .unwrap_or(OverpaymentAction::FlagForRefund);
+
+    match overpayment_action {
+        OverpaymentAction::Credit => Settlement::Paid {
+            settled_crypto_amount: paid_crypto,
+            overpaid: false,
+        },
+        OverpaymentAction::FlagForRefund => Settlement::Paid {
+            settled_crypto_amount: crypto_amount,
+            overpaid: true,
+        },
+    }
+}
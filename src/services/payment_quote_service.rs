@@ -0,0 +1,123 @@
+use super::payment_service;
+use super::price_oracle::{self, PriceOracle};
+use crate::entities::payment::{self, PaymentStatus};
+use crate::entities::{crypto_currency, fiat_currency};
+use crate::errors::PriceOracleError;
+use crate::impl_crud;
+use crate::{
+    entities::{payment_quote, prelude::*},
+    errors::InternalError,
+};
+use chrono::{Duration, Utc};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+
+impl_crud!(PaymentQuote, payment_quote, InternalError, i32);
+
+/// How payments are quoted
+#[derive(Clone, Debug)]
+pub struct QuotePolicy {
+    /// How long the rate of a quote is honored once fetched
+    pub validity_duration: Duration,
+    /// Markup added to the crypto amount, in percent
+    pub spread_percent: Decimal,
+}
+
+pub async fn find_all_by_payment_id(
+    db: &DbConn,
+    payment_id: i32,
+) -> Result<Vec<payment_quote::Model>, InternalError> {
+    Ok(PaymentQuote::find()
+        .filter(payment_quote::Column::PaymentId.eq(payment_id))
+        .order_by_asc(payment_quote::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// The quote the crypto amount of the payment currently comes from
+pub async fn find_current_by_payment_id(
+    db: &DbConn,
+    payment_id: i32,
+) -> Result<Option<payment_quote::Model>, InternalError> {
+    Ok(PaymentQuote::find()
+        .filter(payment_quote::Column::PaymentId.eq(payment_id))
+        .order_by_desc(payment_quote::Column::Id)
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Quote the payment in the crypto currency at the current rate of the price oracle
+pub async fn fetch(
+    price_oracle: &dyn PriceOracle,
+    policy: &QuotePolicy,
+    payment: &payment::Model,
+    fiat_currency: &fiat_currency::Model,
+    crypto_currency: &crypto_currency::Model,
+) -> Result<payment_quote::ActiveModel, PriceOracleError> {
+    let rate = price_oracle
+        .price(&crypto_currency.symbol, &fiat_currency.symbol)
+        .await?;
+    let fetched_at = Utc::now().naive_utc();
+
+    let crypto_amount = price_oracle::fiat_to_crypto_at(payment.amount, rate)
+        * (Decimal::ONE_HUNDRED + policy.spread_percent)
+        / Decimal::ONE_HUNDRED;
+    // never ask for less than the quoted amount because of the currency precision
+    let crypto_amount = price_oracle::round_up_dp(crypto_amount, crypto_currency.decimals as u32);
+
+    Ok(payment_quote::ActiveModel {
+        payment_id: Set(payment.id),
+        crypto_currency_id: Set(crypto_currency.id),
+        rate: Set(rate),
+        source: Set(price_oracle.source()),
+        spread_percent: Set(policy.spread_percent),
+        crypto_amount: Set(crypto_amount),
+        fetched_at: Set(fetched_at),
+        valid_until: Set(fetched_at + policy.validity_duration),
+        ..Default::default()
+    })
+}
+
+/// Replace the lapsed quote of a waiting payment, updating its crypto amount.
+///
+/// Returns `None` when the payment stopped waiting or moved to another crypto currency in the
+/// meantime, in which case the quote is dropped.
+pub async fn requote(
+    db: &DbConn,
+    quote: payment_quote::ActiveModel,
+) -> Result<Option<(payment::Model, payment_quote::Model)>, InternalError> {
+    let txn = db.begin().await?;
+
+    let updated = Payment::update_many()
+        .col_expr(
+            payment::Column::CryptoAmount,
+            Expr::value(quote.crypto_amount.clone().unwrap()),
+        )
+        .filter(payment::Column::Id.eq(quote.payment_id.clone().unwrap()))
+        .filter(payment::Column::Status.eq(PaymentStatus::Waiting))
+        .filter(payment::Column::CryptoCurrencyId.eq(quote.crypto_currency_id.clone().unwrap()))
+        .exec(&txn)
+        .await?;
+
+    if updated.rows_affected == 0 {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
+    let quote = quote.insert(&txn).await?;
+
+    txn.commit().await?;
+
+    // unwrap: the payment was just updated
+    let payment = payment_service::find_by_id(db, quote.payment_id)
+        .await?
+        .unwrap();
+
+    Ok(Some((payment, quote)))
+}
@@ -1,55 +1,382 @@
+use super::price_oracle::{self, PriceOracle};
 use super::{
-    crypto_currency_service, fiat_currency_service, kucoin_api_service, user_transaction_service,
+    crypto_currency_service, fiat_currency_service, payment_quote_service, user_transaction_service,
 };
 use crate::entities::payment::PaymentStatus;
 use crate::entities::user_transaction::{self, UserTransactionType};
 use crate::impl_crud;
-use crate::services::wallet_service;
+use crate::models::dtos::{PaymentFilter, PaymentSortField};
+use crate::models::merchant_event::MerchantEvent;
+use crate::models::pagination::{Page, Pagination};
+use crate::services::{merchant_event_bus::MerchantEventBus, wallet_service, webhook_service};
 use crate::{
     entities::{payment, prelude::*},
     errors::InternalError,
 };
 use actix_web::web::Data;
-use chrono::{Duration, Utc};
-use sea_orm::{ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter, Set};
+use chrono::{Duration, NaiveDateTime, Utc};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveValue, ColumnTrait, Condition, DbConn, DeleteResult, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
 
 impl_crud!(Payment, payment, InternalError, i32);
 
-pub async fn find_all_by_user_id(
+pub async fn record_paid_crypto(
+    db: &DbConn,
+    id: i32,
+    paid_crypto_amount: Decimal,
+) -> Result<payment::Model, InternalError> {
+    let payment = payment::ActiveModel {
+        id: ActiveValue::Unchanged(id),
+        paid_crypto_amount: Set(Some(paid_crypto_amount)),
+        ..Default::default()
+    };
+
+    update(db, payment).await
+}
+
+/// Switch the waiting payment to the crypto currency and its wallet, only if the payment still
+/// holds the wallet it was read with. Returns `None` when a concurrent switch won the race.
+///
+/// What was paid and how far the chain was reconciled belong to the previous currency and its
+/// network, so both are reset.
+pub async fn switch_crypto(
+    db: &DbConn,
+    id: i32,
+    held_wallet_id: Option<i32>,
+    crypto_currency_id: i32,
+    crypto_amount: Decimal,
+    dest_wallet_id: i32,
+) -> Result<Option<payment::Model>, InternalError> {
+    let held_wallet = match held_wallet_id {
+        Some(held_wallet_id) => payment::Column::DestWalletId.eq(held_wallet_id),
+        None => payment::Column::DestWalletId.is_null(),
+    };
+
+    let res = Payment::update_many()
+        .col_expr(
+            payment::Column::CryptoCurrencyId,
+            Expr::value(crypto_currency_id),
+        )
+        .col_expr(payment::Column::CryptoAmount, Expr::value(crypto_amount))
+        .col_expr(payment::Column::DestWalletId, Expr::value(dest_wallet_id))
+        .col_expr(
+            payment::Column::PaidCryptoAmount,
+            Expr::value(Option::<Decimal>::None),
+        )
+        .col_expr(
+            payment::Column::ReconciledBlockNumber,
+            Expr::value(Option::<i64>::None),
+        )
+        .filter(payment::Column::Id.eq(id))
+        .filter(payment::Column::Status.eq(PaymentStatus::Waiting))
+        .filter(held_wallet)
+        .exec(db)
+        .await?;
+
+    if res.rows_affected == 0 {
+        return Ok(None);
+    }
+
+    find_by_id(db, id).await
+}
+
+/// Find a page of the payments matching the filter, of the given user or of all users
+pub async fn find_filtered_page(
+    db: &DbConn,
+    user_id: Option<i32>,
+    filter: &PaymentFilter,
+    pagination: &Pagination,
+) -> Result<Page<payment::Model>, InternalError> {
+    let condition = Condition::all()
+        .add_option(user_id.map(|user_id| payment::Column::UserId.eq(user_id)))
+        .add_option(
+            filter
+                .status
+                .clone()
+                .map(|status| payment::Column::Status.eq(status)),
+        )
+        .add_option(
+            filter
+                .fiat_currency_id
+                .map(|fiat_currency_id| payment::Column::FiatCurrencyId.eq(fiat_currency_id)),
+        )
+        .add_option(
+            filter
+                .crypto_currency_id
+                .map(|crypto_currency_id| payment::Column::CryptoCurrencyId.eq(crypto_currency_id)),
+        )
+        .add_option(
+            filter
+                .seller_order_id
+                .clone()
+                .map(|seller_order_id| payment::Column::SellerOrderId.eq(seller_order_id)),
+        )
+        .add_option(
+            filter
+                .created_from
+                .map(|created_from| payment::Column::CreatedAt.gte(created_from)),
+        )
+        .add_option(
+            filter
+                .created_to
+                .map(|created_to| payment::Column::CreatedAt.lt(created_to)),
+        );
+
+    let sort_by = match filter.sort_by {
+        PaymentSortField::CreatedAt => payment::Column::CreatedAt,
+        PaymentSortField::ExpiredAt => payment::Column::ExpiredAt,
+        PaymentSortField::Amount => payment::Column::Amount,
+    };
+
+    find_page(db, condition, sort_by, pagination).await
+}
+
+/// Find the payment a retried creation request refers to: the one created with the same
+/// idempotency key, or the non expired nor partially paid one of the same seller order
+pub async fn find_existing(
     db: &DbConn,
     user_id: i32,
-) -> Result<Vec<payment::Model>, InternalError> {
+    seller_order_id: &str,
+    idempotency_key: Option<&str>,
+) -> Result<Option<payment::Model>, InternalError> {
+    let mut condition = Condition::any().add(
+        Condition::all()
+            .add(payment::Column::SellerOrderId.eq(seller_order_id))
+            .add(
+                payment::Column::Status
+                    .is_not_in([PaymentStatus::Expired, PaymentStatus::PartiallyPaid]),
+            ),
+    );
+    if let Some(idempotency_key) = idempotency_key {
+        condition = condition.add(payment::Column::IdempotencyKey.eq(idempotency_key));
+    }
+
     Ok(Payment::find()
         .filter(payment::Column::UserId.eq(user_id))
+        .filter(condition)
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Find the waiting payments which have a crypto currency and a wallet to watch
+pub async fn find_all_watchable(db: &DbConn) -> Result<Vec<payment::Model>, InternalError> {
+    Ok(Payment::find()
+        .filter(payment::Column::Status.eq(PaymentStatus::Waiting))
+        .filter(payment::Column::CryptoCurrencyId.is_not_null())
+        .filter(payment::Column::DestWalletId.is_not_null())
+        .filter(payment::Column::ExpiredAt.gt(Utc::now().naive_utc()))
         .all(db)
         .await
         .map_err(Into::<InternalError>::into)?)
 }
 
-pub fn spawn_payment_exp_scheduler(run_after: Duration, payment_id: i32, db: Data<DbConn>) {
-    tokio::spawn(async move {
-        tokio::time::sleep(run_after.to_std().unwrap()).await;
+/// Find the payments whose wallet may have been paid without their watcher noticing: the waiting
+/// ones, and those expired since `expired_since`
+pub async fn find_all_reconcilable(
+    db: &DbConn,
+    expired_since: NaiveDateTime,
+) -> Result<Vec<payment::Model>, InternalError> {
+    Ok(Payment::find()
+        .filter(payment::Column::CryptoCurrencyId.is_not_null())
+        .filter(payment::Column::DestWalletId.is_not_null())
+        .filter(
+            Condition::any()
+                .add(payment::Column::Status.eq(PaymentStatus::Waiting))
+                .add(
+                    Condition::all()
+                        .add(payment::Column::Status.eq(PaymentStatus::Expired))
+                        .add(payment::Column::ExpiredAt.gt(expired_since)),
+                ),
+        )
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+pub async fn record_reconciled_block_number(
+    db: &DbConn,
+    id: i32,
+    reconciled_block_number: i64,
+) -> Result<payment::Model, InternalError> {
+    let payment = payment::ActiveModel {
+        id: ActiveValue::Unchanged(id),
+        reconciled_block_number: Set(Some(reconciled_block_number)),
+        ..Default::default()
+    };
 
-        let payment = find_by_id(&db, payment_id).await.unwrap().unwrap();
+    update(db, payment).await
+}
 
-        if payment.status == PaymentStatus::Waiting {
-            log::info!("Payment with id {} is expired", payment_id);
+/// Mark a payment which was paid at least the accepted amount as done, or overpaid, and free its
+/// wallet, unless its status changed since it was read. Expired payments found paid in time
+/// are settled too.
+pub async fn settle_paid(
+    db: &DbConn,
+    mut payment: payment::Model,
+    settled_crypto_amount: Decimal,
+    overpaid: bool,
+) -> Result<Option<payment::Model>, InternalError> {
+    let status = if overpaid {
+        PaymentStatus::Overpaid
+    } else {
+        PaymentStatus::Done
+    };
+    let done_at = Utc::now().naive_utc();
 
-            if let Some(dest_wallet_id) = payment.dest_wallet_id {
-                wallet_service::free(&db, dest_wallet_id).await.unwrap();
-            }
+    let txn = db.begin().await?;
 
-            let mut payment = payment::ActiveModel::from(payment);
-            payment.status = Set(PaymentStatus::Expired);
+    let settled = Payment::update_many()
+        .col_expr(payment::Column::Status, Expr::value(status.clone()))
+        .col_expr(payment::Column::DoneAt, Expr::value(done_at))
+        .col_expr(
+            payment::Column::SettledCryptoAmount,
+            Expr::value(settled_crypto_amount),
+        )
+        .filter(payment::Column::Id.eq(payment.id))
+        .filter(payment::Column::Status.eq(payment.status.clone()))
+        .filter(payment::Column::Status.is_in([PaymentStatus::Waiting, PaymentStatus::Expired]))
+        .exec(&txn)
+        .await?;
 
-            update(&db, payment).await.unwrap();
+    if settled.rows_affected == 0 {
+        txn.rollback().await?;
+        return Ok(None);
+    }
 
-            // TODO: If some money is paid, return it
+    if let Some(dest_wallet_id) = payment.dest_wallet_id {
+        wallet_service::free(&txn, dest_wallet_id, payment.id).await?;
+    }
+
+    txn.commit().await?;
+
+    payment.status = status;
+    payment.done_at = Some(done_at);
+    payment.settled_crypto_amount = Some(settled_crypto_amount);
+    Ok(Some(payment))
+}
+
+/// Mark a payment which was paid less than accepted as partially paid and free its wallet,
+/// unless its status changed since it was read. The paid amount is left refundable.
+pub async fn settle_partially_paid(
+    db: &DbConn,
+    mut payment: payment::Model,
+) -> Result<Option<payment::Model>, InternalError> {
+    let txn = db.begin().await?;
+
+    let settled = Payment::update_many()
+        .col_expr(
+            payment::Column::Status,
+            Expr::value(PaymentStatus::PartiallyPaid),
+        )
+        .filter(payment::Column::Id.eq(payment.id))
+        .filter(payment::Column::Status.eq(payment.status.clone()))
+        .filter(payment::Column::Status.is_in([PaymentStatus::Waiting, PaymentStatus::Expired]))
+        .exec(&txn)
+        .await?;
+
+    if settled.rows_affected == 0 {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
+    if let Some(dest_wallet_id) = payment.dest_wallet_id {
+        wallet_service::free(&txn, dest_wallet_id, payment.id).await?;
+    }
+
+    txn.commit().await?;
+
+    payment.status = PaymentStatus::PartiallyPaid;
+    Ok(Some(payment))
+}
+
+/// How often overdue payments are looked for
+const EXPIRATION_CHECK_INTERVAL_IN_SECONDS: i64 = 30;
+
+pub fn spawn_payment_expiration_job(merchant_events: Data<MerchantEventBus>, db: Data<DbConn>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = expire_overdue_payments(&merchant_events, &db).await {
+                log::error!("Failed to expire overdue payments: {err}");
+            }
+
+            tokio::time::sleep(
+                Duration::seconds(EXPIRATION_CHECK_INTERVAL_IN_SECONDS)
+                    .to_std()
+                    .unwrap(),
+            )
+            .await;
         }
     });
 }
 
-pub fn spawn_crypto_seller(payment: payment::Model, db: Data<DbConn>) {
+/// Expire every waiting payment which is past its expiration date and free its wallet.
+///
+/// Each payment is expired by a conditional update in its own transaction, so running this on
+/// several gateway instances at once still expires (and notifies) every payment exactly once.
+pub async fn expire_overdue_payments(
+    merchant_events: &MerchantEventBus,
+    db: &Data<DbConn>,
+) -> Result<(), InternalError> {
+    let overdue_payments = Payment::find()
+        .filter(payment::Column::Status.eq(PaymentStatus::Waiting))
+        .filter(payment::Column::ExpiredAt.lt(Utc::now().naive_utc()))
+        .all(db.get_ref())
+        .await?;
+
+    for mut payment in overdue_payments {
+        let txn = db.begin().await?;
+
+        let expired = Payment::update_many()
+            .col_expr(payment::Column::Status, Expr::value(PaymentStatus::Expired))
+            .filter(payment::Column::Id.eq(payment.id))
+            .filter(payment::Column::Status.eq(PaymentStatus::Waiting))
+            .exec(&txn)
+            .await?;
+
+        // already expired by another instance, or paid in the meantime
+        if expired.rows_affected == 0 {
+            txn.rollback().await?;
+            continue;
+        }
+
+        if let Some(dest_wallet_id) = payment.dest_wallet_id {
+            wallet_service::free(&txn, dest_wallet_id, payment.id).await?;
+        }
+
+        txn.commit().await?;
+
+        log::info!("Payment with id {} is expired", payment.id);
+
+        payment.status = PaymentStatus::Expired;
+        // anything paid so far is left refundable, see `refund_service::refundable_amount`
+        webhook_service::spawn_webhook_dispatcher(
+            payment.clone(),
+            PaymentStatus::Waiting,
+            db.clone(),
+        );
+        merchant_events.publish(
+            payment.user_id,
+            MerchantEvent::payment_status_changed(payment, PaymentStatus::Waiting),
+        );
+    }
+
+    Ok(())
+}
+
+/// How long to wait before asking the price oracle again after a failed lookup
+const PRICE_RETRY_DELAY_IN_SECONDS: i64 = 30;
+
+pub fn spawn_crypto_seller(
+    payment: payment::Model,
+    price_oracle: Data<dyn PriceOracle>,
+    merchant_events: Data<MerchantEventBus>,
+    db: Data<DbConn>,
+) {
     tokio::spawn(async move {
         let crypto = crypto_currency_service::find_by_id(&db, payment.crypto_currency_id.unwrap())
             .await
@@ -64,13 +391,46 @@ pub fn spawn_crypto_seller(payment: payment::Model, db: Data<DbConn>) {
         // simulate selling crypto...
         tokio::time::sleep(Duration::seconds(5).to_std().unwrap()).await;
 
-        let fiat_value = kucoin_api_service::crypto_to_fiat(
-            &crypto.symbol,
-            payment.crypto_amount.unwrap(),
-            &fiat.symbol,
-        )
-        .await
-        .unwrap();
+        let quote = payment_quote_service::find_current_by_payment_id(&db, payment.id)
+            .await
+            .unwrap();
+
+        // what the merchant is credited for, an underpayment within tolerance or a credited
+        // overpayment moves it away from the crypto amount
+        let settled_crypto = payment
+            .settled_crypto_amount
+            .or(payment.crypto_amount)
+            .unwrap();
+
+        let fiat_value = match quote {
+            // the payer paid at the locked rate, so the merchant gets the amount they asked for
+            // in proportion of what was settled, and any spread is kept
+            Some(_) => (payment.amount * settled_crypto / payment.crypto_amount.unwrap())
+                .round_dp(price_oracle::FIAT_DECIMAL_POINTS),
+            // payments quoted before quotes were recorded are priced at the current rate
+            None => loop {
+                match price_oracle::crypto_to_fiat(
+                    price_oracle.get_ref(),
+                    &crypto.symbol,
+                    settled_crypto,
+                    &fiat.symbol,
+                )
+                .await
+                {
+                    Ok(fiat_value) => break fiat_value,
+                    Err(err) => {
+                        log::error!("Failed to price payment with id {}: {err}", payment.id);
+
+                        tokio::time::sleep(
+                            Duration::seconds(PRICE_RETRY_DELAY_IN_SECONDS)
+                                .to_std()
+                                .unwrap(),
+                        )
+                        .await;
+                    }
+                }
+            },
+        };
 
         // make payment status as finished
         let mut payment = payment::ActiveModel::from(payment);
@@ -80,6 +440,16 @@ pub fn spawn_crypto_seller(payment: payment::Model, db: Data<DbConn>) {
 
         log::info!("Payment with id {} is finished!", payment.id);
 
+        webhook_service::spawn_webhook_dispatcher(
+            payment.clone(),
+            PaymentStatus::Verified,
+            db.clone(),
+        );
+        merchant_events.publish(
+            payment.user_id,
+            MerchantEvent::payment_status_changed(payment.clone(), PaymentStatus::Verified),
+        );
+
         // create user transaction
         let user_payment_transaction = user_transaction::ActiveModel {
             user_id: Set(payment.user_id),
@@ -100,5 +470,127 @@ pub fn spawn_crypto_seller(payment: payment::Model, db: Data<DbConn>) {
             "New user payment transaction: {:#?}",
             user_payment_transaction
         );
+
+        merchant_events.publish(
+            payment.user_id,
+            MerchantEvent::UserTransactionCreated(user_payment_transaction),
+        );
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::network::NetworkKind;
+    use crate::entities::wallet::{self, WalletStatus};
+    use crate::entities::wallet_transaction::{self, WalletTransactionStatus};
+    use crate::services::wallet_transaction_service;
+    use crate::test_utils;
+
+    async fn create_wallet(db: &DbConn, network_id: i32, address: &str) -> wallet::Model {
+        wallet_service::create(
+            db,
+            wallet::ActiveModel {
+                address: Set(format!("{address}-{}", Utc::now().timestamp_nanos())),
+                network_id: Set(network_id),
+                status: Set(WalletStatus::Busy),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn switching_crypto_leaves_what_was_paid_in_the_previous_one_behind() {
+        let db = test_utils::setup_db().await;
+        let network =
+            test_utils::create_network(&db, NetworkKind::Evm, "http://127.0.0.1:8545").await;
+        let token = test_utils::create_crypto_currency(
+            &db,
+            &network,
+            6,
+            Some("0x00000000000000000000000000000000000000aa".to_owned()),
+        )
+        .await;
+        let ether = test_utils::create_crypto_currency(&db, &network, 18, None).await;
+
+        let payment = test_utils::create_payment(&db, &token, Decimal::ONE_HUNDRED).await;
+        let token_wallet = create_wallet(&db, network.id, "token").await;
+        switch_crypto(
+            &db,
+            payment.id,
+            None,
+            token.id,
+            Decimal::ONE_HUNDRED,
+            token_wallet.id,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        // a cheap token transfer, confirmed and reconciled
+        wallet_transaction_service::record(
+            &db,
+            wallet_transaction::ActiveModel {
+                hash: Set(format!("0x{:064x}", payment.id)),
+                wallet_id: Set(token_wallet.id),
+                network_id: Set(network.id),
+                payment_id: Set(Some(payment.id)),
+                crypto_currency_id: Set(Some(token.id)),
+                value: Set(Some(Decimal::new(5, 0))),
+                confirmations: Set(1),
+                status: Set(WalletTransactionStatus::Confirmed),
+                created_at: Set(Utc::now().naive_utc()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        record_paid_crypto(&db, payment.id, Decimal::new(5, 0))
+            .await
+            .unwrap();
+        record_reconciled_block_number(&db, payment.id, 1_000)
+            .await
+            .unwrap();
+
+        let ether_wallet = create_wallet(&db, network.id, "ether").await;
+        let payment = switch_crypto(
+            &db,
+            payment.id,
+            Some(token_wallet.id),
+            ether.id,
+            Decimal::new(5, 2),
+            ether_wallet.id,
+        )
+        .await
+        .unwrap()
+        .expect("the payment should be switched");
+        assert_eq!(payment.paid_crypto_amount, None);
+        assert_eq!(payment.reconciled_block_number, None);
+
+        // 5 tokens are not 5 ether
+        let paid_in_ether =
+            wallet_transaction_service::find_all_by_payment_id_wallet_id_and_crypto_currency_id(
+                &db,
+                payment.id,
+                ether_wallet.id,
+                ether.id,
+            )
+            .await
+            .unwrap();
+        assert!(paid_in_ether.is_empty());
+
+        let paid_in_token =
+            wallet_transaction_service::find_all_by_payment_id_wallet_id_and_crypto_currency_id(
+                &db,
+                payment.id,
+                token_wallet.id,
+                token.id,
+            )
+            .await
+            .unwrap();
+        assert_eq!(paid_in_token.len(), 1);
+    }
+}
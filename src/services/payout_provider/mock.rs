@@ -0,0 +1,81 @@
+use super::{PayoutProvider, PayoutStatus};
+use crate::entities::withdrawal;
+use crate::errors::PayoutError;
+use async_trait::async_trait;
+use chrono::Utc;
+
+const REFERENCE_PREFIX: &str = "mock-";
+const FAILED_SUFFIX: &str = "-failed";
+
+/// Destination reference of the withdrawals the mock refuses to pay
+const REFUSED_DESTINATION_REFERENCE: &str = "MOCK-REFUSE";
+/// Destination reference of the withdrawals whose payout fails once submitted
+const FAILED_DESTINATION_REFERENCE: &str = "MOCK-FAIL";
+
+/// How long a payout of the mock takes to be paid
+const SETTLEMENT_DURATION_IN_SECONDS: i64 = 30;
+
+/// Pays every withdrawal without moving any money, useful for development and testing.
+///
+/// Payouts are paid a little after being submitted, except the ones of withdrawals with
+/// [`REFUSED_DESTINATION_REFERENCE`] or [`FAILED_DESTINATION_REFERENCE`] as destination
+/// reference. The reference of a payout carries everything the mock needs to know about it, so
+/// it keeps no state.
+#[derive(Default)]
+pub struct MockPayoutProvider;
+
+#[async_trait]
+impl PayoutProvider for MockPayoutProvider {
+    async fn submit(&self, withdrawal: &withdrawal::Model) -> Result<String, PayoutError> {
+        let reference = format!(
+            "{REFERENCE_PREFIX}{}-{}",
+            withdrawal.id,
+            Utc::now().timestamp()
+        );
+
+        match withdrawal.destination_reference.as_deref() {
+            Some(REFUSED_DESTINATION_REFERENCE) => Err(PayoutError::Refused(
+                "destination is refused by the mock payout provider".to_owned(),
+            )),
+            Some(FAILED_DESTINATION_REFERENCE) => Ok(format!("{reference}{FAILED_SUFFIX}")),
+            _ => Ok(reference),
+        }
+    }
+
+    /// The mock keeps no state, so an interrupted submission is always submitted again, which
+    /// is fine as it pays nothing
+    async fn find_by_withdrawal_id(
+        &self,
+        _withdrawal_id: i32,
+    ) -> Result<Option<String>, PayoutError> {
+        Ok(None)
+    }
+
+    async fn status(&self, payout_reference: &str) -> Result<PayoutStatus, PayoutError> {
+        let unknown = || PayoutError::UnknownPayout(payout_reference.to_owned());
+
+        let reference = payout_reference
+            .strip_prefix(REFERENCE_PREFIX)
+            .ok_or_else(unknown)?;
+        let (reference, failed) = match reference.strip_suffix(FAILED_SUFFIX) {
+            Some(reference) => (reference, true),
+            None => (reference, false),
+        };
+        let submitted_at = reference
+            .split_once('-')
+            .and_then(|(_, submitted_at)| submitted_at.parse::<i64>().ok())
+            .ok_or_else(unknown)?;
+
+        if Utc::now().timestamp() - submitted_at < SETTLEMENT_DURATION_IN_SECONDS {
+            return Ok(PayoutStatus::Processing);
+        }
+
+        if failed {
+            Ok(PayoutStatus::Failed(
+                "payout is failed by the mock payout provider".to_owned(),
+            ))
+        } else {
+            Ok(PayoutStatus::Paid)
+        }
+    }
+}
@@ -0,0 +1,44 @@
+mod mock;
+
+pub use mock::MockPayoutProvider;
+
+use crate::entities::withdrawal;
+use crate::errors::PayoutError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutProviderKind {
+    #[default]
+    Mock,
+}
+
+/// Where a submitted payout stands at the payout provider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PayoutStatus {
+    Processing,
+    Paid,
+    /// The payout didn't go through, with why
+    Failed(String),
+}
+
+/// Moves the money of approved withdrawals to their destination
+#[async_trait]
+pub trait PayoutProvider: Send + Sync {
+    /// Submit the payout of the withdrawal, returning the reference to follow it with.
+    ///
+    /// A withdrawal is submitted again when its submission fails, so providers should use its
+    /// id as idempotency key and never pay it twice.
+    async fn submit(&self, withdrawal: &withdrawal::Model) -> Result<String, PayoutError>;
+
+    /// Reference of the payout submitted for the withdrawal, looked up by the withdrawal id it is
+    /// submitted with, to recover a submission whose reference was never recorded
+    async fn find_by_withdrawal_id(
+        &self,
+        withdrawal_id: i32,
+    ) -> Result<Option<String>, PayoutError>;
+
+    /// Status of the payout with the reference given by `submit`
+    async fn status(&self, payout_reference: &str) -> Result<PayoutStatus, PayoutError>;
+}
@@ -0,0 +1,75 @@
+use super::{parse_price, PriceOracle};
+use crate::errors::PriceOracleError;
+use async_trait::async_trait;
+use sea_orm::prelude::Decimal;
+use serde_json::Value;
+
+/// Fetches prices from any JSON HTTP API.
+///
+/// `url_template` may contain `{crypto}` and `{fiat}` placeholders, and `price_pointer` is a
+/// JSON pointer (e.g. `/data/price`) to the price in the response, either a string or a number.
+/// The same placeholders are allowed in the pointer.
+pub struct HttpJsonPriceOracle {
+    client: reqwest::Client,
+    url_template: String,
+    price_pointer: String,
+}
+
+impl HttpJsonPriceOracle {
+    pub fn new(url_template: String, price_pointer: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url_template,
+            price_pointer,
+        }
+    }
+}
+
+fn fill_template(template: &str, crypto_symbol: &str, fiat_symbol: &str) -> String {
+    template
+        .replace("{crypto}", crypto_symbol)
+        .replace("{fiat}", fiat_symbol)
+}
+
+#[async_trait]
+impl PriceOracle for HttpJsonPriceOracle {
+    async fn price(
+        &self,
+        crypto_symbol: &str,
+        fiat_symbol: &str,
+    ) -> Result<Decimal, PriceOracleError> {
+        let url = fill_template(&self.url_template, crypto_symbol, fiat_symbol);
+        let price_pointer = fill_template(&self.price_pointer, crypto_symbol, fiat_symbol);
+
+        let res = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        match res.pointer(&price_pointer) {
+            Some(Value::String(price)) => parse_price(price),
+            Some(Value::Number(price)) => parse_price(&price.to_string()),
+            Some(other) => Err(PriceOracleError::BadResponse(format!(
+                "price at '{price_pointer}' is not a number: {other}"
+            ))),
+            None => Err(PriceOracleError::PriceNotFound {
+                crypto_symbol: crypto_symbol.to_owned(),
+                fiat_symbol: fiat_symbol.to_owned(),
+            }),
+        }
+    }
+
+    /// Only the host of the provider, its URL may carry credentials
+    fn source(&self) -> String {
+        match reqwest::Url::parse(&self.url_template) {
+            Ok(url) if url.host_str().is_some() => {
+                format!("http_json:{}", url.host_str().unwrap())
+            }
+            _ => "http_json".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::{parse_price, PriceOracle};
+use crate::errors::PriceOracleError;
+use async_trait::async_trait;
+use sea_orm::prelude::Decimal;
+use serde_json::Value;
+
+const KUCOIN_PRICES_URL: &str = "https://api.kucoin.com/api/v1/prices";
+
+#[derive(Default)]
+pub struct KucoinPriceOracle {
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl PriceOracle for KucoinPriceOracle {
+    async fn price(
+        &self,
+        crypto_symbol: &str,
+        fiat_symbol: &str,
+    ) -> Result<Decimal, PriceOracleError> {
+        let res = self
+            .client
+            .get(KUCOIN_PRICES_URL)
+            .query(&[("base", fiat_symbol), ("currencies", crypto_symbol)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Value>()
+            .await?;
+
+        /*
+        Response look like this:
+        {
+            "code": "200000",
+            "data": {
+                "BTC": "16696.16026711",
+            }
+        }
+        */
+
+        let crypto_fiat_value = res["data"]
+            .get(crypto_symbol)
+            .and_then(Value::as_str)
+            .ok_or_else(|| PriceOracleError::PriceNotFound {
+                crypto_symbol: crypto_symbol.to_owned(),
+                fiat_symbol: fiat_symbol.to_owned(),
+            })?;
+
+        parse_price(crypto_fiat_value)
+    }
+
+    fn source(&self) -> String {
+        "kucoin".to_owned()
+    }
+}
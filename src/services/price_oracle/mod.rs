@@ -0,0 +1,79 @@
+mod http_json;
+mod kucoin;
+mod static_table;
+
+pub use http_json::HttpJsonPriceOracle;
+pub use kucoin::KucoinPriceOracle;
+pub use static_table::StaticPriceOracle;
+
+use crate::errors::PriceOracleError;
+use async_trait::async_trait;
+use sea_orm::prelude::Decimal;
+use serde::Deserialize;
+
+const CRYPTO_DECIMAL_POINTS: u32 = 18;
+pub const FIAT_DECIMAL_POINTS: u32 = 2;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceOracleKind {
+    #[default]
+    Kucoin,
+    Static,
+    HttpJson,
+}
+
+/// Source of crypto prices expressed in fiat currencies
+#[async_trait]
+pub trait PriceOracle: Send + Sync {
+    /// Price of one unit of `crypto_symbol` in `fiat_symbol`
+    async fn price(
+        &self,
+        crypto_symbol: &str,
+        fiat_symbol: &str,
+    ) -> Result<Decimal, PriceOracleError>;
+
+    /// Where prices come from, recorded with the quotes made from them
+    fn source(&self) -> String;
+}
+
+/// Amount of crypto worth `fiat_amount` when one unit of crypto is worth `crypto_fiat_value`
+pub fn fiat_to_crypto_at(fiat_amount: Decimal, crypto_fiat_value: Decimal) -> Decimal {
+    (fiat_amount / crypto_fiat_value).round_dp(CRYPTO_DECIMAL_POINTS)
+}
+
+pub async fn crypto_to_fiat(
+    price_oracle: &dyn PriceOracle,
+    crypto_symbol: &str,
+    crypto_amount: Decimal,
+    fiat_symbol: &str,
+) -> Result<Decimal, PriceOracleError> {
+    let crypto_fiat_value = price_oracle.price(crypto_symbol, fiat_symbol).await?;
+
+    Ok((crypto_amount * crypto_fiat_value).round_dp(FIAT_DECIMAL_POINTS))
+}
+
+/// Round `amount` to `dp` decimal points, away from zero
+pub fn round_up_dp(amount: Decimal, dp: u32) -> Decimal {
+    let rounded = amount.round_dp(dp);
+
+    if rounded < amount {
+        rounded + Decimal::new(1, dp)
+    } else {
+        rounded
+    }
+}
+
+fn parse_price(value: &str) -> Result<Decimal, PriceOracleError> {
+    let price = value
+        .parse::<Decimal>()
+        .map_err(|_| PriceOracleError::BadResponse(format!("'{value}' is not a valid price")))?;
+
+    if price <= Decimal::ZERO {
+        return Err(PriceOracleError::BadResponse(format!(
+            "'{value}' is not a positive price"
+        )));
+    }
+
+    Ok(price)
+}
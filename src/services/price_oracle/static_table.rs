@@ -0,0 +1,64 @@
+use super::{parse_price, PriceOracle};
+use crate::errors::PriceOracleError;
+use async_trait::async_trait;
+use sea_orm::prelude::Decimal;
+use std::collections::HashMap;
+
+/// Serves prices from a fixed table, useful for offline checkouts and testing
+pub struct StaticPriceOracle {
+    prices: HashMap<(String, String), Decimal>,
+}
+
+impl StaticPriceOracle {
+    pub fn new(prices: HashMap<(String, String), Decimal>) -> Self {
+        Self { prices }
+    }
+
+    /// Parse a table like `BTC/USD=16696.16,ETH/USD=1212.5`
+    pub fn from_table(table: &str) -> Result<Self, PriceOracleError> {
+        let mut prices = HashMap::new();
+
+        for entry in table.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (pair, price) = entry.split_once('=').ok_or_else(|| {
+                PriceOracleError::Misconfigured(format!("'{entry}' is not like CRYPTO/FIAT=PRICE"))
+            })?;
+            let (crypto_symbol, fiat_symbol) = pair.trim().split_once('/').ok_or_else(|| {
+                PriceOracleError::Misconfigured(format!("'{pair}' is not like CRYPTO/FIAT"))
+            })?;
+
+            let price = parse_price(price.trim())
+                .map_err(|err| PriceOracleError::Misconfigured(err.to_string()))?;
+
+            prices.insert(
+                (
+                    crypto_symbol.trim().to_uppercase(),
+                    fiat_symbol.trim().to_uppercase(),
+                ),
+                price,
+            );
+        }
+
+        Ok(Self::new(prices))
+    }
+}
+
+#[async_trait]
+impl PriceOracle for StaticPriceOracle {
+    async fn price(
+        &self,
+        crypto_symbol: &str,
+        fiat_symbol: &str,
+    ) -> Result<Decimal, PriceOracleError> {
+        self.prices
+            .get(&(crypto_symbol.to_uppercase(), fiat_symbol.to_uppercase()))
+            .cloned()
+            .ok_or_else(|| PriceOracleError::PriceNotFound {
+                crypto_symbol: crypto_symbol.to_owned(),
+                fiat_symbol: fiat_symbol.to_owned(),
+            })
+    }
+
+    fn source(&self) -> String {
+        "static".to_owned()
+    }
+}
@@ -0,0 +1,155 @@
+use crate::impl_crud;
+use crate::security::refresh_token as refresh_token_security;
+use crate::{
+    entities::{prelude::*, refresh_token},
+    errors::InternalError,
+};
+use chrono::{Duration, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use sea_orm::{DbConn, DeleteResult};
+
+impl_crud!(RefreshToken, refresh_token, InternalError, i32);
+
+/// Store a new refresh token of the session, returning the token itself
+pub async fn issue<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    session_id: &str,
+    validity_duration_in_days: i64,
+) -> Result<String, InternalError> {
+    let token = refresh_token_security::generate_token();
+
+    refresh_token::ActiveModel {
+        user_id: Set(user_id),
+        session_id: Set(session_id.to_owned()),
+        token_hash: Set(refresh_token_security::hash_token(&token)),
+        created_at: Set(Utc::now().naive_utc()),
+        expires_at: Set(Utc::now().naive_utc() + Duration::days(validity_duration_in_days)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .map_err(Into::<InternalError>::into)?;
+
+    Ok(token)
+}
+
+pub async fn find_by_token(
+    db: &DbConn,
+    token: &str,
+) -> Result<Option<refresh_token::Model>, InternalError> {
+    Ok(RefreshToken::find()
+        .filter(refresh_token::Column::TokenHash.eq(refresh_token_security::hash_token(token)))
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Exchange a refresh token for a new one of the same session.
+///
+/// Each refresh token can be used once, presenting an already used token means it has
+/// leaked so the whole session is revoked.
+pub async fn rotate(
+    db: &DbConn,
+    token: &str,
+    validity_duration_in_days: i64,
+) -> Result<Option<(refresh_token::Model, String)>, InternalError> {
+    let refresh_token = match find_by_token(db, token).await? {
+        Some(refresh_token) => refresh_token,
+        None => return Ok(None),
+    };
+
+    if refresh_token.expires_at <= Utc::now().naive_utc() {
+        return Ok(None);
+    }
+
+    let txn = db.begin().await.map_err(Into::<InternalError>::into)?;
+
+    // only one of concurrent refreshes with the same token can revoke it
+    let revoked = RefreshToken::update_many()
+        .col_expr(
+            refresh_token::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refresh_token::Column::Id.eq(refresh_token.id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(&txn)
+        .await
+        .map_err(Into::<InternalError>::into)?
+        .rows_affected
+        == 1;
+
+    if !revoked {
+        txn.rollback().await.map_err(Into::<InternalError>::into)?;
+
+        log::warn!(
+            "Refresh token of session {} is reused, revoking the session",
+            refresh_token.session_id
+        );
+        revoke_session(db, &refresh_token.session_id).await?;
+
+        return Ok(None);
+    }
+
+    let new_token = issue(
+        &txn,
+        refresh_token.user_id,
+        &refresh_token.session_id,
+        validity_duration_in_days,
+    )
+    .await?;
+
+    txn.commit().await.map_err(Into::<InternalError>::into)?;
+
+    Ok(Some((refresh_token, new_token)))
+}
+
+/// A session is active while it has an unused and unexpired refresh token
+pub async fn is_session_active(db: &DbConn, session_id: &str) -> Result<bool, InternalError> {
+    let active_tokens = RefreshToken::find()
+        .filter(refresh_token::Column::SessionId.eq(session_id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .filter(refresh_token::Column::ExpiresAt.gt(Utc::now().naive_utc()))
+        .count(db)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+
+    Ok(active_tokens > 0)
+}
+
+pub async fn revoke_session(db: &DbConn, session_id: &str) -> Result<(), InternalError> {
+    RefreshToken::update_many()
+        .col_expr(
+            refresh_token::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refresh_token::Column::SessionId.eq(session_id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+
+    Ok(())
+}
+
+pub async fn revoke_all_by_user_id<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+) -> Result<(), InternalError> {
+    RefreshToken::update_many()
+        .col_expr(
+            refresh_token::Column::RevokedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refresh_token::Column::UserId.eq(user_id))
+        .filter(refresh_token::Column::RevokedAt.is_null())
+        .exec(db)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+
+    Ok(())
+}
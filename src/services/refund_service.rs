@@ -0,0 +1,266 @@
+use crate::entities::payment::{self, PaymentStatus};
+use crate::entities::refund::{RefundReason, RefundStatus};
+use crate::impl_crud;
+use crate::services::{chain_watcher, crypto_currency_service, network_service};
+use crate::{
+    entities::{prelude::*, refund},
+    errors::{ChainError, InternalError, NotFoundError, RefundError},
+};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbConn, DeleteResult, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+
+impl_crud!(Refund, refund, InternalError, i32);
+
+pub async fn find_all_by_payment_id<C: ConnectionTrait>(
+    db: &C,
+    payment_id: i32,
+) -> Result<Vec<refund::Model>, InternalError> {
+    Ok(Refund::find()
+        .filter(refund::Column::PaymentId.eq(payment_id))
+        .order_by_asc(refund::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Why crypto paid for the payment is owed back to the payer, if it is
+pub fn refund_reason(payment: &payment::Model) -> Option<RefundReason> {
+    match payment.status {
+        PaymentStatus::Waiting => None,
+        PaymentStatus::Expired | PaymentStatus::PartiallyPaid => Some(RefundReason::Underpaid),
+        PaymentStatus::Done
+        | PaymentStatus::Overpaid
+        | PaymentStatus::Verified
+        | PaymentStatus::Finished => Some(RefundReason::Overpaid),
+    }
+}
+
+/// Crypto amount which can still be refunded to the payer: everything paid for an expired or
+/// partially paid payment, or what the merchant isn't credited for of a paid one, minus refunds
+/// which haven't failed
+pub async fn refundable_amount<C: ConnectionTrait>(
+    db: &C,
+    payment: &payment::Model,
+) -> Result<Decimal, InternalError> {
+    let paid_crypto = payment.paid_crypto_amount.unwrap_or_default();
+
+    let owed_crypto = match refund_reason(payment) {
+        None => Decimal::ZERO,
+        Some(RefundReason::Underpaid) => paid_crypto,
+        Some(RefundReason::Overpaid) => {
+            let settled_crypto = payment
+                .settled_crypto_amount
+                .or(payment.crypto_amount)
+                .unwrap_or_default();
+
+            paid_crypto - settled_crypto
+        }
+    };
+
+    let refunded_crypto = find_all_by_payment_id(db, payment.id)
+        .await?
+        .iter()
+        .filter(|refund| refund.status != RefundStatus::Failed)
+        .map(|refund| refund.amount)
+        .sum::<Decimal>();
+
+    Ok((owed_crypto - refunded_crypto).max(Decimal::ZERO))
+}
+
+async fn validate_destination_address(
+    db: &DbConn,
+    payment_id: i32,
+    destination_address: &str,
+) -> Result<(), RefundError> {
+    let crypto_currency_id = Payment::find_by_id(payment_id)
+        .one(db)
+        .await
+        .map_err(InternalError::from)?
+        .and_then(|payment| payment.crypto_currency_id)
+        .ok_or(RefundError::NothingToRefund)?;
+
+    let crypto_currency = crypto_currency_service::find_by_id(db, crypto_currency_id)
+        .await?
+        .ok_or(NotFoundError::CryptoCurrencyNotFoundWithGivenId)?;
+    let network = network_service::find_by_id(db, crypto_currency.network_id)
+        .await?
+        .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+    let chain_watcher = chain_watcher::for_network(&network)?;
+    if !chain_watcher.validate_address(destination_address).await? {
+        return Err(ChainError::InvalidAddress(destination_address.to_owned()))?;
+    }
+
+    Ok(())
+}
+
+/// Create a requested refund of the payment, after checking it against what is still refundable
+/// and its destination address against the network the payment was paid on.
+///
+/// The row of the payment is locked while its refundable amount is checked, so concurrent
+/// requests can never refund more than it together.
+pub async fn request(
+    db: &DbConn,
+    payment_id: i32,
+    amount: Decimal,
+    destination_address: String,
+) -> Result<refund::Model, RefundError> {
+    if amount <= Decimal::ZERO {
+        return Err(RefundError::NonPositiveAmount);
+    }
+
+    // checked before locking the payment, as the node of the network may be asked
+    validate_destination_address(db, payment_id, &destination_address).await?;
+
+    let txn = db.begin().await.map_err(InternalError::from)?;
+
+    let payment = Payment::find_by_id(payment_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(InternalError::from)?
+        .ok_or(RefundError::NothingToRefund)?;
+
+    let reason = refund_reason(&payment).ok_or(RefundError::NothingToRefund)?;
+
+    let refundable_amount = refundable_amount(&txn, &payment).await?;
+    if refundable_amount.is_zero() {
+        return Err(RefundError::NothingToRefund);
+    }
+    if amount > refundable_amount {
+        return Err(RefundError::AmountExceedsRefundable(refundable_amount));
+    }
+
+    let refund = refund::ActiveModel {
+        payment_id: Set(payment.id),
+        // unwrap: a payment with something to refund has a crypto currency
+        crypto_currency_id: Set(payment.crypto_currency_id.unwrap()),
+        amount: Set(amount),
+        destination_address: Set(destination_address),
+        reason: Set(reason),
+        status: Set(RefundStatus::Requested),
+        created_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await
+    .map_err(InternalError::from)?;
+    txn.commit().await.map_err(InternalError::from)?;
+
+    Ok(refund)
+}
+
+/// Approve the requested refund by a conditional update, so that it is only approved while it
+/// is still requested. Returns whether it was approved.
+pub async fn approve(db: &DbConn, id: i32) -> Result<bool, InternalError> {
+    let approved = Refund::update_many()
+        .col_expr(refund::Column::Status, Expr::value(RefundStatus::Approved))
+        .col_expr(
+            refund::Column::ApprovedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refund::Column::Id.eq(id))
+        .filter(refund::Column::Status.eq(RefundStatus::Requested))
+        .exec(db)
+        .await?;
+
+    Ok(approved.rows_affected == 1)
+}
+
+/// Mark the approved refund as sent outside of the gateway, with its transaction. Returns
+/// whether it was still approved.
+pub async fn mark_sent(
+    db: &DbConn,
+    id: i32,
+    transaction_hash: String,
+) -> Result<bool, InternalError> {
+    let sent = Refund::update_many()
+        .col_expr(refund::Column::Status, Expr::value(RefundStatus::Sent))
+        .col_expr(
+            refund::Column::TransactionHash,
+            Expr::value(transaction_hash),
+        )
+        .col_expr(
+            refund::Column::ProcessedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refund::Column::Id.eq(id))
+        .filter(refund::Column::Status.eq(RefundStatus::Approved))
+        .exec(db)
+        .await?;
+
+    Ok(sent.rows_affected == 1)
+}
+
+/// Mark the approved refund as failed, which gives its amount back to what is refundable.
+/// Returns whether it was still approved, so a refund taken for sending is never failed.
+pub async fn mark_failed(db: &DbConn, id: i32) -> Result<bool, InternalError> {
+    let failed = Refund::update_many()
+        .col_expr(refund::Column::Status, Expr::value(RefundStatus::Failed))
+        .col_expr(
+            refund::Column::ProcessedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refund::Column::Id.eq(id))
+        .filter(refund::Column::Status.eq(RefundStatus::Approved))
+        .exec(db)
+        .await?;
+
+    Ok(failed.rows_affected == 1)
+}
+
+/// Take the approved refund for sending, by a conditional update to `SENT`, so that it is never
+/// sent twice. Returns whether it was taken.
+pub async fn claim_for_sending(db: &DbConn, id: i32) -> Result<bool, InternalError> {
+    let claimed = Refund::update_many()
+        .col_expr(refund::Column::Status, Expr::value(RefundStatus::Sent))
+        .col_expr(
+            refund::Column::ProcessedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(refund::Column::Id.eq(id))
+        .filter(refund::Column::Status.eq(RefundStatus::Approved))
+        .exec(db)
+        .await?;
+
+    Ok(claimed.rows_affected == 1)
+}
+
+pub async fn record_transaction_hash(
+    db: &DbConn,
+    id: i32,
+    transaction_hash: String,
+) -> Result<(), InternalError> {
+    Refund::update_many()
+        .col_expr(
+            refund::Column::TransactionHash,
+            Expr::value(transaction_hash),
+        )
+        .filter(refund::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Put a refund which failed to be sent back to `APPROVED`
+pub async fn release(db: &DbConn, id: i32) -> Result<(), InternalError> {
+    Refund::update_many()
+        .col_expr(refund::Column::Status, Expr::value(RefundStatus::Approved))
+        .col_expr(
+            refund::Column::ProcessedAt,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .filter(refund::Column::Id.eq(id))
+        .filter(refund::Column::Status.eq(RefundStatus::Sent))
+        .filter(refund::Column::TransactionHash.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
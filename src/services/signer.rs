@@ -0,0 +1,154 @@
+use crate::entities::wallet;
+use crate::errors::SignerError;
+use crate::security::keystore;
+use crate::services::web3_service;
+use async_trait::async_trait;
+use coins_bip32::xkeys::XPriv;
+use ethers::signers::{LocalWallet, Signer as _};
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Signature};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Signs the transactions of a wallet controlled by the gateway
+#[async_trait]
+pub trait Signer: Send + Sync {
+    fn address(&self) -> Address;
+
+    async fn sign_transaction(
+        &self,
+        transaction: &TypedTransaction,
+    ) -> Result<Signature, SignerError>;
+}
+
+/// Signer holding its private key in memory
+#[derive(Clone)]
+pub struct LocalSigner {
+    wallet: LocalWallet,
+}
+
+impl LocalSigner {
+    pub fn new(wallet: LocalWallet) -> Self {
+        LocalSigner { wallet }
+    }
+
+    pub fn from_bytes(private_key: &[u8]) -> Result<Self, SignerError> {
+        LocalWallet::from_bytes(private_key)
+            .map(LocalSigner::new)
+            .map_err(|_| SignerError::InvalidKey)
+    }
+
+    /// Parse a hex private key, with or without `0x` prefix
+    pub fn from_hex(private_key: &str) -> Result<Self, SignerError> {
+        let private_key = hex::decode(private_key.trim_start_matches("0x"))
+            .map_err(|_| SignerError::InvalidKey)?;
+
+        Self::from_bytes(&private_key)
+    }
+
+    pub fn private_key(&self) -> Vec<u8> {
+        self.wallet.signer().to_bytes().to_vec()
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    fn address(&self) -> Address {
+        self.wallet.address()
+    }
+
+    async fn sign_transaction(
+        &self,
+        transaction: &TypedTransaction,
+    ) -> Result<Signature, SignerError> {
+        Ok(ethers::signers::Signer::sign_transaction(&self.wallet, transaction).await?)
+    }
+}
+
+/// Keys of the wallets controlled by the gateway.
+///
+/// A wallet is controlled when its private key is imported, stored as a keystore encrypted with
+/// the master secret, or when it is derived from the networks' extended public key and the
+/// matching extended private key is configured.
+pub struct WalletKeys {
+    master_secret: Option<String>,
+    extended_private_key: Option<XPriv>,
+    /// Signers of the imported keys already decrypted, by wallet id, with the keystore they are
+    /// decrypted from
+    unlocked: Mutex<HashMap<i32, (String, LocalSigner)>>,
+}
+
+impl WalletKeys {
+    pub fn new(master_secret: Option<String>, extended_private_key: Option<XPriv>) -> Self {
+        WalletKeys {
+            master_secret,
+            extended_private_key,
+            unlocked: Mutex::default(),
+        }
+    }
+
+    pub async fn signer(&self, wallet: &wallet::Model) -> Result<Box<dyn Signer>, SignerError> {
+        Ok(Box::new(self.local_signer(wallet).await?))
+    }
+
+    /// Signer of the wallet, checked against the wallet's address so that a key of another
+    /// wallet is never used
+    pub async fn local_signer(&self, wallet: &wallet::Model) -> Result<LocalSigner, SignerError> {
+        let signer = match (
+            &wallet.encrypted_key,
+            &wallet.derivation_path,
+            &self.extended_private_key,
+        ) {
+            (Some(encrypted_key), _, _) => self.unlock(wallet.id, encrypted_key).await?,
+            (None, Some(derivation_path), Some(extended_private_key)) => LocalSigner::new(
+                web3_service::derive_signer(extended_private_key, derivation_path)
+                    .map_err(|err| SignerError::Misconfigured(err.to_string()))?,
+            ),
+            _ => return Err(SignerError::NoKey),
+        };
+
+        if format!("{:?}", signer.address()) != wallet.address.to_lowercase() {
+            return Err(SignerError::AddressMismatch);
+        }
+
+        Ok(signer)
+    }
+
+    /// Encrypt the private key of the signer with the master secret, to store it with its wallet
+    pub async fn seal(&self, signer: &LocalSigner) -> Result<String, SignerError> {
+        Ok(keystore::encrypt_blocking(
+            signer.private_key(),
+            format!("{:?}", signer.address()),
+            self.master_secret()?.to_owned(),
+        )
+        .await?)
+    }
+
+    /// Decrypt the imported key of the wallet, once per keystore as it is costly
+    async fn unlock(
+        &self,
+        wallet_id: i32,
+        encrypted_key: &str,
+    ) -> Result<LocalSigner, SignerError> {
+        if let Some((unlocked_key, signer)) = self.unlocked.lock().unwrap().get(&wallet_id) {
+            if unlocked_key == encrypted_key {
+                return Ok(signer.clone());
+            }
+        }
+
+        let private_key =
+            keystore::decrypt_blocking(encrypted_key.to_owned(), self.master_secret()?.to_owned())
+                .await?;
+        let signer = LocalSigner::from_bytes(&private_key)?;
+
+        self.unlocked
+            .lock()
+            .unwrap()
+            .insert(wallet_id, (encrypted_key.to_owned(), signer.clone()));
+
+        Ok(signer)
+    }
+
+    fn master_secret(&self) -> Result<&str, SignerError> {
+        self.master_secret.as_deref().ok_or(SignerError::Locked)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::entities::sweep::SweepStatus;
+use crate::impl_crud;
+use crate::models::dtos::SweepFilter;
+use crate::models::pagination::{Page, Pagination};
+use crate::{
+    entities::{prelude::*, sweep},
+    errors::InternalError,
+};
+use chrono::Utc;
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DbConn, DeleteResult, EntityTrait, QueryFilter, Set,
+};
+
+impl_crud!(Sweep, sweep, InternalError, i32);
+
+pub async fn find_filtered_page(
+    db: &DbConn,
+    filter: &SweepFilter,
+    pagination: &Pagination,
+) -> Result<Page<sweep::Model>, InternalError> {
+    let condition = Condition::all()
+        .add_option(
+            filter
+                .network_id
+                .map(|network_id| sweep::Column::NetworkId.eq(network_id)),
+        )
+        .add_option(
+            filter
+                .wallet_id
+                .map(|wallet_id| sweep::Column::WalletId.eq(wallet_id)),
+        )
+        .add_option(
+            filter
+                .status
+                .clone()
+                .map(|status| sweep::Column::Status.eq(status)),
+        );
+
+    find_page(db, condition, sweep::Column::CreatedAt, pagination).await
+}
+
+/// Sweeps of the network which are sent but not mined yet
+pub async fn find_all_pending_by_network_id(
+    db: &DbConn,
+    network_id: i32,
+) -> Result<Vec<sweep::Model>, InternalError> {
+    Ok(Sweep::find()
+        .filter(sweep::Column::NetworkId.eq(network_id))
+        .filter(sweep::Column::Status.eq(SweepStatus::Pending))
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Record a transaction just sent by the sweeper
+pub async fn record(
+    db: &DbConn,
+    mut sweep: sweep::ActiveModel,
+) -> Result<sweep::Model, InternalError> {
+    sweep.status = Set(SweepStatus::Pending);
+    sweep.created_at = Set(Utc::now().naive_utc());
+
+    Ok(sweep
+        .insert(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Record how a pending sweep ended, with the fee it paid when it was mined
+pub async fn settle(
+    db: &DbConn,
+    id: i32,
+    status: SweepStatus,
+    fee: Option<Decimal>,
+) -> Result<(), InternalError> {
+    Sweep::update_many()
+        .col_expr(sweep::Column::Status, Expr::value(status))
+        .col_expr(sweep::Column::Fee, Expr::value(fee))
+        .col_expr(
+            sweep::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(sweep::Column::Id.eq(id))
+        .filter(sweep::Column::Status.eq(SweepStatus::Pending))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,541 @@
+use super::{crypto_currency_service, network_service, sweep_service, wallet_service};
+use crate::entities::network::{self, NetworkKind};
+use crate::entities::sweep::{self, SweepKind, SweepStatus};
+use crate::entities::{crypto_currency, wallet};
+use crate::errors::{ChainError, InternalError};
+use crate::services::signer::{Signer, WalletKeys};
+use crate::services::web3_service::{
+    self, NonceLocks, TransactionSender, NATIVE_CURRENCY_DECIMALS,
+};
+use actix_web::web::Data;
+use anyhow::Context;
+use chrono::Duration;
+use ethers::prelude::{Address, Bytes, Middleware, TransactionReceipt, TxHash, U256, U64};
+use sea_orm::prelude::Decimal;
+use sea_orm::{DbConn, Set};
+use std::collections::HashSet;
+
+/// How often released wallets are swept
+const SWEEP_INTERVAL_IN_SECONDS: i64 = 60;
+
+/// Gas used by a plain transfer of the native currency
+const NATIVE_TRANSFER_GAS: u64 = 21_000;
+
+/// Sweep released wallets right away, then periodically
+pub fn spawn_sweeper(
+    wallet_keys: Data<WalletKeys>,
+    gas_signer: Data<dyn Signer>,
+    nonce_locks: Data<NonceLocks>,
+    db: Data<DbConn>,
+) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) =
+                sweep_networks(&wallet_keys, gas_signer.get_ref(), &nonce_locks, &db).await
+            {
+                log::error!("Failed to sweep wallets: {err}");
+            }
+
+            tokio::time::sleep(
+                Duration::seconds(SWEEP_INTERVAL_IN_SECONDS)
+                    .to_std()
+                    .unwrap(),
+            )
+            .await;
+        }
+    });
+}
+
+/// Move what released wallets received to the treasury address of their network.
+///
+/// Every run sends at most one transaction per wallet and waits for it to be confirmed before
+/// the next one, so nonces never collide and balances are read once they are final. Tokens are
+/// swept first, topping the wallet up with gas from the gas signer when it cannot pay for the
+/// transfer itself, then the native currency is swept. The sweep request of the wallet is
+/// cleared once nothing worth moving is left.
+pub async fn sweep_networks(
+    wallet_keys: &WalletKeys,
+    gas_signer: &dyn Signer,
+    nonce_locks: &NonceLocks,
+    db: &DbConn,
+) -> Result<(), InternalError> {
+    for network in network_service::find_all(db).await? {
+        let Some(treasury_address) = network.treasury_address.clone() else {
+            continue;
+        };
+        if network.kind != NetworkKind::Evm {
+            continue;
+        }
+
+        let network_id = network.id;
+        let sweeper = Sweeper {
+            network: &network,
+            wallet_keys,
+            gas_signer,
+            nonce_locks,
+        };
+        if let Err(err) = sweeper.sweep_network(&treasury_address, db).await {
+            log::error!("Failed to sweep wallets of network with id {network_id}: {err:#}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Sweeps the wallets of an EVM network
+struct Sweeper<'a> {
+    network: &'a network::Model,
+    wallet_keys: &'a WalletKeys,
+    gas_signer: &'a dyn Signer,
+    nonce_locks: &'a NonceLocks,
+}
+
+impl<'a> Sweeper<'a> {
+    async fn sweep_network(
+        &self,
+        treasury_address: &str,
+        db: &DbConn,
+    ) -> Result<(), anyhow::Error> {
+        let sender = TransactionSender::connect(self.network, self.nonce_locks).await?;
+        let treasury = web3_service::parse_address(treasury_address)?;
+        let in_flight = self.settle_pending(&sender, db).await?;
+
+        let crypto_currencies =
+            crypto_currency_service::find_all_by_network_id(db, self.network.id).await?;
+
+        for wallet in wallet_service::find_all_sweepable(db, self.network.id).await? {
+            if in_flight.contains(&wallet.id) {
+                continue;
+            }
+
+            let wallet_id = wallet.id;
+            if let Err(err) = self
+                .sweep_wallet(&sender, treasury, &wallet, &crypto_currencies, db)
+                .await
+            {
+                log::error!("Failed to sweep wallet with id {wallet_id}: {err:#}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Settle the sweeps which are confirmed or dropped, returning the wallets which still have
+    /// one in flight
+    async fn settle_pending(
+        &self,
+        sender: &TransactionSender,
+        db: &DbConn,
+    ) -> Result<HashSet<i32>, anyhow::Error> {
+        let client = sender.client();
+        let head = client.get_block_number().await?.as_u64();
+        let mut in_flight = HashSet::new();
+
+        for sweep in sweep_service::find_all_pending_by_network_id(db, self.network.id).await? {
+            let hash = sweep
+                .hash
+                .parse::<TxHash>()
+                .with_context(|| format!("'{}' is not a transaction hash", sweep.hash))?;
+
+            let receipt = client.get_transaction_receipt(hash).await?;
+            match receipt {
+                Some(TransactionReceipt {
+                    block_number: Some(block_number),
+                    status,
+                    gas_used,
+                    effective_gas_price,
+                    ..
+                }) => {
+                    let confirmations = head.saturating_sub(block_number.as_u64()) + 1;
+                    if confirmations < self.network.required_confirmations as u64 {
+                        in_flight.insert(sweep.wallet_id);
+                        continue;
+                    }
+
+                    let status = if status == Some(U64::one()) {
+                        SweepStatus::Confirmed
+                    } else {
+                        SweepStatus::Failed
+                    };
+                    let fee = gas_used
+                        .zip(effective_gas_price)
+                        .map(|(gas_used, gas_price)| native_amount(gas_used * gas_price))
+                        .transpose()?;
+
+                    sweep_service::settle(db, sweep.id, status, fee).await?;
+                }
+                _ if client.get_transaction(hash).await?.is_some() => {
+                    in_flight.insert(sweep.wallet_id);
+                }
+                _ => {
+                    // dropped from the mempool, the wallet is swept again on the next run
+                    log::warn!("Sweep transaction {} was dropped", sweep.hash);
+                    sweep_service::settle(db, sweep.id, SweepStatus::Failed, None).await?;
+                }
+            }
+        }
+
+        Ok(in_flight)
+    }
+
+    async fn sweep_wallet(
+        &self,
+        sender: &TransactionSender,
+        treasury: Address,
+        wallet: &wallet::Model,
+        crypto_currencies: &[crypto_currency::Model],
+        db: &DbConn,
+    ) -> Result<(), anyhow::Error> {
+        let signer = self.wallet_keys.signer(wallet).await?;
+        let fees = sender.estimate_fees().await?;
+        let native_balance = sender.balance(signer.address()).await?;
+
+        let native_currency = crypto_currencies
+            .iter()
+            .find(|crypto_currency| crypto_currency.contract_address.is_none());
+
+        for token in crypto_currencies {
+            let Some(ref contract_address) = token.contract_address else {
+                continue;
+            };
+
+            let balance = sender
+                .token_balance(
+                    web3_service::parse_address(contract_address)?,
+                    signer.address(),
+                )
+                .await?;
+            if balance.is_zero() {
+                continue;
+            }
+
+            // converted before sending, so a sent sweep is always recorded
+            let amount = web3_service::convert_from_base_units(balance, token.decimals as u32)?;
+            let mut transfer = sender.transfer(token, signer.address(), treasury, balance, fees)?;
+            let gas = sender.estimate_gas(&transfer).await?;
+            transfer.set_gas(gas);
+
+            let gas_cost = gas * fees.0;
+            if native_balance < gas_cost {
+                // the token is swept on a later run, once the top up is confirmed
+                let top_up = gas_cost - native_balance;
+                let mut transaction = sender.transaction(
+                    self.gas_signer.address(),
+                    signer.address(),
+                    top_up,
+                    Bytes::default(),
+                    fees,
+                );
+                transaction.set_gas(NATIVE_TRANSFER_GAS);
+                let top_up_amount = native_amount(top_up)?;
+
+                let hash = sender.send(self.gas_signer, transaction).await?;
+                self.record(
+                    hash,
+                    self.sweep(wallet, SweepKind::GasTopUp, native_currency),
+                    (self.gas_signer.address(), signer.address()),
+                    top_up_amount,
+                    db,
+                )
+                .await?;
+                return Ok(());
+            }
+
+            let hash = sender.send(signer.as_ref(), transfer).await?;
+            self.record(
+                hash,
+                self.sweep(wallet, SweepKind::Sweep, Some(token)),
+                (signer.address(), treasury),
+                amount,
+                db,
+            )
+            .await?;
+            return Ok(());
+        }
+
+        // what the actual fee leaves below the maximum fee stays in the wallet
+        let gas_cost = U256::from(NATIVE_TRANSFER_GAS) * fees.0;
+        if native_balance > gas_cost {
+            let amount = native_balance - gas_cost;
+            let mut transaction =
+                sender.transaction(signer.address(), treasury, amount, Bytes::default(), fees);
+            transaction.set_gas(NATIVE_TRANSFER_GAS);
+            let amount = native_amount(amount)?;
+
+            let hash = sender.send(signer.as_ref(), transaction).await?;
+            self.record(
+                hash,
+                self.sweep(wallet, SweepKind::Sweep, native_currency),
+                (signer.address(), treasury),
+                amount,
+                db,
+            )
+            .await?;
+            return Ok(());
+        }
+
+        // unwrap: sweepable wallets have a sweep request
+        wallet_service::clear_sweep_request(db, wallet.id, wallet.sweep_requested_at.unwrap())
+            .await?;
+        log::info!("Wallet with id {} is swept", wallet.id);
+
+        Ok(())
+    }
+
+    fn sweep(
+        &self,
+        wallet: &wallet::Model,
+        kind: SweepKind,
+        crypto_currency: Option<&crypto_currency::Model>,
+    ) -> sweep::ActiveModel {
+        sweep::ActiveModel {
+            network_id: Set(self.network.id),
+            wallet_id: Set(wallet.id),
+            crypto_currency_id: Set(crypto_currency.map(|crypto_currency| crypto_currency.id)),
+            kind: Set(kind),
+            ..Default::default()
+        }
+    }
+
+    /// Record a sent transaction as a pending sweep
+    async fn record(
+        &self,
+        hash: TxHash,
+        mut sweep: sweep::ActiveModel,
+        (from, to): (Address, Address),
+        amount: Decimal,
+        db: &DbConn,
+    ) -> Result<sweep::Model, InternalError> {
+        log::info!("Sent {:?} transaction {hash:?}", sweep.kind.as_ref());
+
+        sweep.hash = Set(format!("{hash:?}"));
+        sweep.from_address = Set(format!("{from:?}"));
+        sweep.to_address = Set(format!("{to:?}"));
+        sweep.amount = Set(amount);
+
+        sweep_service::record(db, sweep).await
+    }
+}
+
+fn native_amount(amount: U256) -> Result<Decimal, ChainError> {
+    web3_service::convert_from_base_units(amount, NATIVE_CURRENCY_DECIMALS as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::prelude::Sweep;
+    use crate::entities::wallet::WalletStatus;
+    use crate::services::signer::LocalSigner;
+    use crate::test_utils;
+    use chrono::Utc;
+    use ethers::prelude::{LocalWallet, H256};
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+    use serde_json::Value;
+
+    /// First of the accounts anvil funds at startup
+    const FUNDED_PRIVATE_KEY: &str =
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    /// Runtime code of a minimal token: `balanceOf`, and `transfer` emitting `Transfer`, with
+    /// the balance of an owner stored at the slot of its address
+    const TOKEN_CODE: &str = "60003560e01c806370a0823114610021578063a9059cbb1461002e575b600080fd5b\
+        6004355460005260206000f35b335460243580821061001c57808203335560043580548201815581600052\
+        337fddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef60206000a360016000\
+        5260206000f3";
+
+    const MASTER_SECRET: &str = "test-master-secret";
+
+    /// Anvil node started as the README says
+    fn node_url() -> String {
+        std::env::var("TEST_EVM_URL").expect("TEST_EVM_URL should be set")
+    }
+
+    fn random_signer() -> LocalSigner {
+        LocalSigner::new(LocalWallet::new(&mut ethers::core::rand::thread_rng()))
+    }
+
+    async fn mine(sender: &TransactionSender) {
+        let _: Value = sender
+            .client()
+            .request("evm_mine", None::<()>)
+            .await
+            .unwrap();
+    }
+
+    /// A released wallet of the network holding an imported key
+    async fn sweepable_wallet(
+        network: &network::Model,
+        signer: &LocalSigner,
+        wallet_keys: &WalletKeys,
+        db: &DbConn,
+    ) -> wallet::Model {
+        wallet_service::create(
+            db,
+            wallet::ActiveModel {
+                address: Set(format!("{:?}", signer.address())),
+                network_id: Set(network.id),
+                status: Set(WalletStatus::Free),
+                sweep_requested_at: Set(Some(Utc::now().naive_utc())),
+                encrypted_key: Set(Some(wallet_keys.seal(signer).await.unwrap())),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap()
+    }
+
+    /// Run the sweeper, mining a block after every run, until the wallet's sweep request is
+    /// cleared, returning the sweeps of the wallet
+    async fn sweep_until_swept(
+        wallet_keys: &WalletKeys,
+        gas_signer: &dyn Signer,
+        nonce_locks: &NonceLocks,
+        sender: &TransactionSender,
+        wallet: &wallet::Model,
+        db: &DbConn,
+    ) -> Vec<sweep::Model> {
+        for _ in 0..10 {
+            sweep_networks(wallet_keys, gas_signer, nonce_locks, db)
+                .await
+                .unwrap();
+            mine(sender).await;
+
+            let wallet = wallet_service::find_by_id(db, wallet.id)
+                .await
+                .unwrap()
+                .unwrap();
+            if wallet.sweep_requested_at.is_none() {
+                return Sweep::find()
+                    .filter(sweep::Column::WalletId.eq(wallet.id))
+                    .order_by_asc(sweep::Column::Id)
+                    .all(db)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        panic!("wallet with id {} is still not swept", wallet.id);
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL and TEST_EVM_URL"]
+    async fn sweeps_native_currency_and_tokens_topping_up_gas() {
+        let db = test_utils::setup_db().await;
+        let treasury = random_signer().address();
+
+        let mut network = network::ActiveModel::from(
+            test_utils::create_network(&db, NetworkKind::Evm, &node_url()).await,
+        );
+        network.treasury_address = Set(Some(format!("{treasury:?}")));
+        let network = network.update(&db).await.unwrap();
+
+        let nonce_locks = NonceLocks::default();
+        let sender = TransactionSender::connect(&network, &nonce_locks)
+            .await
+            .unwrap();
+        let gas_signer = LocalSigner::from_hex(FUNDED_PRIVATE_KEY).unwrap();
+        let wallet_keys = WalletKeys::new(Some(MASTER_SECRET.to_owned()), None);
+
+        let token_address = random_signer().address();
+        let _: Value = sender
+            .client()
+            .request("anvil_setCode", (token_address, format!("0x{TOKEN_CODE}")))
+            .await
+            .unwrap();
+        let native_currency = test_utils::create_crypto_currency(&db, &network, 18, None).await;
+        let token = test_utils::create_crypto_currency(
+            &db,
+            &network,
+            6,
+            Some(format!("{token_address:?}")),
+        )
+        .await;
+
+        // a wallet paid in the native currency
+        let native_signer = random_signer();
+        let native_wallet = sweepable_wallet(&network, &native_signer, &wallet_keys, &db).await;
+        let paid = U256::exp10(18);
+        let fees = sender.estimate_fees().await.unwrap();
+        let mut funding = sender.transaction(
+            gas_signer.address(),
+            native_signer.address(),
+            paid,
+            Bytes::default(),
+            fees,
+        );
+        funding.set_gas(NATIVE_TRANSFER_GAS);
+        sender.send(&gas_signer, funding).await.unwrap();
+        mine(&sender).await;
+
+        let sweeps = sweep_until_swept(
+            &wallet_keys,
+            &gas_signer,
+            &nonce_locks,
+            &sender,
+            &native_wallet,
+            &db,
+        )
+        .await;
+        assert_eq!(sweeps.len(), 1);
+        assert_eq!(sweeps[0].kind, SweepKind::Sweep);
+        assert_eq!(sweeps[0].status, SweepStatus::Confirmed);
+        assert_eq!(sweeps[0].crypto_currency_id, Some(native_currency.id));
+        assert_eq!(sweeps[0].to_address, format!("{treasury:?}"));
+        assert!(sweeps[0].fee.is_some());
+
+        let swept = sender.balance(treasury).await.unwrap();
+        assert!(!swept.is_zero() && swept < paid);
+        assert_eq!(native_amount(swept).unwrap(), sweeps[0].amount);
+
+        // a wallet paid in the token, without anything to pay the gas of the transfer
+        let token_signer = random_signer();
+        let token_wallet = sweepable_wallet(&network, &token_signer, &wallet_keys, &db).await;
+        let paid_tokens = U256::from(250_000_000u64);
+        let mut balance = [0u8; 32];
+        paid_tokens.to_big_endian(&mut balance);
+        let _: Value = sender
+            .client()
+            .request(
+                "anvil_setStorageAt",
+                (
+                    token_address,
+                    H256::from(token_signer.address()),
+                    H256::from(balance),
+                ),
+            )
+            .await
+            .unwrap();
+
+        let sweeps = sweep_until_swept(
+            &wallet_keys,
+            &gas_signer,
+            &nonce_locks,
+            &sender,
+            &token_wallet,
+            &db,
+        )
+        .await;
+        assert_eq!(sweeps[0].kind, SweepKind::GasTopUp);
+        assert_eq!(
+            sweeps[0].from_address,
+            format!("{:?}", gas_signer.address())
+        );
+        assert_eq!(sweeps[1].kind, SweepKind::Sweep);
+        assert_eq!(sweeps[1].crypto_currency_id, Some(token.id));
+        assert_eq!(sweeps[1].amount, Decimal::new(250, 0));
+        assert!(sweeps
+            .iter()
+            .all(|sweep| sweep.status == SweepStatus::Confirmed));
+
+        assert_eq!(
+            sender.token_balance(token_address, treasury).await.unwrap(),
+            paid_tokens
+        );
+        assert!(sender
+            .token_balance(token_address, token_signer.address())
+            .await
+            .unwrap()
+            .is_zero());
+    }
+}
@@ -1,9 +1,12 @@
 use crate::impl_crud;
+use crate::security::hash;
+use crate::security::webhook;
+use crate::services::refresh_token_service;
 use crate::{
     entities::{prelude::*, user},
     errors::InternalError,
 };
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set, TransactionTrait};
 use sea_orm::{DbConn, DeleteResult};
 
 impl_crud!(User, user, InternalError, i32);
@@ -18,3 +21,71 @@ pub async fn find_by_username(
         .await
         .map_err(Into::<InternalError>::into)?)
 }
+
+pub async fn get_or_create_webhook_secret(
+    db: &DbConn,
+    user: user::Model,
+) -> Result<String, InternalError> {
+    match user.webhook_secret.clone() {
+        Some(webhook_secret) => Ok(webhook_secret),
+        None => rotate_webhook_secret(db, user).await,
+    }
+}
+
+pub async fn rotate_webhook_secret(
+    db: &DbConn,
+    user: user::Model,
+) -> Result<String, InternalError> {
+    let webhook_secret = webhook::generate_secret();
+
+    let mut user = user::ActiveModel::from(user);
+    user.webhook_secret = Set(Some(webhook_secret.clone()));
+    update(db, user).await?;
+
+    Ok(webhook_secret)
+}
+
+pub async fn change_password(
+    db: &DbConn,
+    user: user::Model,
+    new_password: &String,
+) -> Result<user::Model, InternalError> {
+    let password_hash = hash::hash_password(new_password);
+
+    let mut user = user::ActiveModel::from(user);
+    user.password_hash = Set(password_hash);
+
+    invalidate_sessions(db, user).await
+}
+
+pub async fn set_disabled(
+    db: &DbConn,
+    user: user::Model,
+    disabled: bool,
+) -> Result<user::Model, InternalError> {
+    let mut user = user::ActiveModel::from(user);
+    user.disabled = Set(disabled);
+
+    invalidate_sessions(db, user).await
+}
+
+/// Save the user while revoking all of their refresh tokens, and the access tokens issued
+/// before by bumping their token version
+async fn invalidate_sessions(
+    db: &DbConn,
+    mut user: user::ActiveModel,
+) -> Result<user::Model, InternalError> {
+    user.token_version = Set(user.token_version.clone().unwrap() + 1);
+
+    let txn = db.begin().await.map_err(Into::<InternalError>::into)?;
+
+    let user = user
+        .update(&txn)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+    refresh_token_service::revoke_all_by_user_id(&txn, user.id).await?;
+
+    txn.commit().await.map_err(Into::<InternalError>::into)?;
+
+    Ok(user)
+}
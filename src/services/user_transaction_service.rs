@@ -1,17 +1,21 @@
 use crate::entities::user_transaction::UserTransactionType;
 use crate::impl_crud;
+use crate::models::dtos::{UserTransactionFilter, UserTransactionSortField};
+use crate::models::pagination::{Page, Pagination};
 use crate::{
     entities::{prelude::*, user_transaction},
     errors::InternalError,
 };
 use sea_orm::prelude::Decimal;
-use sea_orm::{ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbConn, DeleteResult, EntityTrait, QueryFilter,
+};
 use std::collections::{HashMap, HashSet};
 
 impl_crud!(UserTransaction, user_transaction, InternalError, i32);
 
-pub async fn find_all_by_user_id(
-    db: &DbConn,
+pub async fn find_all_by_user_id<C: ConnectionTrait>(
+    db: &C,
     user_id: i32,
 ) -> Result<Vec<user_transaction::Model>, InternalError> {
     Ok(UserTransaction::find()
@@ -21,9 +25,48 @@ pub async fn find_all_by_user_id(
         .map_err(Into::<InternalError>::into)?)
 }
 
-pub async fn get_user_balance(
+pub async fn find_filtered_page_by_user_id(
     db: &DbConn,
     user_id: i32,
+    filter: &UserTransactionFilter,
+    pagination: &Pagination,
+) -> Result<Page<user_transaction::Model>, InternalError> {
+    let condition =
+        Condition::all()
+            .add(user_transaction::Column::UserId.eq(user_id))
+            .add_option(
+                filter
+                    .typ
+                    .clone()
+                    .map(|typ| user_transaction::Column::Typ.eq(typ)),
+            )
+            .add_option(filter.fiat_currency_id.map(|fiat_currency_id| {
+                user_transaction::Column::FiatCurrencyId.eq(fiat_currency_id)
+            }))
+            .add_option(
+                filter
+                    .created_from
+                    .map(|created_from| user_transaction::Column::CreatedAt.gte(created_from)),
+            )
+            .add_option(
+                filter
+                    .created_to
+                    .map(|created_to| user_transaction::Column::CreatedAt.lt(created_to)),
+            );
+
+    let sort_by = match filter.sort_by {
+        UserTransactionSortField::CreatedAt => user_transaction::Column::CreatedAt,
+        UserTransactionSortField::Amount => user_transaction::Column::Amount,
+    };
+
+    find_page(db, condition, sort_by, pagination).await
+}
+
+/// Balance of the user in every fiat currency they have transactions in, including what
+/// withdrawals in progress hold, see `withdrawal_service::get_available_balance`
+pub async fn get_user_balance<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
 ) -> Result<HashMap<i32, Decimal>, InternalError> {
     let user_transactions = find_all_by_user_id(db, user_id).await?;
     let user_fiat_currencies_id = user_transactions
@@ -1,36 +1,310 @@
+use crate::entities::network::NetworkKind;
 use crate::entities::wallet::WalletStatus;
 use crate::impl_crud;
+use crate::services::web3_service;
 use crate::{
-    entities::{prelude::*, wallet},
-    errors::{InternalError, PaymentError},
+    entities::{network, prelude::*, wallet},
+    errors::{InternalError, NotFoundError, PaymentError},
 };
-use anyhow::Result;
-use sea_orm::{ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter, Set};
+use anyhow::{Context, Result};
+use chrono::{NaiveDateTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbBackend, DbConn, DeleteResult, EntityTrait,
+    PaginatorTrait, QueryFilter, Set, Statement,
+};
+
+/// Free wallets left in a pool under which every checkout warns that it runs low
+const LOW_POOL_SIZE: u64 = 5;
 
 impl_crud!(Wallet, wallet, InternalError, i32);
 
-pub async fn reserve(db: &DbConn, network_id: i32) -> Result<wallet::Model> {
+/// Reserve a wallet of the network for the payment.
+///
+/// EVM networks with an extended public key get a freshly derived address for every payment,
+/// others hand out wallets of their pre-created pool.
+pub async fn reserve(
+    db: &DbConn,
+    network: &network::Model,
+    payment_id: i32,
+) -> Result<wallet::Model> {
+    match (&network.kind, &network.extended_public_key) {
+        (NetworkKind::Evm, Some(extended_public_key)) => {
+            derive(db, network.id, extended_public_key, payment_id).await
+        }
+        _ => reserve_from_pool(db, network.id, payment_id).await,
+    }
+}
+
+/// Take a free wallet of the pool.
+///
+/// The wallet is picked and taken by one update, skipping the wallets locked by concurrent
+/// checkouts, so they never end up with the same wallet nor fail while free wallets remain.
+async fn reserve_from_pool(db: &DbConn, network_id: i32, payment_id: i32) -> Result<wallet::Model> {
     let wallet = Wallet::find()
-        .filter(wallet::Column::Status.eq(WalletStatus::Free))
-        .filter(wallet::Column::NetworkId.eq(network_id))
+        .from_raw_sql(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"UPDATE "wallet"
+                SET "status" = $1, "payment_id" = $2, "reserved_at" = $3
+                WHERE "id" = (
+                    SELECT "id" FROM "wallet"
+                    WHERE "status" = $4 AND "network_id" = $5
+                    ORDER BY "id"
+                    LIMIT 1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *"#,
+            vec![
+                WalletStatus::Busy.into(),
+                payment_id.into(),
+                Utc::now().naive_utc().into(),
+                WalletStatus::Free.into(),
+                network_id.into(),
+            ],
+        ))
         .one(db)
         .await
         .map_err(Into::<InternalError>::into)?
         .ok_or(PaymentError::NotFreeWallet)?;
 
-    let mut wallet = wallet::ActiveModel::from(wallet);
-    wallet.status = Set(WalletStatus::Busy);
+    let free_wallets = Wallet::find()
+        .filter(wallet::Column::NetworkId.eq(network_id))
+        .filter(wallet::Column::Status.eq(WalletStatus::Free))
+        .count(db)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+    if free_wallets < LOW_POOL_SIZE {
+        log::warn!(
+            "Only {free_wallets} free wallets are left in the pool of network with id {network_id}"
+        );
+    }
 
-    update(db, wallet).await.map_err(Into::into)
+    Ok(wallet)
 }
 
-pub async fn free(db: &DbConn, id: i32) -> Result<wallet::Model> {
-    let wallet = find_by_id(db, id)
-        .await?
-        .ok_or(PaymentError::NotFreeWallet)?;
+/// Derive a new wallet at the next unused index of the network's extended public key
+async fn derive(
+    db: &DbConn,
+    network_id: i32,
+    extended_public_key: &str,
+    payment_id: i32,
+) -> Result<wallet::Model> {
+    let index = claim_derivation_index(db, network_id).await?;
+
+    let (address, derivation_path) =
+        web3_service::derive_address(extended_public_key, index as u32)
+            .with_context(|| format!("Failed to derive wallet at index {index}"))?;
+
+    let wallet = wallet::ActiveModel {
+        address: Set(address),
+        network_id: Set(network_id),
+        status: Set(WalletStatus::Busy),
+        payment_id: Set(Some(payment_id)),
+        reserved_at: Set(Some(Utc::now().naive_utc())),
+        derivation_path: Set(Some(derivation_path)),
+        ..Default::default()
+    };
+
+    Ok(create(db, wallet).await?)
+}
+
+/// Move the network's derivation index forward by one update, so every index is handed out
+/// once even with concurrent checkouts
+async fn claim_derivation_index(db: &DbConn, network_id: i32) -> Result<i32> {
+    let network = Network::find()
+        .from_raw_sql(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            r#"UPDATE "network"
+                SET "next_derivation_index" = "next_derivation_index" + 1
+                WHERE "id" = $1
+                RETURNING *"#,
+            vec![network_id.into()],
+        ))
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?
+        .ok_or(NotFoundError::NetworkNotFoundWithGivenId)?;
+
+    // the index claimed is the one before the update
+    Ok(network.next_derivation_index - 1)
+}
+
+/// Release the wallet, if it is still held by the payment, and ask for what it received to be
+/// swept to the treasury of its network.
+///
+/// EVM pool wallets go back to the pool. Derived wallets are retired so their address is never
+/// given to another payer, and so are bitcoin wallets, whose outputs left unspent would count
+/// for the next payment of the address, until they are recycled.
+pub async fn free<C: ConnectionTrait>(
+    db: &C,
+    id: i32,
+    payment_id: i32,
+) -> Result<(), InternalError> {
+    let wallet = match Wallet::find_by_id(id).one(db).await? {
+        Some(wallet) if wallet.payment_id == Some(payment_id) => wallet,
+        _ => return Ok(()),
+    };
+
+    let network = Network::find_by_id(wallet.network_id).one(db).await?;
+    let single_use = wallet.derivation_path.is_some()
+        || network.map_or(false, |network| network.kind == NetworkKind::Bitcoin);
+    let status = if single_use {
+        WalletStatus::Retired
+    } else {
+        WalletStatus::Free
+    };
+
+    Wallet::update_many()
+        .col_expr(wallet::Column::Status, Expr::value(status))
+        .col_expr(wallet::Column::PaymentId, Expr::value(Option::<i32>::None))
+        .col_expr(
+            wallet::Column::ReservedAt,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .col_expr(
+            wallet::Column::SweepRequestedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(wallet::Column::Id.eq(id))
+        .filter(wallet::Column::PaymentId.eq(payment_id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Released wallets of the network waiting to be swept, among those the gateway controls
+pub async fn find_all_sweepable(
+    db: &DbConn,
+    network_id: i32,
+) -> Result<Vec<wallet::Model>, InternalError> {
+    Ok(Wallet::find()
+        .filter(wallet::Column::NetworkId.eq(network_id))
+        .filter(wallet::Column::Status.ne(WalletStatus::Busy))
+        .filter(wallet::Column::SweepRequestedAt.is_not_null())
+        .filter(
+            Condition::any()
+                .add(wallet::Column::DerivationPath.is_not_null())
+                .add(wallet::Column::EncryptedKey.is_not_null()),
+        )
+        .all(db)
+        .await?)
+}
+
+/// Retired pool wallets of the network released before the given time, which may go back to
+/// the pool
+pub async fn find_all_recyclable(
+    db: &DbConn,
+    network_id: i32,
+    released_before: NaiveDateTime,
+) -> Result<Vec<wallet::Model>, InternalError> {
+    Ok(Wallet::find()
+        .filter(wallet::Column::NetworkId.eq(network_id))
+        .filter(wallet::Column::Status.eq(WalletStatus::Retired))
+        .filter(wallet::Column::DerivationPath.is_null())
+        .filter(wallet::Column::SweepRequestedAt.lt(released_before))
+        .all(db)
+        .await?)
+}
+
+/// Put the retired wallet back in the pool, unless it was released again since it was read
+pub async fn recycle(
+    db: &DbConn,
+    id: i32,
+    sweep_requested_at: NaiveDateTime,
+) -> Result<bool, InternalError> {
+    let recycled = Wallet::update_many()
+        .col_expr(wallet::Column::Status, Expr::value(WalletStatus::Free))
+        .col_expr(
+            wallet::Column::SweepRequestedAt,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .filter(wallet::Column::Id.eq(id))
+        .filter(wallet::Column::Status.eq(WalletStatus::Retired))
+        .filter(wallet::Column::SweepRequestedAt.eq(sweep_requested_at))
+        .exec(db)
+        .await?;
+
+    Ok(recycled.rows_affected == 1)
+}
+
+/// Mark the wallet as swept, unless it was released again since the sweep was requested
+pub async fn clear_sweep_request(
+    db: &DbConn,
+    id: i32,
+    sweep_requested_at: NaiveDateTime,
+) -> Result<(), InternalError> {
+    Wallet::update_many()
+        .col_expr(
+            wallet::Column::SweepRequestedAt,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .filter(wallet::Column::Id.eq(id))
+        .filter(wallet::Column::SweepRequestedAt.eq(sweep_requested_at))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Store the private key of the wallet, encrypted, or forget it
+pub async fn set_encrypted_key(
+    db: &DbConn,
+    id: i32,
+    encrypted_key: Option<String>,
+) -> Result<(), InternalError> {
+    Wallet::update_many()
+        .col_expr(wallet::Column::EncryptedKey, Expr::value(encrypted_key))
+        .filter(wallet::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use std::collections::HashSet;
+
+    const POOL_SIZE: i32 = 20;
+
+    #[actix_web::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn concurrent_checkouts_take_distinct_wallets() {
+        let db = test_utils::setup_db().await;
+        let network =
+            test_utils::create_network(&db, NetworkKind::Evm, "http://127.0.0.1:8545").await;
+
+        for index in 0..POOL_SIZE {
+            let wallet = wallet::ActiveModel {
+                address: Set(format!("pool-{}-{index}", network.id)),
+                network_id: Set(network.id),
+                status: Set(WalletStatus::Free),
+                ..Default::default()
+            };
+            create(&db, wallet).await.unwrap();
+        }
+
+        let checkouts = (0..POOL_SIZE)
+            .map(|payment_id| reserve_from_pool(&db, network.id, payment_id))
+            .collect::<Vec<_>>();
+        let wallets = futures_util::future::join_all(checkouts)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .expect("every checkout should get a wallet while free ones remain");
 
-    let mut wallet = wallet::ActiveModel::from(wallet);
-    wallet.status = Set(WalletStatus::Free);
+        let wallet_ids = wallets
+            .iter()
+            .map(|wallet| wallet.id)
+            .collect::<HashSet<_>>();
+        assert_eq!(wallet_ids.len(), POOL_SIZE as usize);
+        assert!(wallets
+            .iter()
+            .all(|wallet| wallet.status == WalletStatus::Busy));
 
-    update(db, wallet).await.map_err(Into::into)
+        assert!(reserve_from_pool(&db, network.id, POOL_SIZE).await.is_err());
+    }
 }
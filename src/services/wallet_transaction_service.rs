@@ -1,8 +1,144 @@
+use crate::entities::wallet_transaction::WalletTransactionStatus;
 use crate::impl_crud;
 use crate::{
     entities::{prelude::*, wallet_transaction},
     errors::InternalError,
 };
-use sea_orm::{DbConn, DeleteResult};
+use chrono::Utc;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
 
 impl_crud!(WalletTransaction, wallet_transaction, InternalError, i32);
+
+pub async fn find_all_by_payment_id(
+    db: &DbConn,
+    payment_id: i32,
+) -> Result<Vec<wallet_transaction::Model>, InternalError> {
+    Ok(WalletTransaction::find()
+        .filter(wallet_transaction::Column::PaymentId.eq(payment_id))
+        .order_by_asc(wallet_transaction::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Transactions recorded for the payment on the wallet in the crypto currency, leaving out
+/// those of the wallets and currencies it used before switching to them
+pub async fn find_all_by_payment_id_wallet_id_and_crypto_currency_id(
+    db: &DbConn,
+    payment_id: i32,
+    wallet_id: i32,
+    crypto_currency_id: i32,
+) -> Result<Vec<wallet_transaction::Model>, InternalError> {
+    Ok(WalletTransaction::find()
+        .filter(wallet_transaction::Column::PaymentId.eq(payment_id))
+        .filter(wallet_transaction::Column::WalletId.eq(wallet_id))
+        .filter(wallet_transaction::Column::CryptoCurrencyId.eq(crypto_currency_id))
+        .order_by_asc(wallet_transaction::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Find what the transaction transferred to the wallet
+pub async fn find_by_network_id_hash_and_wallet_id(
+    db: &DbConn,
+    network_id: i32,
+    hash: &str,
+    wallet_id: i32,
+) -> Result<Option<wallet_transaction::Model>, InternalError> {
+    Ok(WalletTransaction::find()
+        .filter(wallet_transaction::Column::NetworkId.eq(network_id))
+        .filter(wallet_transaction::Column::Hash.eq(hash))
+        .filter(wallet_transaction::Column::WalletId.eq(wallet_id))
+        .one(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+/// Insert the transaction, or refresh what was seen of it when it is already recorded for its
+/// wallet. The confirmation progress of a recorded transaction is kept, and so is the payment
+/// it is attributed to: a transaction recorded for another payment is returned untouched.
+pub async fn record(
+    db: &DbConn,
+    mut wallet_transaction: wallet_transaction::ActiveModel,
+) -> Result<wallet_transaction::Model, InternalError> {
+    let existing = find_by_network_id_hash_and_wallet_id(
+        db,
+        *wallet_transaction.network_id.as_ref(),
+        wallet_transaction.hash.as_ref(),
+        *wallet_transaction.wallet_id.as_ref(),
+    )
+    .await?;
+
+    let now = Utc::now().naive_utc();
+    match existing {
+        Some(existing) if existing.payment_id != *wallet_transaction.payment_id.as_ref() => {
+            Ok(existing)
+        }
+        Some(existing) => {
+            wallet_transaction.id = Set(existing.id);
+            wallet_transaction.wallet_id = ActiveValue::NotSet;
+            wallet_transaction.payment_id = ActiveValue::NotSet;
+            wallet_transaction.updated_at = Set(Some(now));
+            // a transaction seen again before being mined has no block yet
+            if existing.block_number.is_some() {
+                wallet_transaction.block_number = Set(existing.block_number);
+                wallet_transaction.block_hash = Set(existing.block_hash);
+            }
+
+            Ok(wallet_transaction
+                .update(db)
+                .await
+                .map_err(Into::<InternalError>::into)?)
+        }
+        None => {
+            wallet_transaction.created_at = Set(now);
+
+            Ok(wallet_transaction
+                .insert(db)
+                .await
+                .map_err(Into::<InternalError>::into)?)
+        }
+    }
+}
+
+/// Record the block a transaction is mined in and how far it is confirmed
+pub async fn update_progress(
+    db: &DbConn,
+    network_id: i32,
+    hash: &str,
+    block_number: Option<i64>,
+    block_hash: Option<String>,
+    confirmations: i32,
+    status: WalletTransactionStatus,
+) -> Result<(), InternalError> {
+    WalletTransaction::update_many()
+        .col_expr(
+            wallet_transaction::Column::BlockNumber,
+            Expr::value(block_number),
+        )
+        .col_expr(
+            wallet_transaction::Column::BlockHash,
+            Expr::value(block_hash),
+        )
+        .col_expr(
+            wallet_transaction::Column::Confirmations,
+            Expr::value(confirmations),
+        )
+        .col_expr(wallet_transaction::Column::Status, Expr::value(status))
+        .col_expr(
+            wallet_transaction::Column::UpdatedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(wallet_transaction::Column::NetworkId.eq(network_id))
+        .filter(wallet_transaction::Column::Hash.eq(hash))
+        .exec(db)
+        .await
+        .map_err(Into::<InternalError>::into)?;
+
+    Ok(())
+}
@@ -1,87 +1,841 @@
-use crate::entities::{wallet, wallet_transaction};
-use crate::models::ws::WsOutputMessage;
-use crate::services::wallet_transaction_service;
+use crate::entities::wallet_transaction::{self, WalletTransactionStatus};
+use crate::entities::{crypto_currency, network, wallet};
+use crate::errors::ChainError;
+use crate::models::payment_event::{PaymentEvent, TransactionConfirmation};
+use crate::services::chain_watcher::{
+    self, ChainTransfer, TransferStatus, WatchOutcome, WatchTarget,
+};
+use crate::services::price_oracle;
+use crate::services::signer::Signer;
+use crate::services::{payment_monitor::PaymentEventPublisher, wallet_transaction_service};
 use actix_web::web::Data;
 use chrono::{NaiveDateTime, Utc};
-use ethers::{prelude::*, types::U256};
+use coins_bip32::{
+    enc::{MainnetEncoder, XKeyEncoder},
+    xkeys::{Parent, XPriv, XPub},
+    Bip32Error,
+};
+use ethers::abi::{self, Token};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::{
+    prelude::*,
+    types::U256,
+    utils::{id, keccak256},
+};
+use futures_util::stream;
 use sea_orm::prelude::Decimal;
 use sea_orm::{DbConn, Set};
-use std::str::FromStr;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
 
+/// Confirmations required on networks created without an explicit value
+pub const DEFAULT_REQUIRED_CONFIRMATIONS: i32 = 12;
+
+/// Decimals of ether and of crypto currencies created without an explicit value
+pub const NATIVE_CURRENCY_DECIMALS: i32 = 18;
+
+const TRANSFER_EVENT_SIGNATURE: &str = "Transfer(address,address,uint256)";
+const BALANCE_OF_SIGNATURE: &str = "balanceOf(address)";
+const TRANSFER_SIGNATURE: &str = "transfer(address,uint256)";
+
+enum ChainEvent {
+    PendingTransaction(TxHash),
+    TransferLog(Log),
+    NewBlock(Block<TxHash>),
+}
+
+/// Wallet of a payment being watched, and where its transactions are recorded
+struct WatchedWallet<'a> {
+    network: &'a network::Model,
+    crypto_currency: &'a crypto_currency::Model,
+    wallet: &'a wallet::Model,
+    payment_id: i32,
+    db: &'a DbConn,
+}
+
+/// Watch the wallet until transfers worth the target amount reach the network's required number
+/// of confirmations, or until the payment expires, returning the confirmed amount. Every
+/// transfer is recorded as a wallet transaction of the payment, along with its progress.
+///
+/// Native transfers are tracked from the moment they are seen in the mempool (or in a block, for
+/// transactions that never went through it), token transfers from their `Transfer` logs. Both are
+/// re-checked against their receipts on every new block, so dropped, replaced, reverted or
+/// re-orged transactions never count as paid.
 pub async fn subscribe_transactions(
-    websocket_url: &str,
+    network: &network::Model,
+    crypto_currency: &crypto_currency::Model,
     wallet: &wallet::Model,
-    payment_crypto: Decimal,
-    expiration_date: NaiveDateTime,
-    session: &mut actix_ws::Session,
+    target: &WatchTarget,
+    events: &PaymentEventPublisher,
     db: Data<DbConn>,
-) -> bool {
-    let client = Provider::<Ws>::connect(websocket_url).await.unwrap();
+) -> Result<WatchOutcome, ChainError> {
+    let client = Provider::<Ws>::connect(&network.websocket_address_url).await?;
     let client = Arc::new(client);
 
-    let wallet_address = wallet.address.parse::<Address>().unwrap();
+    let wallet_address = parse_address(&wallet.address)?;
+    let token_address = crypto_currency
+        .contract_address
+        .as_deref()
+        .map(parse_address)
+        .transpose()?;
+    let required_confirmations = network.required_confirmations as u64;
+
+    let watched = WatchedWallet {
+        network,
+        crypto_currency,
+        wallet,
+        payment_id: target.payment_id,
+        db: &db,
+    };
 
-    let payment_crypto = convert_eth_to_wei(payment_crypto);
+    let decimals = crypto_currency.decimals as u32;
+    // the target may be a fraction of a base unit short of the amount, after tolerances
+    let payment_crypto = convert_to_base_units(
+        price_oracle::round_up_dp(target.crypto_amount, decimals),
+        decimals,
+    )?;
     log::info!(
         "Payment with amount of {payment_crypto} for wallet with address {wallet_address} started"
     );
 
-    let mut transactions_stream = client.subscribe_pending_txs().await.unwrap();
+    // subscriptions are unsubscribed when dropped
+    let incoming = match token_address {
+        Some(token_address) => {
+            let filter = Filter::new()
+                .address(token_address)
+                .event(TRANSFER_EVENT_SIGNATURE)
+                .topic2(H256::from(wallet_address));
+
+            client
+                .subscribe_logs(&filter)
+                .await?
+                .map(ChainEvent::TransferLog)
+                .boxed()
+        }
+        None => client
+            .subscribe_pending_txs()
+            .await?
+            .map(ChainEvent::PendingTransaction)
+            .boxed(),
+    };
+    let blocks = client.subscribe_blocks().await?;
+    let mut chain_events = stream::select(incoming, blocks.map(ChainEvent::NewBlock));
 
-    let mut payment_successful = false;
+    // value sent to the wallet by every transaction, by hash
+    let mut tracked_transactions = HashMap::<TxHash, U256>::new();
+    // token transfer logs already counted, by transaction hash and log index
+    let mut seen_transfer_logs = HashSet::<(TxHash, U256)>::new();
+    // transactions whose final state is recorded
+    let mut settled_transactions = HashSet::<TxHash>::new();
 
-    let mut paid_crypto = U256::from_str("0").unwrap();
-    while let Some(transaction_hash) = transactions_stream.next().await {
-        if Utc::now().naive_utc() > expiration_date {
+    // transactions recorded before this watch, by a previous watcher or by the reconciler
+    let mut recorded_transactions = HashSet::<TxHash>::new();
+    match wallet_transaction_service::find_all_by_payment_id_wallet_id_and_crypto_currency_id(
+        &db,
+        target.payment_id,
+        wallet.id,
+        crypto_currency.id,
+    )
+    .await
+    {
+        Ok(wallet_transactions) => {
+            for wallet_transaction in wallet_transactions {
+                let (Ok(hash), Some(Ok(value))) = (
+                    wallet_transaction.hash.parse::<TxHash>(),
+                    wallet_transaction
+                        .value
+                        .map(|value| convert_to_base_units(value, decimals)),
+                ) else {
+                    continue;
+                };
+                if wallet_transaction.status == WalletTransactionStatus::Failed {
+                    continue;
+                }
+
+                tracked_transactions.insert(hash, value);
+                recorded_transactions.insert(hash);
+            }
+        }
+        Err(err) => log::error!("Failed to load recorded transactions of the wallet: {err}"),
+    }
+
+    let mut confirmed_crypto = U256::zero();
+
+    loop {
+        // the node dropped the connection
+        let Some(event) = chain_events.next().await else {
+            return Err(ChainError::SubscriptionClosed);
+        };
+
+        if Utc::now().naive_utc() > target.expiration_date {
             log::info!("Payment is expired, unsubscribing...");
             break;
         }
-        if let Ok(Some(transaction)) = client.get_transaction(transaction_hash).await {
-            if transaction.to == Some(wallet_address) {
-                log::info!("New transaction received for wallet address {wallet_address} : {transaction:#?}");
-
-                // broadcast new transaction into socket
-                session
-                    .text(WsOutputMessage::TransactionReceived(transaction.clone()).into_str())
-                    .await
-                    .unwrap();
-
-                // store new transaction into db
-                let wallet_transaction = wallet_transaction::ActiveModel {
-                    hash: Set(transaction_hash.to_string()), //TODO: use full format
-                    wallet_id: Set(wallet.id),
-                    created_at: Set(Utc::now().naive_utc()),
-                    ..Default::default()
+
+        let quote_lapsed = target
+            .quote_valid_until
+            .map(|valid_until| Utc::now().naive_utc() > valid_until)
+            .unwrap_or(false);
+        if quote_lapsed && tracked_transactions.is_empty() {
+            log::info!("Quote of payment lapsed before any transfer, unsubscribing...");
+            return Ok(WatchOutcome::QuoteLapsed);
+        }
+
+        match event {
+            ChainEvent::PendingTransaction(transaction_hash) => {
+                if tracked_transactions.contains_key(&transaction_hash) {
+                    continue;
+                }
+
+                if let Ok(Some(transaction)) = client.get_transaction(transaction_hash).await {
+                    if transaction.to == Some(wallet_address) {
+                        track_transaction(&transaction, &watched, events).await;
+                        tracked_transactions.insert(transaction.hash, transaction.value);
+                    }
+                }
+            }
+
+            ChainEvent::TransferLog(log) => {
+                let (Some(transaction_hash), Some(log_index)) =
+                    (log.transaction_hash, log.log_index)
+                else {
+                    continue;
                 };
-                wallet_transaction_service::create(&db, wallet_transaction)
-                    .await
-                    .unwrap();
 
-                //TODO: move broadcast and db logic to outside the function
+                // recorded with the value of all of its transfers
+                if recorded_transactions.contains(&transaction_hash) {
+                    continue;
+                }
 
-                paid_crypto += transaction.value;
+                if log.removed == Some(true)
+                    || !seen_transfer_logs.insert((transaction_hash, log_index))
+                {
+                    continue;
+                }
 
-                log::info!("Crypto paid amount: {paid_crypto}");
+                let value = tracked_transactions.entry(transaction_hash).or_default();
+                // a transaction may transfer to the wallet several times
+                *value += U256::from_big_endian(&log.data);
+                track_transfer_log(&log, *value, &watched, events).await;
             }
 
-            if paid_crypto >= payment_crypto {
-                payment_successful = true;
-                break;
+            ChainEvent::NewBlock(block) => {
+                let (Some(block_number), Some(block_hash)) = (block.number, block.hash) else {
+                    continue;
+                };
+
+                // pick up native transfers which were never broadcast through the mempool
+                if token_address.is_none() {
+                    if let Ok(Some(block)) = client.get_block_with_txs(block_hash).await {
+                        for transaction in block.transactions {
+                            if transaction.to == Some(wallet_address)
+                                && !tracked_transactions.contains_key(&transaction.hash)
+                            {
+                                track_transaction(&transaction, &watched, events).await;
+                                tracked_transactions.insert(transaction.hash, transaction.value);
+                            }
+                        }
+                    }
+                }
+
+                confirmed_crypto = U256::zero();
+                let mut failed_transactions = Vec::new();
+
+                for (&transaction_hash, &value) in tracked_transactions.iter() {
+                    // not mined yet, or dropped from the mempool
+                    let Ok(Some(receipt)) = client.get_transaction_receipt(transaction_hash).await
+                    else {
+                        continue;
+                    };
+                    let Some(mined_at) = receipt.block_number else {
+                        continue;
+                    };
+
+                    let confirmations = block_number.saturating_sub(mined_at).as_u64() + 1;
+
+                    if receipt.status != Some(U64::one()) {
+                        log::info!("Transaction {transaction_hash:?} is reverted");
+
+                        update_wallet_transaction(
+                            &watched,
+                            &receipt,
+                            confirmations,
+                            WalletTransactionStatus::Failed,
+                        )
+                        .await;
+
+                        failed_transactions.push(transaction_hash);
+                        events.publish(PaymentEvent::TransactionFailed(receipt));
+                        continue;
+                    }
+
+                    if !settled_transactions.contains(&transaction_hash) {
+                        let status = if confirmations >= required_confirmations {
+                            settled_transactions.insert(transaction_hash);
+                            WalletTransactionStatus::Confirmed
+                        } else {
+                            WalletTransactionStatus::Pending
+                        };
+
+                        update_wallet_transaction(&watched, &receipt, confirmations, status).await;
+                    }
+
+                    if confirmations <= required_confirmations {
+                        events.publish(PaymentEvent::TransactionConfirmation(
+                            TransactionConfirmation {
+                                hash: format!("{transaction_hash:?}"),
+                                block_number: mined_at,
+                                confirmations,
+                                required_confirmations,
+                            },
+                        ));
+                    }
+
+                    if confirmations >= required_confirmations {
+                        confirmed_crypto += value;
+                    }
+                }
+
+                for transaction_hash in failed_transactions {
+                    tracked_transactions.remove(&transaction_hash);
+                }
+
+                log::info!("Crypto confirmed amount: {confirmed_crypto}");
+
+                if confirmed_crypto >= payment_crypto {
+                    break;
+                }
             }
         }
     }
 
-    transactions_stream.unsubscribe().await.unwrap();
+    // failing leaves the payment to be watched again, never settles it as unpaid
+    Ok(WatchOutcome::Ended(convert_from_base_units(
+        confirmed_crypto,
+        decimals,
+    )?))
+}
+
+async fn track_transaction(
+    transaction: &Transaction,
+    watched: &WatchedWallet<'_>,
+    events: &PaymentEventPublisher,
+) {
+    log::info!(
+        "New transaction received for wallet address {} : {transaction:#?}",
+        watched.wallet.address
+    );
+
+    // broadcast new transaction to the payment watchers
+    events.publish(PaymentEvent::TransactionReceived(transaction.clone()));
 
-    payment_successful
+    store_wallet_transaction(
+        watched,
+        transaction.hash,
+        transaction.from,
+        transaction.value,
+        transaction.block_number.zip(transaction.block_hash),
+    )
+    .await;
 }
 
-fn convert_eth_to_wei(mut decimal: Decimal) -> U256 {
-    decimal = decimal * Decimal::from_str_exact("1_000_000_000_000_000_000").unwrap();
+/// `value` is everything the transaction of the log transferred to the wallet so far
+async fn track_transfer_log(
+    log: &Log,
+    value: U256,
+    watched: &WatchedWallet<'_>,
+    events: &PaymentEventPublisher,
+) {
+    log::info!(
+        "New token transfer received for wallet address {} : {log:#?}",
+        watched.wallet.address
+    );
+
+    // broadcast new transfer to the payment watchers
+    events.publish(PaymentEvent::TokenTransferReceived(log.clone()));
+
+    // `Transfer(from, to, value)` has the sender as first indexed topic
+    let from = log
+        .topics
+        .get(1)
+        .map(|topic| Address::from(*topic))
+        .unwrap_or_default();
+
+    store_wallet_transaction(
+        watched,
+        // unwrap: logs without transaction hash are skipped before
+        log.transaction_hash.unwrap(),
+        from,
+        value,
+        log.block_number.zip(log.block_hash),
+    )
+    .await;
+}
+
+async fn store_wallet_transaction(
+    watched: &WatchedWallet<'_>,
+    transaction_hash: TxHash,
+    from: Address,
+    value: U256,
+    block: Option<(U64, H256)>,
+) {
+    let value = match convert_from_base_units(value, watched.crypto_currency.decimals as u32) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            log::error!("Failed to convert the value of transaction {transaction_hash:?}: {err}");
+            None
+        }
+    };
+
+    let wallet_transaction = wallet_transaction::ActiveModel {
+        hash: Set(format!("{transaction_hash:?}")),
+        wallet_id: Set(watched.wallet.id),
+        network_id: Set(watched.network.id),
+        payment_id: Set(Some(watched.payment_id)),
+        crypto_currency_id: Set(Some(watched.crypto_currency.id)),
+        from_address: Set(Some(format!("{from:?}"))),
+        value: Set(value),
+        block_number: Set(block.map(|(block_number, _)| block_number.as_u64() as i64)),
+        block_hash: Set(block.map(|(_, block_hash)| format!("{block_hash:?}"))),
+        ..Default::default()
+    };
+
+    if let Err(err) = wallet_transaction_service::record(watched.db, wallet_transaction).await {
+        log::error!("Failed to record transaction {transaction_hash:?}: {err}");
+    }
+}
+
+async fn update_wallet_transaction(
+    watched: &WatchedWallet<'_>,
+    receipt: &TransactionReceipt,
+    confirmations: u64,
+    status: WalletTransactionStatus,
+) {
+    let res = wallet_transaction_service::update_progress(
+        watched.db,
+        watched.network.id,
+        &format!("{:?}", receipt.transaction_hash),
+        receipt
+            .block_number
+            .map(|block_number| block_number.as_u64() as i64),
+        receipt
+            .block_hash
+            .map(|block_hash| format!("{block_hash:?}")),
+        confirmations as i32,
+        status,
+    )
+    .await;
+
+    if let Err(err) = res {
+        log::error!(
+            "Failed to update transaction {:?}: {err}",
+            receipt.transaction_hash
+        );
+    }
+}
+
+/// Reads past blocks of an EVM network through its HTTP endpoint
+pub struct ChainScanner {
+    client: Provider<Http>,
+}
+
+impl ChainScanner {
+    pub fn new(network: &network::Model) -> Result<Self, ChainError> {
+        let client = Provider::<Http>::try_from(network.http_address_url.as_str())
+            .map_err(|err| ChainError::Misconfigured(err.to_string()))?;
+
+        Ok(ChainScanner { client })
+    }
+
+    pub async fn head(&self) -> Result<u64, ChainError> {
+        Ok(self.client.get_block_number().await?.as_u64())
+    }
+
+    pub async fn block_time(&self, block_number: u64) -> Result<Option<NaiveDateTime>, ChainError> {
+        Ok(self
+            .client
+            .get_block(block_number)
+            .await?
+            .and_then(|block| {
+                NaiveDateTime::from_timestamp_opt(block.timestamp.as_u64() as i64, 0)
+            }))
+    }
+
+    pub async fn transfer_status(&self, hash: &str) -> Result<Option<TransferStatus>, ChainError> {
+        let hash = hash.parse::<TxHash>().map_err(|_| {
+            ChainError::Misconfigured(format!("'{hash}' is not a transaction hash"))
+        })?;
+
+        let Some(receipt) = self.client.get_transaction_receipt(hash).await? else {
+            return Ok(None);
+        };
+        let Some(mined_at) = receipt.block_number else {
+            return Ok(None);
+        };
+        let head = self.head().await?;
+
+        Ok(Some(TransferStatus {
+            block_number: Some(mined_at.as_u64()),
+            block_hash: receipt
+                .block_hash
+                .map(|block_hash| format!("{block_hash:?}")),
+            confirmations: head.saturating_sub(mined_at.as_u64()) + 1,
+            succeeded: receipt.status == Some(U64::one()),
+        }))
+    }
+
+    /// Transfers of the crypto currency to the wallet mined in blocks `from..=to`
+    pub async fn find_transfers(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        wallet_address: &str,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<ChainTransfer>, ChainError> {
+        let wallet_address = parse_address(wallet_address)?;
+        let decimals = crypto_currency.decimals as u32;
 
-    let decimal_str = decimal.to_string();
-    let decimal_str = decimal_str.split(".").next().unwrap();
+        // transfers by transaction hash, with the block they are mined in
+        let mut found = HashMap::<TxHash, (Address, U256, U64, H256)>::new();
+
+        match crypto_currency.contract_address {
+            Some(ref contract_address) => {
+                let filter = Filter::new()
+                    .address(parse_address(contract_address)?)
+                    .event(TRANSFER_EVENT_SIGNATURE)
+                    .topic2(H256::from(wallet_address))
+                    .from_block(from)
+                    .to_block(to);
+
+                for log in self.client.get_logs(&filter).await? {
+                    let (Some(hash), Some(block_number), Some(block_hash)) =
+                        (log.transaction_hash, log.block_number, log.block_hash)
+                    else {
+                        continue;
+                    };
+                    if log.removed == Some(true) {
+                        continue;
+                    }
+
+                    let from = log
+                        .topics
+                        .get(1)
+                        .map(|topic| Address::from(*topic))
+                        .unwrap_or_default();
+                    let (_, value, _, _) =
+                        found
+                            .entry(hash)
+                            .or_insert((from, U256::zero(), block_number, block_hash));
+                    *value += U256::from_big_endian(&log.data);
+                }
+            }
+            None => {
+                for block_number in from..=to {
+                    let Some(block) = self.client.get_block_with_txs(block_number).await? else {
+                        continue;
+                    };
+
+                    for transaction in block.transactions {
+                        if transaction.to != Some(wallet_address) {
+                            continue;
+                        }
+                        if let (Some(block_number), Some(block_hash)) =
+                            (transaction.block_number, transaction.block_hash)
+                        {
+                            found.insert(
+                                transaction.hash,
+                                (
+                                    transaction.from,
+                                    transaction.value,
+                                    block_number,
+                                    block_hash,
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut transfers = Vec::new();
+        let mut block_times = HashMap::<u64, Option<NaiveDateTime>>::new();
+
+        for (hash, (from, value, block_number, block_hash)) in found {
+            let block_number = block_number.as_u64();
+            let Some(receipt) = self.client.get_transaction_receipt(hash).await? else {
+                continue;
+            };
+
+            let mined_at = match block_times.get(&block_number) {
+                Some(mined_at) => *mined_at,
+                None => {
+                    let mined_at = self.block_time(block_number).await?;
+                    block_times.insert(block_number, mined_at);
+                    mined_at
+                }
+            };
+            let Some(mined_at) = mined_at else {
+                continue;
+            };
+
+            transfers.push(ChainTransfer {
+                hash: format!("{hash:?}"),
+                from: Some(format!("{from:?}")),
+                value: convert_from_base_units(value, decimals)?,
+                block_number,
+                block_hash: format!("{block_hash:?}"),
+                mined_at,
+                succeeded: receipt.status == Some(U64::one()),
+            });
+        }
+
+        Ok(transfers)
+    }
+}
+
+/// Locks of the addresses the gateway sends from, shared by the sweeper and the handlers so that
+/// two transactions of an address are never given the same nonce
+#[derive(Clone, Default)]
+pub struct NonceLocks {
+    locks: Arc<Mutex<HashMap<Address, Arc<AsyncMutex<()>>>>>,
+}
+
+impl NonceLocks {
+    /// Wait for the transaction being sent from the address, if any, to be sent
+    async fn lock(&self, address: Address) -> OwnedMutexGuard<()> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(address)
+            .or_default()
+            .clone();
+
+        lock.lock_owned().await
+    }
+}
+
+/// Sends transactions signed by the gateway to an EVM network, over its HTTP endpoint
+pub struct TransactionSender {
+    client: Provider<Http>,
+    chain_id: u64,
+    nonce_locks: NonceLocks,
+}
+
+impl TransactionSender {
+    pub async fn connect(
+        network: &network::Model,
+        nonce_locks: &NonceLocks,
+    ) -> Result<Self, ChainError> {
+        let client = Provider::<Http>::try_from(network.http_address_url.as_str())
+            .map_err(|err| ChainError::Misconfigured(err.to_string()))?;
+        let chain_id = client.get_chainid().await?.as_u64();
+
+        Ok(TransactionSender {
+            client,
+            chain_id,
+            nonce_locks: nonce_locks.clone(),
+        })
+    }
+
+    pub fn client(&self) -> &Provider<Http> {
+        &self.client
+    }
+
+    /// Max fee and max priority fee per gas, as estimated by the node from recent blocks
+    pub async fn estimate_fees(&self) -> Result<(U256, U256), ChainError> {
+        Ok(self.client.estimate_eip1559_fees(None).await?)
+    }
+
+    pub async fn balance(&self, address: Address) -> Result<U256, ChainError> {
+        Ok(self.client.get_balance(address, None).await?)
+    }
+
+    pub async fn token_balance(&self, token: Address, owner: Address) -> Result<U256, ChainError> {
+        let call = TypedTransaction::Eip1559(
+            Eip1559TransactionRequest::new()
+                .to(token)
+                .data(encode_call(BALANCE_OF_SIGNATURE, &[Token::Address(owner)])),
+        );
+        let balance = self.client.call(&call, None).await?;
+
+        Ok(U256::from_big_endian(&balance))
+    }
+
+    /// EIP-1559 transaction, without gas limit nor nonce
+    pub fn transaction(
+        &self,
+        from: Address,
+        to: Address,
+        value: U256,
+        data: Bytes,
+        (max_fee_per_gas, max_priority_fee_per_gas): (U256, U256),
+    ) -> TypedTransaction {
+        TypedTransaction::Eip1559(
+            Eip1559TransactionRequest::new()
+                .from(from)
+                .to(to)
+                .value(value)
+                .data(data)
+                .max_fee_per_gas(max_fee_per_gas)
+                .max_priority_fee_per_gas(max_priority_fee_per_gas)
+                .chain_id(self.chain_id),
+        )
+    }
+
+    /// Transfer of `amount` base units of the crypto currency, through its token contract for
+    /// tokens
+    pub fn transfer(
+        &self,
+        crypto_currency: &crypto_currency::Model,
+        from: Address,
+        to: Address,
+        amount: U256,
+        fees: (U256, U256),
+    ) -> Result<TypedTransaction, ChainError> {
+        Ok(match crypto_currency.contract_address {
+            Some(ref contract_address) => self.transaction(
+                from,
+                parse_address(contract_address)?,
+                U256::zero(),
+                encode_call(
+                    TRANSFER_SIGNATURE,
+                    &[Token::Address(to), Token::Uint(amount)],
+                ),
+                fees,
+            ),
+            None => self.transaction(from, to, amount, Bytes::default(), fees),
+        })
+    }
+
+    /// Gas the transaction needs. It is estimated without its fees, which nodes would otherwise
+    /// check the sender can pay, so that a wallet waiting for a gas top up can be estimated.
+    pub async fn estimate_gas(&self, transaction: &TypedTransaction) -> Result<U256, ChainError> {
+        let mut transaction = transaction.clone();
+        if let TypedTransaction::Eip1559(ref mut request) = transaction {
+            request.max_fee_per_gas = None;
+            request.max_priority_fee_per_gas = None;
+        }
+
+        Ok(self.client.estimate_gas(&transaction, None).await?)
+    }
+
+    /// Sign the transaction at the next nonce of the signer and send it, returning its hash. The
+    /// address is locked until the transaction is in the node's pool, which the next nonce then
+    /// counts.
+    pub async fn send(
+        &self,
+        signer: &dyn Signer,
+        mut transaction: TypedTransaction,
+    ) -> Result<TxHash, ChainError> {
+        let _nonce_lock = self.nonce_locks.lock(signer.address()).await;
+        let nonce = self
+            .client
+            .get_transaction_count(signer.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        transaction.set_nonce(nonce);
+
+        let signature = signer.sign_transaction(&transaction).await?;
+        let pending = self
+            .client
+            .send_raw_transaction(transaction.rlp_signed(&signature))
+            .await?;
+
+        Ok(pending.tx_hash())
+    }
+}
+
+/// Send `amount` of the crypto currency to the address, signed by the signer, at the fees
+/// estimated by the node
+pub async fn send_transfer(
+    network: &network::Model,
+    crypto_currency: &crypto_currency::Model,
+    signer: &dyn Signer,
+    to: &str,
+    amount: Decimal,
+    nonce_locks: &NonceLocks,
+) -> Result<TxHash, ChainError> {
+    let sender = TransactionSender::connect(network, nonce_locks).await?;
+    let fees = sender.estimate_fees().await?;
+
+    let mut transaction = sender.transfer(
+        crypto_currency,
+        signer.address(),
+        parse_address(to)?,
+        convert_to_base_units(amount, crypto_currency.decimals as u32)?,
+        fees,
+    )?;
+    let gas = sender.estimate_gas(&transaction).await?;
+    transaction.set_gas(gas);
+
+    sender.send(signer, transaction).await
+}
+
+fn encode_call(signature: &str, args: &[Token]) -> Bytes {
+    let mut data = id(signature).to_vec();
+    data.extend(abi::encode(args));
+
+    data.into()
+}
+
+pub fn parse_address(address: &str) -> Result<Address, ChainError> {
+    address
+        .parse::<Address>()
+        .map_err(|_| ChainError::InvalidAddress(address.to_owned()))
+}
+
+pub fn parse_extended_public_key(extended_public_key: &str) -> Result<XPub, Bip32Error> {
+    MainnetEncoder::xpub_from_base58(extended_public_key)
+}
+
+/// Derive the receiving address at `index` of an account level (`m/44'/60'/n'`) extended public
+/// key, returning it with its derivation path relative to that key
+pub fn derive_address(
+    extended_public_key: &str,
+    index: u32,
+) -> Result<(String, String), Bip32Error> {
+    let account = parse_extended_public_key(extended_public_key)?;
+    let child = account.derive_child(0)?.derive_child(index)?;
+
+    let verifying_key: &coins_bip32::ecdsa::VerifyingKey = child.as_ref();
+    let public_key = verifying_key.to_encoded_point(false);
+    let hash = keccak256(&public_key.as_bytes()[1..]);
+    let address = Address::from_slice(&hash[12..]);
+
+    Ok((format!("{address:?}"), format!("0/{index}")))
+}
+
+pub fn parse_extended_private_key(extended_private_key: &str) -> Result<XPriv, Bip32Error> {
+    MainnetEncoder::xpriv_from_base58(extended_private_key)
+}
+
+/// Signer of the wallet at `derivation_path` (as returned by [`derive_address`]) of the account
+/// level extended private key matching the network's extended public key
+pub fn derive_signer(
+    extended_private_key: &XPriv,
+    derivation_path: &str,
+) -> Result<LocalWallet, Bip32Error> {
+    let mut key = extended_private_key.clone();
+    for index in derivation_path.split('/') {
+        let index = index
+            .parse::<u32>()
+            .map_err(|_| Bip32Error::MalformattedDerivation(derivation_path.to_owned()))?;
+        key = key.derive_child(index)?;
+    }
+
+    let signing_key: &coins_bip32::ecdsa::SigningKey = key.as_ref();
+    Ok(LocalWallet::from(signing_key.clone()))
+}
+
+pub fn convert_from_base_units(amount: U256, decimals: u32) -> Result<Decimal, ChainError> {
+    if amount > U256::from(u128::MAX) {
+        return Err(ChainError::AmountOutOfRange(amount.to_string()));
+    }
+
+    chain_watcher::from_base_units(amount.as_u128(), decimals)
+}
 
-    U256::from_dec_str(decimal_str).unwrap()
+fn convert_to_base_units(amount: Decimal, decimals: u32) -> Result<U256, ChainError> {
+    chain_watcher::to_base_units(amount, decimals).map(U256::from)
 }
@@ -0,0 +1,126 @@
+use super::user_service;
+use crate::entities::payment::{self, PaymentStatus};
+use crate::impl_crud;
+use crate::models::webhook::PaymentWebhookEvent;
+use crate::security::webhook::{self, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::{
+    entities::{prelude::*, webhook_delivery},
+    errors::InternalError,
+};
+use actix_web::web::Data;
+use chrono::Utc;
+use reqwest::header;
+use sea_orm::{ColumnTrait, DbConn, DeleteResult, EntityTrait, QueryFilter, QueryOrder, Set};
+use std::time::Duration;
+
+/// How many times a webhook is posted before giving up
+const MAX_DELIVERY_ATTEMPTS: i32 = 6;
+
+/// Delay before the first retry, doubled after every failed attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How long to wait for the merchant endpoint to respond
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl_crud!(WebhookDelivery, webhook_delivery, InternalError, i32);
+
+pub async fn find_all_by_payment_id(
+    db: &DbConn,
+    payment_id: i32,
+) -> Result<Vec<webhook_delivery::Model>, InternalError> {
+    Ok(WebhookDelivery::find()
+        .filter(webhook_delivery::Column::PaymentId.eq(payment_id))
+        .order_by_asc(webhook_delivery::Column::Id)
+        .all(db)
+        .await
+        .map_err(Into::<InternalError>::into)?)
+}
+
+pub fn spawn_webhook_dispatcher(
+    payment: payment::Model,
+    old_status: PaymentStatus,
+    db: Data<DbConn>,
+) {
+    tokio::spawn(async move {
+        let user = user_service::find_by_id(&db, payment.user_id)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let secret = user_service::get_or_create_webhook_secret(&db, user)
+            .await
+            .unwrap();
+
+        let event = PaymentWebhookEvent::new(&payment, old_status);
+        let payload = serde_json::to_string(&event).unwrap();
+
+        let client = reqwest::Client::new();
+        let mut retry_delay = INITIAL_RETRY_DELAY;
+
+        for attempt in 1..=MAX_DELIVERY_ATTEMPTS {
+            // signed again on every attempt, for retries to stay within receivers' tolerance
+            let timestamp = Utc::now().timestamp();
+            let signature = webhook::sign_payload(&secret, timestamp, &payload);
+
+            let response = client
+                .post(&payment.callback_url)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(TIMESTAMP_HEADER, timestamp)
+                .header(SIGNATURE_HEADER, signature)
+                .timeout(REQUEST_TIMEOUT)
+                .body(payload.clone())
+                .send()
+                .await;
+
+            let (response_status, error) = match response {
+                Ok(response) if response.status().is_success() => {
+                    (Some(i32::from(response.status().as_u16())), None)
+                }
+                Ok(response) => (
+                    Some(i32::from(response.status().as_u16())),
+                    Some(format!("Unexpected response status: {}", response.status())),
+                ),
+                Err(err) => (None, Some(err.to_string())),
+            };
+            let delivered = error.is_none();
+
+            let delivery = webhook_delivery::ActiveModel {
+                payment_id: Set(payment.id),
+                url: Set(payment.callback_url.clone()),
+                old_status: Set(event.old_status.clone()),
+                new_status: Set(event.new_status.clone()),
+                payload: Set(payload.clone()),
+                attempt: Set(attempt),
+                response_status: Set(response_status),
+                error: Set(error),
+                delivered: Set(delivered),
+                created_at: Set(Utc::now().naive_utc()),
+                ..Default::default()
+            };
+            create(&db, delivery).await.unwrap();
+
+            if delivered {
+                log::info!(
+                    "Webhook of payment with id {} delivered on attempt {attempt}",
+                    payment.id
+                );
+                return;
+            }
+
+            log::warn!(
+                "Webhook of payment with id {} failed on attempt {attempt}",
+                payment.id
+            );
+
+            if attempt < MAX_DELIVERY_ATTEMPTS {
+                tokio::time::sleep(retry_delay).await;
+                retry_delay *= 2;
+            }
+        }
+
+        log::error!(
+            "Giving up webhook delivery of payment with id {} after {MAX_DELIVERY_ATTEMPTS} attempts",
+            payment.id
+        );
+    });
+}
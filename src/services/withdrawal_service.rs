@@ -0,0 +1,472 @@
+use crate::entities::user_transaction::{self, UserTransactionType};
+use crate::entities::withdrawal::WithdrawalStatus;
+use crate::errors::{PayoutError, WithdrawalError};
+use crate::impl_crud;
+use crate::models::dtos::WithdrawalFilter;
+use crate::models::merchant_event::MerchantEvent;
+use crate::models::pagination::{Page, Pagination};
+use crate::services::merchant_event_bus::MerchantEventBus;
+use crate::services::payout_provider::{PayoutProvider, PayoutStatus};
+use crate::services::user_transaction_service;
+use crate::{
+    entities::{prelude::*, withdrawal},
+    errors::InternalError,
+};
+use actix_web::web::Data;
+use chrono::{Duration, Utc};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbConn, DeleteResult, EntityTrait,
+    QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use std::collections::HashMap;
+
+/// Withdrawals which hold the funds they withdraw, until they are paid or rejected
+const HOLDING_STATUSES: [WithdrawalStatus; 3] = [
+    WithdrawalStatus::Pending,
+    WithdrawalStatus::Approved,
+    WithdrawalStatus::Processing,
+];
+
+impl_crud!(Withdrawal, withdrawal, InternalError, i32);
+
+pub async fn find_filtered_page(
+    db: &DbConn,
+    user_id: Option<i32>,
+    filter: &WithdrawalFilter,
+    pagination: &Pagination,
+) -> Result<Page<withdrawal::Model>, InternalError> {
+    let condition = Condition::all()
+        .add_option(user_id.map(|user_id| withdrawal::Column::UserId.eq(user_id)))
+        .add_option(
+            filter
+                .status
+                .clone()
+                .map(|status| withdrawal::Column::Status.eq(status)),
+        )
+        .add_option(
+            filter
+                .fiat_currency_id
+                .map(|fiat_currency_id| withdrawal::Column::FiatCurrencyId.eq(fiat_currency_id)),
+        );
+
+    find_page(db, condition, withdrawal::Column::CreatedAt, pagination).await
+}
+
+/// What the withdrawals in progress of the user hold, by fiat currency
+pub async fn get_held_amounts<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+) -> Result<HashMap<i32, Decimal>, InternalError> {
+    let withdrawals = Withdrawal::find()
+        .filter(withdrawal::Column::UserId.eq(user_id))
+        .filter(withdrawal::Column::Status.is_in(HOLDING_STATUSES))
+        .all(db)
+        .await?;
+
+    let mut held_amounts = HashMap::new();
+    for withdrawal in withdrawals {
+        *held_amounts
+            .entry(withdrawal.fiat_currency_id)
+            .or_insert(Decimal::ZERO) += withdrawal.amount;
+    }
+
+    Ok(held_amounts)
+}
+
+/// What the user can withdraw and what their withdrawals in progress hold, by fiat currency
+pub async fn get_available_balance<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+) -> Result<HashMap<i32, (Decimal, Decimal)>, InternalError> {
+    let balance = user_transaction_service::get_user_balance(db, user_id).await?;
+    let held_amounts = get_held_amounts(db, user_id).await?;
+
+    let mut available_balance = balance
+        .into_iter()
+        .map(|(fiat_currency_id, balance)| (fiat_currency_id, (balance, Decimal::ZERO)))
+        .collect::<HashMap<_, _>>();
+    for (fiat_currency_id, held) in held_amounts {
+        let (balance, held_amount) = available_balance
+            .entry(fiat_currency_id)
+            .or_insert((Decimal::ZERO, Decimal::ZERO));
+        *balance -= held;
+        *held_amount = held;
+    }
+
+    Ok(available_balance)
+}
+
+/// Create a pending withdrawal, holding its amount from the balance of the user.
+///
+/// The row of the user is locked while the balance is checked, so concurrent requests of the
+/// same user can never withdraw more than their balance together.
+pub async fn request(
+    db: &DbConn,
+    mut withdrawal: withdrawal::ActiveModel,
+) -> Result<withdrawal::Model, WithdrawalError> {
+    let user_id = *withdrawal.user_id.as_ref();
+    let fiat_currency_id = *withdrawal.fiat_currency_id.as_ref();
+    let amount = *withdrawal.amount.as_ref();
+
+    if amount <= Decimal::ZERO {
+        return Err(WithdrawalError::NonPositiveAmount);
+    }
+
+    let txn = db.begin().await.map_err(InternalError::from)?;
+
+    User::find_by_id(user_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(InternalError::from)?;
+
+    let available = get_available_balance(&txn, user_id)
+        .await?
+        .get(&fiat_currency_id)
+        .map_or(Decimal::ZERO, |(balance, _)| *balance);
+    if amount > available {
+        return Err(WithdrawalError::NotEnoughBalance(
+            available.max(Decimal::ZERO),
+        ));
+    }
+
+    withdrawal.status = Set(WithdrawalStatus::Pending);
+    withdrawal.created_at = Set(Utc::now().naive_utc());
+
+    let withdrawal = withdrawal.insert(&txn).await.map_err(InternalError::from)?;
+    txn.commit().await.map_err(InternalError::from)?;
+
+    Ok(withdrawal)
+}
+
+/// Approve the pending withdrawal, by a conditional update so that a withdrawal rejected in the
+/// meantime is never paid. Returns whether it was approved.
+pub async fn approve(db: &DbConn, id: i32) -> Result<bool, InternalError> {
+    let approved = Withdrawal::update_many()
+        .col_expr(
+            withdrawal::Column::Status,
+            Expr::value(WithdrawalStatus::Approved),
+        )
+        .col_expr(
+            withdrawal::Column::ApprovedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(withdrawal::Column::Id.eq(id))
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Pending))
+        .exec(db)
+        .await?;
+
+    Ok(approved.rows_affected == 1)
+}
+
+/// Reject the withdrawal if it is still in the `from` status, releasing what it holds. Returns
+/// whether it was rejected.
+pub async fn reject(
+    db: &DbConn,
+    id: i32,
+    from: WithdrawalStatus,
+    reason: String,
+) -> Result<bool, InternalError> {
+    let rejected = Withdrawal::update_many()
+        .col_expr(
+            withdrawal::Column::Status,
+            Expr::value(WithdrawalStatus::Rejected),
+        )
+        .col_expr(withdrawal::Column::RejectionReason, Expr::value(reason))
+        .col_expr(
+            withdrawal::Column::ProcessedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(withdrawal::Column::Id.eq(id))
+        .filter(withdrawal::Column::Status.eq(from))
+        .exec(db)
+        .await?;
+
+    Ok(rejected.rows_affected == 1)
+}
+
+/// Take the approved withdrawal for submitting it to the payout provider, by a conditional
+/// update to `PROCESSING`, so that it is never submitted twice. Returns whether it was taken.
+pub async fn claim_for_payout(db: &DbConn, id: i32) -> Result<bool, InternalError> {
+    let claimed = Withdrawal::update_many()
+        .col_expr(
+            withdrawal::Column::Status,
+            Expr::value(WithdrawalStatus::Processing),
+        )
+        .col_expr(
+            withdrawal::Column::SubmittedAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(withdrawal::Column::Id.eq(id))
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Approved))
+        .exec(db)
+        .await?;
+
+    Ok(claimed.rows_affected == 1)
+}
+
+pub async fn record_payout_reference(
+    db: &DbConn,
+    id: i32,
+    payout_reference: String,
+) -> Result<(), InternalError> {
+    Withdrawal::update_many()
+        .col_expr(
+            withdrawal::Column::PayoutReference,
+            Expr::value(payout_reference),
+        )
+        .filter(withdrawal::Column::Id.eq(id))
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Processing))
+        .filter(withdrawal::Column::PayoutReference.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Put a withdrawal which failed to be submitted back to `APPROVED`, to submit it again later
+pub async fn release(db: &DbConn, id: i32) -> Result<(), InternalError> {
+    Withdrawal::update_many()
+        .col_expr(
+            withdrawal::Column::Status,
+            Expr::value(WithdrawalStatus::Approved),
+        )
+        .filter(withdrawal::Column::Id.eq(id))
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Processing))
+        .filter(withdrawal::Column::PayoutReference.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Mark the processing withdrawal as paid and record the withdrawal transaction of the user's
+/// balance, in the same transaction so that its amount is never held and withdrawn at once.
+/// Returns the transaction, or none when the withdrawal was not processing anymore.
+pub async fn settle_paid(
+    db: &DbConn,
+    withdrawal: &withdrawal::Model,
+) -> Result<Option<user_transaction::Model>, InternalError> {
+    let txn = db.begin().await?;
+
+    let now = Utc::now().naive_utc();
+    let user_transaction = user_transaction::ActiveModel {
+        user_id: Set(withdrawal.user_id),
+        typ: Set(UserTransactionType::Withdrawal),
+        amount: Set(withdrawal.amount),
+        fiat_currency_id: Set(withdrawal.fiat_currency_id),
+        created_at: Set(now),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+
+    let paid = Withdrawal::update_many()
+        .col_expr(
+            withdrawal::Column::Status,
+            Expr::value(WithdrawalStatus::Paid),
+        )
+        .col_expr(
+            withdrawal::Column::UserTransactionId,
+            Expr::value(user_transaction.id),
+        )
+        .col_expr(withdrawal::Column::ProcessedAt, Expr::value(now))
+        .filter(withdrawal::Column::Id.eq(withdrawal.id))
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Processing))
+        .exec(&txn)
+        .await?;
+
+    if paid.rows_affected == 0 {
+        txn.rollback().await?;
+        return Ok(None);
+    }
+
+    txn.commit().await?;
+
+    Ok(Some(user_transaction))
+}
+
+/// How often approved withdrawals are paid out and processing ones followed
+const PAYOUT_INTERVAL_IN_SECONDS: i64 = 30;
+
+/// How long a withdrawal stays processing without payout reference before its submission is
+/// taken for interrupted, as when the gateway stops while submitting it
+const SUBMISSION_TIMEOUT_IN_SECONDS: i64 = 300;
+
+pub fn spawn_payout_job(
+    payout_provider: Data<dyn PayoutProvider>,
+    merchant_events: Data<MerchantEventBus>,
+    db: Data<DbConn>,
+) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) =
+                process_payouts(payout_provider.get_ref(), &merchant_events, &db).await
+            {
+                log::error!("Failed to process payouts: {err}");
+            }
+
+            tokio::time::sleep(
+                Duration::seconds(PAYOUT_INTERVAL_IN_SECONDS)
+                    .to_std()
+                    .unwrap(),
+            )
+            .await;
+        }
+    });
+}
+
+/// Submit approved withdrawals to the payout provider, and settle the processing ones once their
+/// payout is paid or failed.
+///
+/// Every status change is a conditional update, so running this on several gateway instances at
+/// once still submits and settles every withdrawal exactly once. Submissions interrupted before
+/// their payout reference is recorded are recovered first.
+pub async fn process_payouts(
+    payout_provider: &dyn PayoutProvider,
+    merchant_events: &MerchantEventBus,
+    db: &DbConn,
+) -> Result<(), InternalError> {
+    recover_interrupted_submissions(payout_provider, db).await?;
+
+    let approved_withdrawals = Withdrawal::find()
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Approved))
+        .all(db)
+        .await?;
+
+    for withdrawal in approved_withdrawals {
+        if !claim_for_payout(db, withdrawal.id).await? {
+            continue;
+        }
+
+        match payout_provider.submit(&withdrawal).await {
+            Ok(payout_reference) => {
+                record_payout_reference(db, withdrawal.id, payout_reference).await?;
+                log::info!("Withdrawal with id {} is submitted", withdrawal.id);
+            }
+            Err(PayoutError::Refused(reason)) => {
+                reject(db, withdrawal.id, WithdrawalStatus::Processing, reason).await?;
+                log::info!("Payout of withdrawal with id {} is refused", withdrawal.id);
+            }
+            Err(err) => {
+                release(db, withdrawal.id).await?;
+                log::error!(
+                    "Failed to submit payout of withdrawal with id {}: {err}",
+                    withdrawal.id
+                );
+                continue;
+            }
+        }
+
+        publish_status_change(merchant_events, db, withdrawal.id).await?;
+    }
+
+    let processing_withdrawals = Withdrawal::find()
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Processing))
+        .filter(withdrawal::Column::PayoutReference.is_not_null())
+        .all(db)
+        .await?;
+
+    for withdrawal in processing_withdrawals {
+        // unwrap: only withdrawals with a payout reference are followed
+        let payout_reference = withdrawal.payout_reference.clone().unwrap();
+
+        let status = match payout_provider.status(&payout_reference).await {
+            Ok(status) => status,
+            Err(err) => {
+                log::error!(
+                    "Failed to get payout status of withdrawal with id {}: {err}",
+                    withdrawal.id
+                );
+                continue;
+            }
+        };
+
+        match status {
+            PayoutStatus::Processing => continue,
+            PayoutStatus::Paid => {
+                let Some(user_transaction) = settle_paid(db, &withdrawal).await? else {
+                    continue;
+                };
+                log::info!("Withdrawal with id {} is paid", withdrawal.id);
+
+                merchant_events.publish(
+                    withdrawal.user_id,
+                    MerchantEvent::UserTransactionCreated(user_transaction),
+                );
+            }
+            PayoutStatus::Failed(reason) => {
+                if !reject(db, withdrawal.id, WithdrawalStatus::Processing, reason).await? {
+                    continue;
+                }
+                log::info!("Payout of withdrawal with id {} is failed", withdrawal.id);
+            }
+        }
+
+        publish_status_change(merchant_events, db, withdrawal.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Record the payout reference of the withdrawals processing for too long without one, as the
+/// payout provider knows it by their id, or put them back to `APPROVED` when it knows no payout
+/// of theirs, to submit them again
+async fn recover_interrupted_submissions(
+    payout_provider: &dyn PayoutProvider,
+    db: &DbConn,
+) -> Result<(), InternalError> {
+    let stale_from = Utc::now().naive_utc() - Duration::seconds(SUBMISSION_TIMEOUT_IN_SECONDS);
+    let interrupted_withdrawals = Withdrawal::find()
+        .filter(withdrawal::Column::Status.eq(WithdrawalStatus::Processing))
+        .filter(withdrawal::Column::PayoutReference.is_null())
+        .filter(
+            Condition::any()
+                .add(withdrawal::Column::SubmittedAt.is_null())
+                .add(withdrawal::Column::SubmittedAt.lt(stale_from)),
+        )
+        .all(db)
+        .await?;
+
+    for withdrawal in interrupted_withdrawals {
+        match payout_provider.find_by_withdrawal_id(withdrawal.id).await {
+            Ok(Some(payout_reference)) => {
+                record_payout_reference(db, withdrawal.id, payout_reference).await?;
+                log::info!(
+                    "Payout reference of withdrawal with id {} is recovered",
+                    withdrawal.id
+                );
+            }
+            Ok(None) => {
+                release(db, withdrawal.id).await?;
+                log::warn!(
+                    "Interrupted submission of withdrawal with id {} is released",
+                    withdrawal.id
+                );
+            }
+            Err(err) => log::error!(
+                "Failed to find payout of withdrawal with id {}: {err}",
+                withdrawal.id
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+async fn publish_status_change(
+    merchant_events: &MerchantEventBus,
+    db: &DbConn,
+    id: i32,
+) -> Result<(), InternalError> {
+    if let Some(withdrawal) = find_by_id(db, id).await? {
+        merchant_events.publish(
+            withdrawal.user_id,
+            MerchantEvent::WithdrawalStatusChanged(withdrawal),
+        );
+    }
+
+    Ok(())
+}
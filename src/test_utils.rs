@@ -0,0 +1,109 @@
+//! Helpers of the tests which need services running beside them. They are ignored by default,
+//! see the README to run them.
+
+use crate::entities::network::{self, NetworkKind};
+use crate::entities::payment::{self, PaymentStatus};
+use crate::entities::user::{self, UserRole};
+use crate::entities::{crypto_currency, fiat_currency};
+use chrono::{Duration, Utc};
+use migration::{Migrator, MigratorTrait};
+use sea_orm::prelude::Decimal;
+use sea_orm::{ActiveModelTrait, Database, DbConn, Set};
+
+/// Connect to the database of `TEST_DATABASE_URL` and bring its schema up to date
+pub async fn setup_db() -> DbConn {
+    let database_url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL should be set");
+
+    let db = Database::connect(database_url)
+        .await
+        .expect("Failed to connect to the test database");
+    Migrator::up(&db, None)
+        .await
+        .expect("Failed to migrate the test database");
+
+    db
+}
+
+/// Create a network reached at `url`, for both JSON-RPC and subscriptions
+pub async fn create_network(db: &DbConn, kind: NetworkKind, url: &str) -> network::Model {
+    network::ActiveModel {
+        name: Set(format!("test-{}", chrono::Utc::now().timestamp_nanos())),
+        http_address_url: Set(url.to_owned()),
+        websocket_address_url: Set(url.replacen("http", "ws", 1)),
+        required_confirmations: Set(1),
+        next_derivation_index: Set(0),
+        kind: Set(kind),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to create the test network")
+}
+
+/// Create a currency of the network, a token when it has a contract address
+pub async fn create_crypto_currency(
+    db: &DbConn,
+    network: &network::Model,
+    decimals: i32,
+    contract_address: Option<String>,
+) -> crypto_currency::Model {
+    crypto_currency::ActiveModel {
+        name: Set(format!("test-{}", Utc::now().timestamp_nanos())),
+        symbol: Set("TEST".to_owned()),
+        network_id: Set(network.id),
+        contract_address: Set(contract_address),
+        decimals: Set(decimals),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to create the test crypto currency")
+}
+
+/// Create a waiting payment of a new merchant, which chose the currency for the crypto amount
+pub async fn create_payment(
+    db: &DbConn,
+    crypto_currency: &crypto_currency::Model,
+    crypto_amount: Decimal,
+) -> payment::Model {
+    let now = Utc::now().naive_utc();
+
+    let user = user::ActiveModel {
+        username: Set(format!("test-{}", Utc::now().timestamp_nanos())),
+        password_hash: Set(String::new()),
+        role: Set(UserRole::User),
+        created_at: Set(now),
+        token_version: Set(0),
+        disabled: Set(false),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to create the test user");
+
+    let fiat_currency = fiat_currency::ActiveModel {
+        name: Set("Test dollar".to_owned()),
+        symbol: Set("TSD".to_owned()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to create the test fiat currency");
+
+    payment::ActiveModel {
+        user_id: Set(user.id),
+        fiat_currency_id: Set(fiat_currency.id),
+        amount: Set(Decimal::ONE_HUNDRED),
+        callback_url: Set("http://127.0.0.1/callback".to_owned()),
+        seller_order_id: Set(format!("test-{}", Utc::now().timestamp_nanos())),
+        status: Set(PaymentStatus::Waiting),
+        crypto_currency_id: Set(Some(crypto_currency.id)),
+        crypto_amount: Set(Some(crypto_amount)),
+        created_at: Set(now),
+        expired_at: Set(now + Duration::hours(1)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .expect("Failed to create the test payment")
+}